@@ -0,0 +1,54 @@
+-- 1 つの DB ファイルに複数の tierlist を持てるよう、各テーブルに list_id を追加する
+-- 既存のデータは list_id = 1 の tierlist として引き継ぐ
+
+ALTER TABLE items_pos RENAME TO items_pos_old;
+ALTER TABLE items RENAME TO items_old;
+ALTER TABLE tiers RENAME TO tiers_old;
+ALTER TABLE tierlist RENAME TO tierlist_old;
+
+CREATE TABLE tierlist (
+    id INTEGER PRIMARY KEY NOT NULL,
+    title TEXT NOT NULL
+);
+
+CREATE TABLE tiers (
+    list_id INTEGER NOT NULL,
+    id INTEGER NOT NULL,
+    pos INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    PRIMARY KEY (list_id, id),
+    FOREIGN KEY (list_id) REFERENCES tierlist (id) ON DELETE CASCADE
+);
+
+CREATE TABLE items (
+    list_id INTEGER NOT NULL,
+    id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    thumb NONE,  -- blob
+    memo TEXT NOT NULL,
+    PRIMARY KEY (list_id, id),
+    FOREIGN KEY (list_id) REFERENCES tierlist (id) ON DELETE CASCADE
+);
+
+CREATE TABLE items_pos (
+    list_id INTEGER NOT NULL,
+    item_id INTEGER NOT NULL,
+    tier_id INTEGER NOT NULL,
+    pos INTEGER NOT NULL,
+    PRIMARY KEY (list_id, item_id, tier_id),
+    FOREIGN KEY (list_id, item_id) REFERENCES items (list_id, id) ON DELETE CASCADE,
+    FOREIGN KEY (list_id, tier_id) REFERENCES tiers (list_id, id) ON DELETE CASCADE
+);
+
+INSERT INTO tierlist(id, title) SELECT 1, title FROM tierlist_old LIMIT 1;
+INSERT INTO tiers(list_id, id, pos, title) SELECT 1, id, pos, title FROM tiers_old;
+INSERT INTO items(list_id, id, name, url, thumb, memo)
+    SELECT 1, id, name, url, thumb, memo FROM items_old;
+INSERT INTO items_pos(list_id, item_id, tier_id, pos)
+    SELECT 1, item_id, tier_id, pos FROM items_pos_old;
+
+DROP TABLE items_pos_old;
+DROP TABLE items_old;
+DROP TABLE tiers_old;
+DROP TABLE tierlist_old;
@@ -0,0 +1,18 @@
+-- tier ごとの色、文字色、アイコン
+-- 既存の tier には上から順に S, A, B, ... の色を付ける (tierlist::TIER_PALETTE と同じ)
+
+ALTER TABLE tiers ADD COLUMN color TEXT NOT NULL DEFAULT '#ff7f7f';
+ALTER TABLE tiers ADD COLUMN text_color TEXT;
+ALTER TABLE tiers ADD COLUMN icon TEXT;
+
+UPDATE tiers SET color = CASE (
+    SELECT COUNT(*) FROM tiers AS t WHERE t.list_id = tiers.list_id AND t.pos < tiers.pos
+) % 7
+    WHEN 0 THEN '#ff7f7f'
+    WHEN 1 THEN '#ffbf7f'
+    WHEN 2 THEN '#ffdf7f'
+    WHEN 3 THEN '#ffff7f'
+    WHEN 4 THEN '#bfff7f'
+    WHEN 5 THEN '#7fff7f'
+    ELSE '#7fffff'
+END;
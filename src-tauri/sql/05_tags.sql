@@ -0,0 +1,19 @@
+-- アイテムに付けるタグ。名前は tierlist の中で重複しない
+
+CREATE TABLE tags (
+    list_id INTEGER NOT NULL,
+    id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    PRIMARY KEY (list_id, id),
+    UNIQUE (list_id, name),
+    FOREIGN KEY (list_id) REFERENCES tierlist (id) ON DELETE CASCADE
+);
+
+CREATE TABLE item_tags (
+    list_id INTEGER NOT NULL,
+    item_id INTEGER NOT NULL,
+    tag_id INTEGER NOT NULL,
+    PRIMARY KEY (list_id, item_id, tag_id),
+    FOREIGN KEY (list_id, item_id) REFERENCES items (list_id, id) ON DELETE CASCADE,
+    FOREIGN KEY (list_id, tag_id) REFERENCES tags (list_id, id) ON DELETE CASCADE
+);
@@ -0,0 +1,27 @@
+-- アイテムの名前とメモの全文検索用の索引
+-- 日本語は単語に区切れないので trigram で索引を作る (3 文字より短い語は LIKE で探す)
+-- items の rowid は VACUUM で変わりうるので、list_id と item_id を一緒に持つ
+
+CREATE VIRTUAL TABLE items_fts USING fts5(
+    list_id UNINDEXED,
+    item_id UNINDEXED,
+    name,
+    memo,
+    tokenize = 'trigram'
+);
+
+INSERT INTO items_fts(list_id, item_id, name, memo) SELECT list_id, id, name, memo FROM items;
+
+CREATE TRIGGER items_fts_insert AFTER INSERT ON items BEGIN
+    INSERT INTO items_fts(list_id, item_id, name, memo)
+        VALUES (new.list_id, new.id, new.name, new.memo);
+END;
+
+CREATE TRIGGER items_fts_delete AFTER DELETE ON items BEGIN
+    DELETE FROM items_fts WHERE list_id = old.list_id AND item_id = old.id;
+END;
+
+CREATE TRIGGER items_fts_update AFTER UPDATE OF name, memo ON items BEGIN
+    UPDATE items_fts SET name = new.name, memo = new.memo
+        WHERE list_id = old.list_id AND item_id = old.id;
+END;
@@ -0,0 +1,21 @@
+-- tierlist ごとに定義する、アイテムに付ける値 (公開年、作者など) とその値
+
+CREATE TABLE fields (
+    list_id INTEGER NOT NULL,
+    id INTEGER NOT NULL,
+    pos INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    type TEXT NOT NULL,  -- text, number, date, url, boolean
+    PRIMARY KEY (list_id, id),
+    FOREIGN KEY (list_id) REFERENCES tierlist (id) ON DELETE CASCADE
+);
+
+CREATE TABLE item_fields (
+    list_id INTEGER NOT NULL,
+    item_id INTEGER NOT NULL,
+    field_id INTEGER NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (list_id, item_id, field_id),
+    FOREIGN KEY (list_id, item_id) REFERENCES items (list_id, id) ON DELETE CASCADE,
+    FOREIGN KEY (list_id, field_id) REFERENCES fields (list_id, id) ON DELETE CASCADE
+);
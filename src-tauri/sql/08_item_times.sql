@@ -0,0 +1,8 @@
+-- アイテムを追加した、内容を変えた、別の tier に移した時刻 (UNIX 時間、秒)
+-- 既存のアイテムはいつのものかわからないので 0 にしておく
+
+ALTER TABLE items ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE items ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE items ADD COLUMN moved_at INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX items_created_at ON items(list_id, created_at);
@@ -0,0 +1,20 @@
+-- アイテムを置いた場所の履歴。保存するたびに、前回の保存から tier が変わったアイテムの分を追記する
+-- tier が消されたり名前が変わったりしても読めるように、そのときの名前も残す
+
+CREATE TABLE placement_log (
+    id INTEGER PRIMARY KEY,
+    list_id INTEGER NOT NULL,
+    item_id INTEGER NOT NULL,
+    item_name TEXT NOT NULL,
+    kind TEXT NOT NULL,  -- added, moved, removed
+    from_tier INTEGER,  -- NULL ならプール (added なら使わない)
+    from_title TEXT,
+    to_tier INTEGER,  -- NULL ならプール (removed なら使わない)
+    to_title TEXT,
+    pos INTEGER,  -- 移した先の tier での位置
+    at INTEGER NOT NULL,  -- UNIX 時間 (秒)
+    FOREIGN KEY (list_id) REFERENCES tierlist (id) ON DELETE CASCADE
+);
+
+CREATE INDEX placement_log_item ON placement_log(list_id, item_id);
+CREATE INDEX placement_log_at ON placement_log(list_id, at);
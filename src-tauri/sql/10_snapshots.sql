@@ -0,0 +1,12 @@
+-- tierlist のある時点の状態に名前を付けて残したもの
+-- data は tiers、アイテムの置き場所と内容を TierList の JSON にしたもの (サムネイルは含めない)
+
+CREATE TABLE snapshots (
+    id INTEGER PRIMARY KEY,
+    list_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    created_at INTEGER NOT NULL,  -- UNIX 時間 (秒)
+    data TEXT NOT NULL,
+    UNIQUE (list_id, name),
+    FOREIGN KEY (list_id) REFERENCES tierlist (id) ON DELETE CASCADE
+);
@@ -0,0 +1,10 @@
+-- snapshots に残したアイテムのサムネイル。残したあとでアイテムが消されても戻せるように、
+-- items とは別に画像そのものを持っておく
+
+CREATE TABLE snapshot_thumbs (
+    snapshot_id INTEGER NOT NULL,
+    item_id INTEGER NOT NULL,
+    thumb BLOB NOT NULL,
+    PRIMARY KEY (snapshot_id, item_id),
+    FOREIGN KEY (snapshot_id) REFERENCES snapshots (id) ON DELETE CASCADE
+);
@@ -0,0 +1,448 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tauri::{async_runtime::Mutex, AppHandle, Manager};
+use tempdir::TempDir;
+use tokio::fs;
+
+use crate::{
+    db,
+    export::{self, ExportedTierList},
+    recent,
+    tierlist::{ListId, TierList},
+};
+
+const RECOVERY_FILE_NAME: &str = "recovery.json";
+const AUTOSAVE_OPTIONS_NAME: &str = "autosave_options.json";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutosaveOptions {
+    // 0 なら自動保存しない
+    pub interval_secs: u64,
+}
+
+impl Default for AutosaveOptions {
+    fn default() -> Self {
+        AutosaveOptions { interval_secs: 60 }
+    }
+}
+
+// DB に保存できなかった tierlist の退避先
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recovery {
+    // UNIX 時間 (秒)
+    pub saved_at: u64,
+    // 編集していた DB ファイルと tierlist (一度も保存していなければ None)
+    pub path: Option<String>,
+    pub list_id: Option<ListId>,
+    pub tierlist: ExportedTierList,
+}
+
+impl Recovery {
+    // 元のファイルが退避した後に保存されていれば、退避したものは古い
+    pub async fn is_newer_than_file(&self) -> bool {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return true,
+        };
+        let modified = match fs::metadata(path).await.and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(_) => return true,
+        };
+        let modified = modified
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.saved_at > modified
+    }
+}
+
+// 起動時にフロントエンドに見せる、退避された tierlist の情報
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryInfo {
+    pub saved_at: u64,
+    pub path: Option<String>,
+    pub list_id: Option<ListId>,
+    pub title: String,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// 設定は最近開いたファイルと同じく config_dir に置く
+pub fn options_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(AUTOSAVE_OPTIONS_NAME)
+}
+
+// 読めなかった場合は既定の設定として扱う
+pub async fn load_options(config_dir: &Path) -> AutosaveOptions {
+    match fs::read(options_path(config_dir)).await {
+        Ok(buf) => serde_json::from_slice(&buf).unwrap_or_default(),
+        Err(_) => AutosaveOptions::default(),
+    }
+}
+
+pub async fn save_options(config_dir: &Path, options: &AutosaveOptions) -> Result<(), String> {
+    fs::create_dir_all(config_dir)
+        .await
+        .map_err(|e| e.to_string())?;
+    let buf = serde_json::to_vec_pretty(options).map_err(|e| e.to_string())?;
+    fs::write(options_path(config_dir), buf)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+pub fn recovery_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(RECOVERY_FILE_NAME)
+}
+
+// 書き込み途中で落ちても前の退避ファイルが壊れないよう、別名で書いてから置き換える
+pub async fn write_recovery(data_dir: &Path, recovery: &Recovery) -> Result<(), String> {
+    fs::create_dir_all(data_dir)
+        .await
+        .map_err(|e| e.to_string())?;
+    let buf = serde_json::to_vec(recovery).map_err(|e| e.to_string())?;
+    let tmp_path = data_dir.join(format!("{}.tmp", RECOVERY_FILE_NAME));
+    fs::write(&tmp_path, buf).await.map_err(|e| e.to_string())?;
+    fs::rename(&tmp_path, recovery_path(data_dir))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 読めなかった場合は退避ファイルがないものとして扱う
+pub async fn read_recovery(data_dir: &Path) -> Option<Recovery> {
+    let buf = fs::read(recovery_path(data_dir)).await.ok()?;
+    serde_json::from_slice(&buf).ok()
+}
+
+pub async fn remove_recovery(data_dir: &Path) {
+    let _ = fs::remove_file(recovery_path(data_dir)).await;
+}
+
+pub fn data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| "Data directory not found".to_owned())
+}
+
+// DB に書き込めなかった tierlist と、編集していたファイルと tierlist
+struct Unsaved {
+    tierlist: TierList,
+    path: Option<String>,
+    list_id: Option<ListId>,
+}
+
+// 前回から変わっていれば、開いている DB の編集中の tierlist に書き込む
+// 書き込み終わるまで pool の lock を持ち (write_tierlist_to_db と同じ pool → tierlist の順)、
+// tierlist と cur_list はその間に一緒に読むので、別の tierlist に切り替えている途中の
+// 組み合わせで書き込むことはない
+// 書き込んだら Ok(Some(書き込んだもの))、変わっていなければ Ok(None)
+// 書き込んだサムネイルは、編集中の tierlist でも展開先のパスに向ける
+async fn save_to_db(
+    pool: &Mutex<Option<SqlitePool>>,
+    tierlist: &Mutex<TierList>,
+    cur_file: &Mutex<Option<String>>,
+    cur_list: &Mutex<Option<ListId>>,
+    thumb_dir: &Path,
+    last_saved: Option<&TierList>,
+) -> Result<Option<TierList>, Unsaved> {
+    let pool = pool.lock().await;
+    let (mut saving, list_id) = {
+        let tierlist = tierlist.lock().await;
+        (tierlist.clone(), *cur_list.lock().await)
+    };
+    if last_saved == Some(&saving) {
+        return Ok(None);
+    }
+    if let (Some(list_id), Some(pool)) = (list_id, pool.as_ref()) {
+        if let Ok(saved) = db::write_tierlist(pool, list_id, &saving, thumb_dir).await {
+            db::use_saved_thumbs(&mut saving, &saved);
+            db::use_saved_thumbs(&mut *tierlist.lock().await, &saved);
+            return Ok(Some(saving));
+        }
+    }
+    Err(Unsaved {
+        tierlist: saving,
+        path: cur_file.lock().await.clone(),
+        list_id,
+    })
+}
+
+// 開いている DB の tierlist を編集中ならそこへ書き込み、できなければ退避ファイルに書く
+async fn autosave(app: &AppHandle, last_saved: &mut Option<TierList>) -> Result<(), String> {
+    let data_dir = data_dir(app)?;
+    let thumb_dir = app.state::<TempDir>();
+    let saved = save_to_db(
+        &app.state::<Mutex<Option<SqlitePool>>>(),
+        &app.state::<Mutex<TierList>>(),
+        &app.state::<Mutex<Option<String>>>(),
+        &app.state::<Mutex<Option<ListId>>>(),
+        thumb_dir.path(),
+        last_saved.as_ref(),
+    )
+    .await;
+    let unsaved = match saved {
+        Ok(Some(saved)) => {
+            remove_recovery(&data_dir).await;
+            *last_saved = Some(saved);
+            return Ok(());
+        }
+        Ok(None) => return Ok(()),
+        Err(unsaved) => unsaved,
+    };
+
+    // 何も編集していない新規の tierlist は退避しない
+    if unsaved.list_id.is_some() || unsaved.tierlist != TierList::with_default_tiers() {
+        let recovery = Recovery {
+            saved_at: now_secs(),
+            path: unsaved.path,
+            list_id: unsaved.list_id,
+            tierlist: export::export_tierlist(&unsaved.tierlist).await?,
+        };
+        write_recovery(&data_dir, &recovery).await?;
+    }
+    *last_saved = Some(unsaved.tierlist);
+    Ok(())
+}
+
+// 設定された間隔で、前回から変わっていれば自動保存する
+// 間隔の設定を変えたらすぐ反映されるように、設定は 1 秒ごとに確かめる
+pub async fn run(app: AppHandle) {
+    let mut last_saved: Option<TierList> = None;
+    let mut waited = 0;
+    loop {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let interval = app
+            .state::<Mutex<AutosaveOptions>>()
+            .lock()
+            .await
+            .interval_secs;
+        if interval == 0 {
+            waited = 0;
+            continue;
+        }
+        waited += 1;
+        if waited < interval {
+            continue;
+        }
+        waited = 0;
+        let _ = autosave(&app, &mut last_saved).await;
+    }
+}
+
+pub mod commands {
+    use tauri::State;
+
+    use super::*;
+    use crate::history::History;
+
+    #[tauri::command]
+    pub async fn get_autosave_options(
+        options: State<'_, Mutex<AutosaveOptions>>,
+    ) -> Result<AutosaveOptions, String> {
+        Ok(options.lock().await.clone())
+    }
+
+    #[tauri::command]
+    pub async fn set_autosave_options(
+        app: AppHandle,
+        options: State<'_, Mutex<AutosaveOptions>>,
+        new_options: AutosaveOptions,
+    ) -> Result<(), String> {
+        let mut options = options.lock().await;
+        save_options(&recent::config_dir(&app)?, &new_options).await?;
+        *options = new_options;
+        Ok(())
+    }
+
+    // 保存済みのファイルより新しい退避ファイルがあれば、その情報を返す
+    #[tauri::command]
+    pub async fn check_recovery(app: AppHandle) -> Result<Option<RecoveryInfo>, String> {
+        let recovery = match read_recovery(&data_dir(&app)?).await {
+            Some(recovery) => recovery,
+            None => return Ok(None),
+        };
+        if !recovery.is_newer_than_file().await {
+            return Ok(None);
+        }
+        Ok(Some(RecoveryInfo {
+            saved_at: recovery.saved_at,
+            path: recovery.path,
+            list_id: recovery.list_id,
+            title: recovery.tierlist.title,
+        }))
+    }
+
+    // 退避ファイルの tierlist を、まだ保存していない tierlist として読み込む
+    #[tauri::command]
+    pub async fn restore_recovery(
+        app: AppHandle,
+        img_dir: State<'_, TempDir>,
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+    ) -> Result<TierList, String> {
+        let data_dir = data_dir(&app)?;
+        let recovery = read_recovery(&data_dir)
+            .await
+            .ok_or("Recovery file not found")?;
+        let restored = export::import_tierlist(recovery.tierlist, img_dir.path()).await?;
+        // 自動保存が新しい tierlist を前の cur_list に書き込まないように、lock を持ったまま切り替える
+        let mut tierlist = tierlist.lock().await;
+        *tierlist = restored;
+        history.lock().await.clear();
+        *cur_list.lock().await = None;
+        remove_recovery(&data_dir).await;
+        Ok(tierlist.clone())
+    }
+
+    #[tauri::command]
+    pub async fn discard_recovery(app: AppHandle) -> Result<(), String> {
+        remove_recovery(&data_dir(&app)?).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn recovery(path: Option<String>, saved_at: u64) -> Recovery {
+        let mut tierlist = TierList::empty();
+        tierlist.title = "unsaved".to_owned();
+        Recovery {
+            saved_at,
+            path,
+            list_id: None,
+            tierlist: export::export_tierlist(&tierlist).await.unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn options_saved_and_loaded() {
+        let config_dir = TempDir::new("autosave_config").unwrap();
+        assert_eq!(
+            load_options(config_dir.path()).await,
+            AutosaveOptions::default()
+        );
+        let options = AutosaveOptions { interval_secs: 0 };
+        save_options(config_dir.path(), &options).await.unwrap();
+        assert_eq!(load_options(config_dir.path()).await, options);
+    }
+
+    #[tokio::test]
+    async fn write_and_read_recovery() {
+        let dir = TempDir::new("recovery_test").unwrap();
+        assert_eq!(read_recovery(dir.path()).await, None);
+
+        let data_dir = dir.path().join("data");
+        let written = recovery(None, now_secs()).await;
+        write_recovery(&data_dir, &written).await.unwrap();
+        assert_eq!(read_recovery(&data_dir).await, Some(written));
+
+        remove_recovery(&data_dir).await;
+        assert_eq!(read_recovery(&data_dir).await, None);
+    }
+
+    #[tokio::test]
+    async fn autosave_while_switching_lists() {
+        let dir = TempDir::new("autosave_test").unwrap();
+        let thumb_dir = TempDir::new("autosave_thumb").unwrap();
+        let db_url = dir.path().join("test.db3").to_string_lossy().to_string();
+        let db_pool = db::connect(&db_url).await.unwrap();
+        db::MIGRATOR.run(&db_pool).await.unwrap();
+        let mut ids = vec![];
+        for title in ["A", "B"] {
+            let res = sqlx::query("INSERT INTO tierlist(title) VALUES (?)")
+                .bind(title)
+                .execute(&db_pool)
+                .await
+                .unwrap();
+            ids.push(res.last_insert_rowid());
+        }
+        let (a, b) = (ids[0], ids[1]);
+
+        let mut edited = TierList::empty();
+        edited.title = "A edited".to_owned();
+        let pool = Mutex::new(Some(db_pool.clone()));
+        let tierlist = Mutex::new(edited);
+        let cur_file = Mutex::new(Some(db_url));
+        let cur_list = Mutex::new(Some(a));
+
+        // read_tierlist_from_db と同じく、pool の lock を持ったまま tierlist、cur_list の順に切り替える
+        let switch = async {
+            let _pool = pool.lock().await;
+            let mut list_b = TierList::empty();
+            list_b.title = "B".to_owned();
+            *tierlist.lock().await = list_b;
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+            *cur_list.lock().await = Some(b);
+        };
+        let save = save_to_db(
+            &pool,
+            &tierlist,
+            &cur_file,
+            &cur_list,
+            thumb_dir.path(),
+            None,
+        );
+        let (_, saved) = tokio::join!(switch, save);
+        assert_eq!(saved.ok().flatten().unwrap().title, "B");
+
+        let titles: Vec<String> = sqlx::query_scalar("SELECT title FROM tierlist ORDER BY id")
+            .fetch_all(&db_pool)
+            .await
+            .unwrap();
+        assert_eq!(titles, vec!["A", "B"]);
+
+        // 変わっていなければ書き込まない
+        let last = tierlist.lock().await.clone();
+        let saved = save_to_db(
+            &pool,
+            &tierlist,
+            &cur_file,
+            &cur_list,
+            thumb_dir.path(),
+            Some(&last),
+        )
+        .await;
+        assert!(matches!(saved, Ok(None)));
+    }
+
+    #[tokio::test]
+    async fn newer_than_saved_file() {
+        let dir = TempDir::new("recovery_test").unwrap();
+        let db_path = dir.path().join("list.db3");
+        fs::write(&db_path, b"").await.unwrap();
+        let db_path = Some(db_path.to_string_lossy().to_string());
+
+        assert!(recovery(None, 0).await.is_newer_than_file().await);
+        assert!(
+            !recovery(db_path.clone(), now_secs() - 100)
+                .await
+                .is_newer_than_file()
+                .await
+        );
+        assert!(
+            recovery(db_path, now_secs() + 100)
+                .await
+                .is_newer_than_file()
+                .await
+        );
+        let missing = dir.path().join("missing.db3").to_string_lossy().to_string();
+        assert!(recovery(Some(missing), 0).await.is_newer_than_file().await);
+    }
+}
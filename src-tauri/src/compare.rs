@@ -0,0 +1,410 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    placement::Place,
+    snapshot::places,
+    sort::{compare_names, fold_name},
+    tierlist::{Item, ListId, TierList},
+};
+
+// 別々のファイルにある tierlist。list_id が None ならファイルにある唯一の tierlist
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareSource {
+    pub path: String,
+    pub list_id: Option<ListId>,
+}
+
+// 左右の tierlist で同じとみなしたアイテム
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparedItem {
+    // 左にあれば左の名前
+    pub name: String,
+    pub left: Place,
+    pub right: Place,
+    // tier の位置を 0 (一番上) から 1 (一番下) にしたときの差 (右 - 左)。
+    // 正なら右の人の方が低く付けた。tier の数が違っても比べられる
+    pub diff: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Comparison {
+    pub left_title: String,
+    pub right_title: String,
+    // 0 から 1。両方が tier に置いたアイテムがなければ None
+    pub agreement: Option<f64>,
+    // 両方が tier に置いたアイテム。差 (の絶対値) の大きい順
+    pub both: Vec<ComparedItem>,
+    // 片方だけが tier に置いた (もう片方はプールに置いたか、持っていない) アイテム
+    pub only_left: Vec<ComparedItem>,
+    pub only_right: Vec<ComparedItem>,
+}
+
+// URL は http(s):// と www. と末尾の / を除いて、大文字小文字を区別しない
+fn url_key(url: &str) -> Option<String> {
+    let url = url.trim().to_lowercase();
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(&url);
+    let url = url
+        .strip_prefix("www.")
+        .unwrap_or(url)
+        .trim_end_matches('/');
+    if url.is_empty() {
+        None
+    } else {
+        Some(url.to_owned())
+    }
+}
+
+// 名前は並べ替えと同じように揃えて、記号と空白を除く
+fn name_key(name: &str) -> Option<String> {
+    let key: String = fold_name(name)
+        .into_iter()
+        .filter(|c| c.is_alphanumeric())
+        .collect();
+    if key.is_empty() {
+        None
+    } else {
+        Some(key)
+    }
+}
+
+// 左右のアイテムの組 (items のインデックス)。URL が同じものを先に、残りを名前で組にする
+fn match_items(left: &TierList, right: &TierList) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut left_matched: Vec<Option<usize>> = vec![None; left.items.len()];
+    let mut right_used = vec![false; right.items.len()];
+    let keys: [fn(&Item) -> Option<String>; 2] =
+        [|item| url_key(&item.url), |item| name_key(&item.name)];
+    for key in keys {
+        // 同じキーのアイテムが複数あれば前から順に組にする
+        let mut candidates: HashMap<String, Vec<usize>> = HashMap::new();
+        for (j, item) in right.items.iter().enumerate().rev() {
+            if let Some(key) = key(item) {
+                candidates.entry(key).or_default().push(j);
+            }
+        }
+        for (i, item) in left.items.iter().enumerate() {
+            if left_matched[i].is_some() {
+                continue;
+            }
+            let found = key(item)
+                .and_then(|key| candidates.get_mut(&key))
+                .and_then(|js| {
+                    while let Some(j) = js.pop() {
+                        if !right_used[j] {
+                            return Some(j);
+                        }
+                    }
+                    None
+                });
+            if let Some(j) = found {
+                left_matched[i] = Some(j);
+                right_used[j] = true;
+            }
+        }
+    }
+
+    let mut pairs: Vec<(Option<usize>, Option<usize>)> = left_matched
+        .into_iter()
+        .enumerate()
+        .map(|(i, j)| (Some(i), j))
+        .collect();
+    for (j, used) in right_used.into_iter().enumerate() {
+        if !used {
+            pairs.push((None, Some(j)));
+        }
+    }
+    pairs
+}
+
+// 一致度は、両方が tier に置いたアイテムの差 (ComparedItem::diff) の絶対値の平均を 1 から引いたもの
+pub fn compare(left: &TierList, right: &TierList) -> Comparison {
+    let (left_places, right_places) = (places(left), places(right));
+    let position = |rank: usize, tiers: usize| {
+        if tiers <= 1 {
+            0.0
+        } else {
+            rank as f64 / (tiers - 1) as f64
+        }
+    };
+    let mut comparison = Comparison {
+        left_title: left.title.clone(),
+        right_title: right.title.clone(),
+        agreement: None,
+        both: vec![],
+        only_left: vec![],
+        only_right: vec![],
+    };
+    let mut distance = 0.0;
+    for (i, j) in match_items(left, right) {
+        let left_item = i.map(|i| &left.items[i]);
+        let right_item = j.map(|j| &right.items[j]);
+        let locate = |item: Option<&Item>, places: &HashMap<_, (Place, usize)>| match item
+            .and_then(|item| places.get(&item.id))
+        {
+            Some((place @ Place::Tier { .. }, rank)) => (place.clone(), Some(*rank)),
+            Some((place, _)) => (place.clone(), None),
+            None => (Place::Absent, None),
+        };
+        let (left_place, left_rank) = locate(left_item, &left_places);
+        let (right_place, right_rank) = locate(right_item, &right_places);
+        let name = left_item.or(right_item).map_or("", |it| it.name.as_str());
+        let mut compared = ComparedItem {
+            name: name.to_owned(),
+            left: left_place,
+            right: right_place,
+            diff: None,
+        };
+        match (left_rank, right_rank) {
+            (Some(l), Some(r)) => {
+                let diff = position(r, right.tiers.len()) - position(l, left.tiers.len());
+                compared.diff = Some(diff);
+                distance += diff.abs();
+                comparison.both.push(compared);
+            }
+            (Some(_), None) => comparison.only_left.push(compared),
+            (None, Some(_)) => comparison.only_right.push(compared),
+            (None, None) => {}
+        }
+    }
+    if !comparison.both.is_empty() {
+        comparison.agreement = Some(1.0 - distance / comparison.both.len() as f64);
+    }
+
+    let abs_diff = |c: &ComparedItem| c.diff.map_or(0.0, |d| d.abs());
+    comparison.both.sort_by(|a, b| {
+        abs_diff(b)
+            .partial_cmp(&abs_diff(a))
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| compare_names(&a.name, &b.name))
+    });
+    comparison
+        .only_left
+        .sort_by(|a, b| compare_names(&a.name, &b.name));
+    comparison
+        .only_right
+        .sort_by(|a, b| compare_names(&a.name, &b.name));
+    comparison
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn place_label(place: &Place) -> &str {
+    match place {
+        Place::Tier { title, .. } => title,
+        Place::Pool | Place::Absent => "",
+    }
+}
+
+// 1 行目は見出し、最後の行は一致度。差と一致度は % にする。片方だけが置いたアイテムは差を空にする
+pub fn to_csv(comparison: &Comparison) -> String {
+    let mut rows = vec![[
+        "Item".to_owned(),
+        comparison.left_title.clone(),
+        comparison.right_title.clone(),
+        "Difference".to_owned(),
+    ]];
+    let items = comparison
+        .both
+        .iter()
+        .chain(comparison.only_left.iter())
+        .chain(comparison.only_right.iter());
+    for item in items {
+        rows.push([
+            item.name.clone(),
+            place_label(&item.left).to_owned(),
+            place_label(&item.right).to_owned(),
+            item.diff
+                .map_or("".to_owned(), |d| format!("{:.0}%", d * 100.0)),
+        ]);
+    }
+    let agreement = comparison
+        .agreement
+        .map_or("".to_owned(), |a| format!("{:.0}%", a * 100.0));
+    rows.push([
+        "Agreement".to_owned(),
+        "".to_owned(),
+        "".to_owned(),
+        agreement,
+    ]);
+
+    let mut csv = String::new();
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+pub mod commands {
+    use std::path::Path;
+
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::{db, render, tierlist::TierListInfo};
+
+    // サムネイルは使わないので、読んだ後に一時ディレクトリごと消す
+    async fn compare_sources(
+        left: &CompareSource,
+        right: &CompareSource,
+    ) -> Result<Comparison, String> {
+        let thumb_dir = TempDir::new("compare").map_err(|e| e.to_string())?;
+        let mut lists = vec![];
+        for source in [left, right] {
+            let tierlist =
+                db::read_tierlist_file(Path::new(&source.path), source.list_id, thumb_dir.path())
+                    .await
+                    .map_err(|e| format!("{}: {}", source.path, e))?;
+            lists.push(tierlist);
+        }
+        Ok(compare(&lists[0], &lists[1]))
+    }
+
+    // 開いている DB とは別のファイルにある tierlist の一覧
+    #[tauri::command]
+    pub async fn list_tierlists_in_file(path: String) -> Result<Vec<TierListInfo>, String> {
+        db::list_tierlists_file(Path::new(&path))
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    #[tauri::command]
+    pub async fn compare_tierlists(
+        left: CompareSource,
+        right: CompareSource,
+    ) -> Result<Comparison, String> {
+        compare_sources(&left, &right).await
+    }
+
+    #[tauri::command]
+    pub async fn export_comparison_csv(
+        left: CompareSource,
+        right: CompareSource,
+        path: String,
+    ) -> Result<(), String> {
+        let comparison = compare_sources(&left, &right).await?;
+        tokio::fs::write(&path, to_csv(&comparison))
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    #[tauri::command]
+    pub async fn export_comparison_png(
+        left: CompareSource,
+        right: CompareSource,
+        path: String,
+    ) -> Result<(), String> {
+        let comparison = compare_sources(&left, &right).await?;
+        render::save_comparison_png(comparison, path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tierlist::ItemData;
+
+    fn tierlist(title: &str, tiers: &[&str], items: &[(&str, &str, Option<usize>)]) -> TierList {
+        let mut tierlist = TierList::empty();
+        tierlist.title = title.to_owned();
+        let tier_ids: Vec<_> = tiers.iter().map(|t| tierlist.add_tier(t)).collect();
+        for &(name, url, tier) in items {
+            let id = tierlist.add_item(ItemData {
+                name: name.to_owned(),
+                url: url.to_owned(),
+                thumb: None,
+                memo: "".to_owned(),
+            });
+            if let Some(tier) = tier {
+                tierlist.move_item(id, Some(tier_ids[tier]), 99).unwrap();
+            }
+        }
+        tierlist
+    }
+
+    #[test]
+    fn compare_lists() {
+        let left = tierlist(
+            "alice",
+            &["S", "A", "B"],
+            &[
+                ("Final Fantasy VII", "", Some(0)),
+                ("Chrono Trigger", "https://example.com/ct", Some(0)),
+                ("ドラゴンクエスト", "", Some(1)),
+                ("Tetris", "", Some(2)),
+                ("Pong", "", None),
+                ("Myst", "", Some(1)),
+            ],
+        );
+        let right = tierlist(
+            "bob",
+            &["S", "A", "B", "C", "D"],
+            &[
+                // URL が同じなら名前が違っても同じアイテム
+                ("クロノ・トリガー", "http://www.example.com/ct/", Some(0)),
+                ("final fantasy 7", "", Some(4)),
+                ("FINAL FANTASY VII", "", Some(2)),
+                ("どらごんくえすと", "", Some(1)),
+                ("Tetris", "", None),
+                ("Pong", "", Some(3)),
+                ("Zelda", "", Some(0)),
+            ],
+        );
+        let comparison = compare(&left, &right);
+
+        let both: Vec<(&str, &str, Option<f64>)> = comparison
+            .both
+            .iter()
+            .map(|c| (c.name.as_str(), place_label(&c.right), c.diff))
+            .collect();
+        assert_eq!(
+            both,
+            vec![
+                ("Final Fantasy VII", "B", Some(0.5)),
+                ("ドラゴンクエスト", "A", Some(-0.25)),
+                ("Chrono Trigger", "S", Some(0.0)),
+            ]
+        );
+        // 3 段の A (0.5) と 5 段の A (0.25) は同じ名前でも位置が違う
+        let agreement = comparison.agreement.unwrap();
+        assert!((agreement - 0.75).abs() < 1e-9, "{}", agreement);
+
+        let names = |items: &[ComparedItem]| -> Vec<String> {
+            items.iter().map(|c| c.name.clone()).collect()
+        };
+        assert_eq!(names(&comparison.only_left), vec!["Myst", "Tetris"]);
+        assert_eq!(comparison.only_left[0].right, Place::Absent);
+        assert_eq!(comparison.only_left[1].right, Place::Pool);
+        assert_eq!(
+            names(&comparison.only_right),
+            vec!["final fantasy 7", "Pong", "Zelda"]
+        );
+
+        let csv = to_csv(&comparison);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "Item,alice,bob,Difference");
+        assert_eq!(lines[1], "Final Fantasy VII,S,B,50%");
+        assert_eq!(lines[2], "ドラゴンクエスト,A,A,-25%");
+        assert_eq!(lines[4], "Myst,A,,");
+        assert_eq!(lines[lines.len() - 1], "Agreement,,,75%");
+        assert_eq!(csv_field("a, \"b\""), "\"a, \"\"b\"\"\"");
+
+        let empty = compare(&TierList::empty(), &right);
+        assert_eq!(empty.agreement, None);
+        assert_eq!(empty.only_right.len(), 6);
+    }
+}
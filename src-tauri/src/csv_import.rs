@@ -0,0 +1,464 @@
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::{
+    fields::FieldType,
+    scraping,
+    thumbnail::{self, ThumbnailOptions},
+    tierlist::{now, palette_color, Item, ItemTimes, Tier, TierList},
+};
+
+// 行ごとのエラー。line はファイル中の行番号 (1 始まり)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowError {
+    pub line: usize,
+    pub message: String,
+}
+
+// 取り込む 1 行分の内容
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CsvRow {
+    pub line: usize,
+    pub name: String,
+    pub url: String,
+    pub memo: String,
+    pub tier: Option<String>,
+    pub image: Option<String>,
+    // 上記以外の列の (列名, 値)。値が空の列は含めない
+    pub fields: Vec<(String, String)>,
+}
+
+// 拡張子が .tsv か、1 行目にタブがあればタブ区切りとみなす
+pub fn detect_delimiter(path: &Path, text: &str) -> char {
+    let is_tsv = path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("tsv"))
+        .unwrap_or(false);
+    if is_tsv || text.lines().next().unwrap_or("").contains('\t') {
+        '\t'
+    } else {
+        ','
+    }
+}
+
+// RFC 4180 風に読む。"..." で囲んだフィールドには区切り文字や改行を含められ、"" は " になる
+// 返り値はレコードごとの (開始行番号, フィールド)
+pub fn parse_records(text: &str, delimiter: char) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut records = vec![];
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                records.push((record_line, std::mem::take(&mut fields)));
+                line += 1;
+                record_line = line;
+            }
+            _ if c == delimiter => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(format!("Unterminated quoted field at line {}", record_line));
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        records.push((record_line, fields));
+    }
+
+    // 空行は読み飛ばす
+    records.retain(|(_, fields)| fields.iter().any(|f| !f.trim().is_empty()));
+    Ok(records)
+}
+
+// 1 行目をヘッダーとして列を対応付ける。name 列は必須
+pub fn parse_rows(text: &str, delimiter: char) -> Result<(Vec<CsvRow>, Vec<RowError>), String> {
+    let mut records = parse_records(text, delimiter)?.into_iter();
+    let (_, header) = records.next().ok_or("File is empty")?;
+    let column = |names: &[&str]| {
+        header
+            .iter()
+            .position(|h| names.iter().any(|n| h.trim().eq_ignore_ascii_case(n)))
+    };
+    let name_col = column(&["name", "title"]).ok_or("Column \"name\" not found")?;
+    let url_col = column(&["url"]);
+    let memo_col = column(&["memo"]);
+    let tier_col = column(&["tier"]);
+    let image_col = column(&["image", "thumb"]);
+    let known = [Some(name_col), url_col, memo_col, tier_col, image_col];
+    let field_cols: Vec<(usize, String)> = header
+        .iter()
+        .enumerate()
+        .filter(|(i, h)| !known.contains(&Some(*i)) && !h.trim().is_empty())
+        .map(|(i, h)| (i, h.trim().to_owned()))
+        .collect();
+
+    let mut rows = vec![];
+    let mut errors = vec![];
+    for (line, fields) in records {
+        let get = |col: Option<usize>| {
+            col.and_then(|i| fields.get(i))
+                .map(|f| f.trim().to_owned())
+                .unwrap_or_default()
+        };
+        let non_empty = |s: String| if s.is_empty() { None } else { Some(s) };
+
+        let name = get(Some(name_col));
+        if name.is_empty() {
+            errors.push(RowError {
+                line,
+                message: "Name is empty".to_owned(),
+            });
+            continue;
+        }
+        rows.push(CsvRow {
+            line,
+            name,
+            url: get(url_col),
+            memo: get(memo_col),
+            tier: non_empty(get(tier_col)),
+            image: non_empty(get(image_col)),
+            fields: field_cols
+                .iter()
+                .map(|(i, h)| (h.clone(), get(Some(*i))))
+                .filter(|(_, value)| !value.is_empty())
+                .collect(),
+        });
+    }
+    Ok((rows, errors))
+}
+
+// image 列の値 (URL かローカルのパス) からサムネイルを作る
+async fn load_image(
+    image: &str,
+    img_dir: &Path,
+    name: &str,
+    options: &ThumbnailOptions,
+) -> Result<String, String> {
+    if image.starts_with("http://") || image.starts_with("https://") {
+        let bytes = scraping::fetch_bytes(image).await?;
+        thumbnail::save_normalized(bytes, img_dir, name, options).await
+    } else {
+        let bytes = fs::read(image)
+            .await
+            .map_err(|e| format!("Could not read {}: {}", image, e))?;
+        thumbnail::save_normalized(bytes, img_dir, name, options).await
+    }
+}
+
+// 各行の image 列の画像をサムネイルにする (rows と同じ順)。読めなかった行はエラーとして報告する
+// ダウンロードに時間がかかるので、tierlist の lock を持たずに呼べるように tierlist には触らない
+pub async fn load_images(
+    rows: &[CsvRow],
+    img_dir: &Path,
+    options: &ThumbnailOptions,
+) -> (Vec<Option<String>>, Vec<RowError>) {
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let mut thumbs = vec![];
+    let mut errors = vec![];
+    for row in rows {
+        let thumb = match &row.image {
+            Some(image) => {
+                let name = format!("_csv_{}_{}", nonce, row.line);
+                match load_image(image, img_dir, &name, options).await {
+                    Ok(path) => Some(path),
+                    Err(e) => {
+                        errors.push(RowError {
+                            line: row.line,
+                            message: format!("Image not imported: {}", e),
+                        });
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        thumbs.push(thumb);
+    }
+    (thumbs, errors)
+}
+
+// rows を tierlist に追加する。thumbs は load_images で作ったサムネイル
+// tier が指定されていればその名前の tier に (なければ作る)、
+// なければ items_pool に入れる
+// fields は同じ名前の field に入れる。なければ text の field を作る
+// tier も field も名前の大文字小文字は区別しない
+// 型に合わない値はエラーとして報告するが、アイテム自体は追加する
+pub fn import_rows(
+    tierlist: &mut TierList,
+    rows: Vec<CsvRow>,
+    thumbs: Vec<Option<String>>,
+) -> Vec<RowError> {
+    let mut errors = vec![];
+
+    for (row, thumb) in rows.into_iter().zip(thumbs) {
+        let id = tierlist.item_max_id + 1;
+        tierlist.item_max_id = id;
+        tierlist.items.push(Item {
+            id,
+            name: row.name,
+            url: row.url,
+            thumb,
+            memo: row.memo,
+            tags: vec![],
+            fields: Default::default(),
+            times: ItemTimes::new(now()),
+        });
+        match row.tier {
+            Some(title) => {
+                let pos = match tierlist
+                    .tiers
+                    .iter()
+                    .position(|t| t.title.eq_ignore_ascii_case(&title))
+                {
+                    Some(pos) => pos,
+                    None => {
+                        tierlist.tier_max_id += 1;
+                        tierlist.tiers.push(Tier {
+                            id: tierlist.tier_max_id,
+                            title,
+                            items: vec![],
+                            color: palette_color(tierlist.tiers.len()),
+                            text_color: None,
+                            icon: None,
+                        });
+                        tierlist.tiers.len() - 1
+                    }
+                };
+                tierlist.tiers[pos].items.push(id);
+            }
+            None => tierlist.items_pool.push(id),
+        }
+
+        for (name, value) in row.fields {
+            let field = match tierlist
+                .fields
+                .iter()
+                .find(|f| f.name.eq_ignore_ascii_case(&name))
+            {
+                Some(field) => Ok(field.id),
+                None => tierlist.add_field(&name, FieldType::Text),
+            };
+            let res = field.and_then(|field| tierlist.set_item_field(id, field, Some(value)));
+            if let Err(e) = res {
+                errors.push(RowError {
+                    line: row.line,
+                    message: format!("{} not imported: {}", name, e),
+                });
+            }
+        }
+    }
+    errors
+}
+
+pub mod commands {
+    use tauri::{async_runtime::Mutex, State};
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::history::{History, Op};
+
+    #[derive(Clone, Debug, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct CsvImportResult {
+        pub tierlist: TierList,
+        pub imported: usize,
+        pub errors: Vec<RowError>,
+    }
+
+    #[tauri::command]
+    pub async fn import_items_csv(
+        img_dir: State<'_, TempDir>,
+        thumb_options: State<'_, Mutex<ThumbnailOptions>>,
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        path: String,
+    ) -> Result<CsvImportResult, String> {
+        let path = Path::new(&path);
+        let text = fs::read_to_string(path).await.map_err(|e| e.to_string())?;
+        let (rows, mut errors) = parse_rows(&text, detect_delimiter(path, &text))?;
+        let imported = rows.len();
+        let thumb_options = thumb_options.lock().await.clone();
+        // 画像のダウンロード中は他の操作を止めないように lock を持たない
+        let (thumbs, image_errors) = load_images(&rows, img_dir.path(), &thumb_options).await;
+        errors.extend(image_errors);
+        let mut tierlist = tierlist.lock().await;
+        // 取り込み全体を 1 回の undo で戻せるようにする
+        let mut imported_list = tierlist.clone();
+        errors.extend(import_rows(&mut imported_list, rows, thumbs));
+        errors.sort_by_key(|e| e.line);
+        history
+            .lock()
+            .await
+            .apply(&mut tierlist, Op::Replace(Box::new(imported_list)))?;
+        Ok(CsvImportResult {
+            tierlist: tierlist.clone(),
+            imported,
+            errors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, ImageOutputFormat, RgbImage};
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn quoted_fields() {
+        let text = "name,memo\r\n\"a, b\",\"say \"\"hi\"\"\"\n\"multi\nline\",x\n";
+        let records = parse_records(text, ',').unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records[1],
+            (2, vec!["a, b".to_owned(), "say \"hi\"".to_owned()])
+        );
+        assert_eq!(
+            records[2],
+            (3, vec!["multi\nline".to_owned(), "x".to_owned()])
+        );
+        assert!(parse_records("name\n\"open", ',').is_err());
+    }
+
+    #[test]
+    fn tsv_rows() {
+        let text = "\u{feff}Name\tURL\tTier\n\nitem1\turl1\tS\n\turl2\t\nitem3\n";
+        assert_eq!(detect_delimiter(Path::new("items.txt"), text), '\t');
+        let (rows, errors) = parse_rows(text, '\t').unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                CsvRow {
+                    line: 3,
+                    name: "item1".to_owned(),
+                    url: "url1".to_owned(),
+                    tier: Some("S".to_owned()),
+                    ..Default::default()
+                },
+                CsvRow {
+                    line: 5,
+                    name: "item3".to_owned(),
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 4);
+        assert!(parse_rows("url,memo\nu,m\n", ',').is_err());
+    }
+
+    #[tokio::test]
+    async fn import_into_tiers_and_pool() {
+        let dir = TempDir::new("csv_test").unwrap();
+        let img_path = dir.path().join("cover.png");
+        let mut buf = vec![];
+        DynamicImage::ImageRgb8(RgbImage::new(8, 8))
+            .write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Png)
+            .unwrap();
+        fs::write(&img_path, buf).await.unwrap();
+
+        let mut tierlist = TierList::empty();
+        tierlist.tiers = vec![Tier {
+            id: 1,
+            title: "S".to_owned(),
+            items: vec![],
+            color: "#ff7f7f".to_owned(),
+            text_color: None,
+            icon: None,
+        }];
+        tierlist.tier_max_id = 1;
+        tierlist.item_max_id = 5;
+
+        let text = format!(
+            "name,url,memo,tier,image\na,,,S,{}\nb,,,A,\nc,,m,,\nd,,,a,{}\n",
+            img_path.display(),
+            dir.path().join("missing.png").display()
+        );
+        let (rows, errors) = parse_rows(&text, ',').unwrap();
+        assert!(errors.is_empty());
+        let img_dir = TempDir::new("csv_imgs").unwrap();
+        let (thumbs, errors) =
+            load_images(&rows, img_dir.path(), &ThumbnailOptions::default()).await;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 5);
+        assert!(import_rows(&mut tierlist, rows, thumbs).is_empty());
+
+        assert_eq!(tierlist.item_max_id, 9);
+        assert_eq!(tierlist.tier_max_id, 2);
+        assert_eq!(tierlist.tiers[0].items, vec![6]);
+        assert_eq!(tierlist.tiers[1].title, "A");
+        assert_eq!(tierlist.tiers[1].items, vec![7, 9]);
+        assert_eq!(tierlist.items_pool, vec![8]);
+        assert_eq!(tierlist.items[2].memo, "m");
+        assert!(Path::new(tierlist.items[0].thumb.as_ref().unwrap()).starts_with(img_dir.path()));
+        assert_eq!(tierlist.items[3].thumb, None);
+    }
+
+    #[tokio::test]
+    async fn import_fields() {
+        let mut tierlist = TierList::empty();
+        let year = tierlist.add_field("Year", FieldType::Number).unwrap();
+
+        let text = "name,year,studio,empty\na,1998,Sunrise,\nb,soon,,\n";
+        let (rows, errors) = parse_rows(text, ',').unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(
+            rows[0].fields,
+            vec![
+                ("year".to_owned(), "1998".to_owned()),
+                ("studio".to_owned(), "Sunrise".to_owned()),
+            ]
+        );
+        let thumbs = vec![None; rows.len()];
+        let errors = import_rows(&mut tierlist, rows, thumbs);
+
+        // 型に合わない値は入れずに報告する
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 3);
+        assert_eq!(tierlist.fields.len(), 2);
+        assert_eq!(tierlist.fields[1].name, "studio");
+        assert_eq!(tierlist.fields[1].field_type, FieldType::Text);
+        let studio = tierlist.fields[1].id;
+        assert_eq!(tierlist.items[0].fields.get(&year).unwrap(), "1998");
+        assert_eq!(tierlist.items[0].fields.get(&studio).unwrap(), "Sunrise");
+        assert!(tierlist.items[1].fields.is_empty());
+        assert!(tierlist.validate().is_empty());
+    }
+}
@@ -1,12 +1,14 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
     error, fmt,
-    path::Path,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
 };
 
 use sqlx::{
+    migrate::Migrator,
     sqlite::{SqliteConnectOptions, SqlitePoolOptions},
-    QueryBuilder, Row, Sqlite, SqlitePool,
+    QueryBuilder, Row, Sqlite, SqliteConnection, SqlitePool,
 };
 use tokio::{
     fs::File,
@@ -14,9 +16,19 @@ use tokio::{
 };
 use tokio_stream::StreamExt;
 
-use crate::tierlist::{Item, ItemId, Tier, TierId, TierList};
+use crate::{
+    fields::{FieldDef, FieldId, FieldType},
+    placement::{Place, Placement},
+    snapshot::{self, SnapshotId, SnapshotInfo},
+    tierlist::{
+        now, Item, ItemId, ItemTimes, ListId, Tier, TierId, TierList, TierListInfo, Timestamp,
+    },
+};
+
+type DbResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
-type DbResult<T> = Result<T, Box<dyn std::error::Error>>;
+// 01_cleanup.sql などは適用済みのファイルにチェックサムが記録されているので、内容を変えてはいけない
+pub static MIGRATOR: Migrator = sqlx::migrate!("./sql");
 
 #[derive(Debug, Clone)]
 struct ReadError {
@@ -31,6 +43,111 @@ impl fmt::Display for ReadError {
 
 impl error::Error for ReadError {}
 
+#[derive(Debug, Clone)]
+struct ListNotFoundError {
+    id: ListId,
+}
+
+impl fmt::Display for ListNotFoundError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tierlist {} not found", self.id)
+    }
+}
+
+impl error::Error for ListNotFoundError {}
+
+#[derive(Debug, Clone)]
+struct ListNotChosenError {
+    count: usize,
+}
+
+impl fmt::Display for ListNotChosenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "this file has {} tier lists; choose one", self.count)
+    }
+}
+
+impl error::Error for ListNotChosenError {}
+
+#[derive(Debug, Clone)]
+struct SnapshotNotFoundError {
+    id: SnapshotId,
+}
+
+impl fmt::Display for SnapshotNotFoundError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "snapshot {} not found", self.id)
+    }
+}
+
+impl error::Error for SnapshotNotFoundError {}
+
+#[derive(Debug, Clone)]
+struct SnapshotExistsError {
+    name: String,
+}
+
+impl fmt::Display for SnapshotExistsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "snapshot \"{}\" already exists", self.name)
+    }
+}
+
+impl error::Error for SnapshotExistsError {}
+
+#[derive(Debug, Clone)]
+struct SchemaTooNewError {
+    version: i64,
+}
+
+impl fmt::Display for SchemaTooNewError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "this file was saved by a newer version of the app (schema {} > {})",
+            self.version,
+            schema_version()
+        )
+    }
+}
+
+impl error::Error for SchemaTooNewError {}
+
+#[derive(Debug, Clone)]
+struct SchemaTooOldError {
+    version: i64,
+}
+
+impl fmt::Display for SchemaTooOldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "this file was saved by an older version of the app (schema {} < {}); \
+            open it once to upgrade it",
+            self.version,
+            schema_version()
+        )
+    }
+}
+
+impl error::Error for SchemaTooOldError {}
+
+#[derive(Debug, Clone)]
+struct NotTierListFileError;
+
+impl fmt::Display for NotTierListFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "this file is not a Tierlist Maker file")
+    }
+}
+
+impl error::Error for NotTierListFileError {}
+
+// このビルドが扱えるスキーマのバージョン (最後のマイグレーションの番号)
+pub fn schema_version() -> i64 {
+    MIGRATOR.iter().map(|m| m.version).max().unwrap_or(0)
+}
+
 pub async fn connect(url: &str) -> DbResult<SqlitePool> {
     let opt = SqliteConnectOptions::new()
         .filename(url)
@@ -39,15 +156,37 @@ pub async fn connect(url: &str) -> DbResult<SqlitePool> {
     Ok(pool)
 }
 
-async fn read_tierlist(pool: &SqlitePool, thumb_dir: &Path) -> DbResult<TierList> {
+// DB 内のサムネイルを展開するディレクトリ。別の DB ファイルの同じ id のアイテムのものを
+// 上書きしないように、thumb_dir の下に DB ファイルごとに分ける
+async fn thumb_cache_dir(pool: &SqlitePool, thumb_dir: &Path) -> DbResult<PathBuf> {
+    const SQL_FILE: &str = "SELECT file FROM pragma_database_list WHERE name = 'main'";
+    let file: String = sqlx::query_scalar(SQL_FILE).fetch_one(pool).await?;
+    let mut hasher = DefaultHasher::new();
+    file.hash(&mut hasher);
+    let cache_dir = thumb_dir.join(format!("_indb_{:016x}", hasher.finish()));
+    tokio::fs::create_dir_all(&cache_dir).await?;
+    Ok(cache_dir)
+}
+
+// DB 内のサムネイルを cache_dir (thumb_cache_dir で作ったもの) に展開するときのパス
+fn thumb_cache_path(cache_dir: &Path, list_id: ListId, item_id: ItemId) -> PathBuf {
+    cache_dir.join(format!("{}_{}", list_id, item_id))
+}
+
+async fn read_tierlist(pool: &SqlitePool, list_id: ListId, thumb_dir: &Path) -> DbResult<TierList> {
     let mut tierlist = TierList::empty();
 
-    const SQL_TITLE: &str = "SELECT * FROM tierlist";
-    let res = sqlx::query(SQL_TITLE).fetch_one(pool).await?;
+    const SQL_TITLE: &str = "SELECT title FROM tierlist WHERE id = ?";
+    let res = sqlx::query(SQL_TITLE)
+        .bind(list_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(ListNotFoundError { id: list_id })?;
     tierlist.title = res.try_get("title")?;
 
-    const SQL_TIERS: &str = "SELECT id, title FROM tiers ORDER BY pos ASC";
-    let mut res = sqlx::query(SQL_TIERS).fetch(pool);
+    const SQL_TIERS: &str =
+        "SELECT id, title, color, text_color, icon FROM tiers WHERE list_id = ? ORDER BY pos ASC";
+    let mut res = sqlx::query(SQL_TIERS).bind(list_id).fetch(pool);
     let mut tier_pos = HashMap::new();
     while let Some(row) = res.try_next().await? {
         let tier_id: TierId = row.try_get("id")?;
@@ -56,13 +195,18 @@ async fn read_tierlist(pool: &SqlitePool, thumb_dir: &Path) -> DbResult<TierList
             id: tier_id,
             title: title.to_owned(),
             items: vec![],
+            color: row.try_get("color")?,
+            text_color: row.try_get("text_color")?,
+            icon: row.try_get("icon")?,
         });
         tierlist.tier_max_id = tierlist.tier_max_id.max(tier_id);
         tier_pos.insert(tier_id, tierlist.tiers.len() - 1);
     }
 
-    const SQL_ITEMS: &str = "SELECT * FROM items";
-    let mut res = sqlx::query(SQL_ITEMS).fetch(pool);
+    const SQL_ITEMS: &str = "SELECT id, name, url, thumb, memo, created_at, updated_at, moved_at \
+        FROM items WHERE list_id = ?";
+    let cache_dir = thumb_cache_dir(pool, thumb_dir).await?;
+    let mut res = sqlx::query(SQL_ITEMS).bind(list_id).fetch(pool);
     while let Some(row) = res.try_next().await? {
         let item_id: ItemId = row.try_get("id")?;
         let name: &str = row.try_get("name")?;
@@ -71,9 +215,9 @@ async fn read_tierlist(pool: &SqlitePool, thumb_dir: &Path) -> DbResult<TierList
         let memo: &str = row.try_get("memo")?;
 
         let thumb_path = if let Some(thumb) = thumb {
-            let thumb_path = thumb_dir.join(format!("_indb_{}", item_id));
+            let thumb_path = thumb_cache_path(&cache_dir, list_id, item_id);
             let mut thumb_file = File::create(&thumb_path).await?;
-            thumb_file.write(&thumb).await?;
+            thumb_file.write_all(&thumb).await?;
             Some(thumb_path.to_string_lossy().to_string())
         } else {
             None
@@ -85,12 +229,66 @@ async fn read_tierlist(pool: &SqlitePool, thumb_dir: &Path) -> DbResult<TierList
             url: url.to_owned(),
             thumb: thumb_path,
             memo: memo.to_owned(),
+            tags: vec![],
+            fields: Default::default(),
+            times: ItemTimes {
+                created_at: row.try_get("created_at")?,
+                updated_at: row.try_get("updated_at")?,
+                moved_at: row.try_get("moved_at")?,
+            },
         });
         tierlist.item_max_id = tierlist.item_max_id.max(item_id);
     }
 
-    const SQL_POS: &str = "SELECT item_id, tier_id FROM items_pos ORDER BY pos ASC";
-    let mut res = sqlx::query(SQL_POS).fetch(pool);
+    const SQL_TAGS: &str = "SELECT item_tags.item_id, tags.name FROM item_tags \
+        JOIN tags ON tags.list_id = item_tags.list_id AND tags.id = item_tags.tag_id \
+        WHERE item_tags.list_id = ? ORDER BY tags.name ASC";
+    let item_pos: HashMap<ItemId, usize> = tierlist
+        .items
+        .iter()
+        .enumerate()
+        .map(|(i, it)| (it.id, i))
+        .collect();
+    let mut res = sqlx::query(SQL_TAGS).bind(list_id).fetch(pool);
+    while let Some(row) = res.try_next().await? {
+        let item_id: ItemId = row.try_get("item_id")?;
+        let name: String = row.try_get("name")?;
+        if let Some(&idx) = item_pos.get(&item_id) {
+            tierlist.items[idx].tags.push(name);
+        }
+    }
+
+    const SQL_FIELDS: &str = "SELECT id, name, type FROM fields WHERE list_id = ? ORDER BY pos ASC";
+    let mut res = sqlx::query(SQL_FIELDS).bind(list_id).fetch(pool);
+    while let Some(row) = res.try_next().await? {
+        let field_id: FieldId = row.try_get("id")?;
+        let field_type: &str = row.try_get("type")?;
+        let field_type = FieldType::from_name(field_type).ok_or_else(|| ReadError {
+            msg: format!("invalid field type {}", field_type),
+        })?;
+        tierlist.fields.push(FieldDef {
+            id: field_id,
+            name: row.try_get("name")?,
+            field_type,
+        });
+        tierlist.field_max_id = tierlist.field_max_id.max(field_id);
+    }
+
+    const SQL_ITEM_FIELDS: &str =
+        "SELECT item_id, field_id, value FROM item_fields WHERE list_id = ?";
+    let mut res = sqlx::query(SQL_ITEM_FIELDS).bind(list_id).fetch(pool);
+    while let Some(row) = res.try_next().await? {
+        let item_id: ItemId = row.try_get("item_id")?;
+        if let Some(&idx) = item_pos.get(&item_id) {
+            tierlist.items[idx]
+                .fields
+                .insert(row.try_get("field_id")?, row.try_get("value")?);
+        }
+    }
+
+    const SQL_POS: &str =
+        "SELECT item_id, tier_id FROM items_pos WHERE list_id = ? ORDER BY pos ASC";
+    let mut res = sqlx::query(SQL_POS).bind(list_id).fetch(pool);
     let mut items_in_list = HashSet::new();
     while let Some(row) = res.try_next().await? {
         let item_id: ItemId = row.try_get("item_id")?;
@@ -108,306 +306,2583 @@ async fn read_tierlist(pool: &SqlitePool, thumb_dir: &Path) -> DbResult<TierList
         }
     }
 
+    // items_pos は同じアイテムを複数の tier に置けてしまう
+    tierlist.repair();
     Ok(tierlist)
 }
 
-async fn write_tierlist(pool: &SqlitePool, tierlist: &TierList) -> DbResult<()> {
-    sqlx::migrate!("./sql").run(pool).await?;
-    let mut tx = pool.begin().await?;
+// DB に書き込んだサムネイル。from のファイルを to (展開先) にコピーしてある
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SavedThumb {
+    pub item: ItemId,
+    pub from: String,
+    pub to: String,
+}
 
-    // cleanup
-    sqlx::query("DELETE FROM tierlist;")
-        .execute(&mut *tx)
-        .await?;
-    sqlx::query("DELETE FROM tiers;").execute(&mut *tx).await?;
-    sqlx::query("DELETE FROM items;").execute(&mut *tx).await?;
-    sqlx::query("DELETE FROM items_pos;")
-        .execute(&mut *tx)
-        .await?;
+// 書き込んだサムネイルを展開先のパスに向ける。次に保存するときに読み直して書き込まない
+// 保存している間に別の画像に変えたアイテムはそのままにする
+pub(crate) fn use_saved_thumbs(tierlist: &mut TierList, saved: &[SavedThumb]) {
+    for thumb in saved {
+        if let Ok(idx) = tierlist.item_index(thumb.item) {
+            let item = &mut tierlist.items[idx];
+            if item.thumb.as_deref() == Some(thumb.from.as_str()) {
+                item.thumb = Some(thumb.to.clone());
+            }
+        }
+    }
+}
 
-    const SQL_TIERLIST: &str = "INSERT INTO tierlist(title) VALUES (?)";
-    sqlx::query(SQL_TIERLIST)
-        .bind(&tierlist.title)
-        .execute(&mut *tx)
-        .await?;
+// 書き込んだサムネイルを返すので、use_saved_thumbs で編集中の tierlist に反映する
+pub(crate) async fn write_tierlist(
+    pool: &SqlitePool,
+    list_id: ListId,
+    tierlist: &TierList,
+    thumb_dir: &Path,
+) -> DbResult<Vec<SavedThumb>> {
+    let mut tierlist = tierlist.clone();
+    tierlist.repair();
+    let tierlist = &tierlist;
+    let cache_dir = thumb_cache_dir(pool, thumb_dir).await?;
+    let mut tx = pool.begin().await?;
 
-    const SQL_TIERS: &str = "INSERT INTO tiers(id, pos, title) ";
-    let mut qbuilder: QueryBuilder<Sqlite> = QueryBuilder::new(SQL_TIERS);
-    qbuilder.push_values(tierlist.tiers.iter().enumerate(), |mut b, (pos, tier)| {
-        b.push_bind(tier.id)
-            .push_bind(pos as i64)
-            .push_bind(&tier.title);
-    });
-    qbuilder.build().execute(&mut *tx).await?;
-
-    const SQL_ITEMS: &str = "INSERT INTO items(id, name, url, thumb, memo) ";
-    let mut images = HashMap::new();
-    for item in tierlist.items.iter() {
-        if let Some(thumb_file) = &item.thumb {
-            let mut thumb_file = File::open(thumb_file).await?;
-            let mut buf = vec![];
-            thumb_file.read_to_end(&mut buf).await?;
-            images.insert(item.id, buf);
+    // title
+    const SQL_CUR_TITLE: &str = "SELECT title FROM tierlist WHERE id = ?";
+    let cur_title: Option<String> = sqlx::query_scalar(SQL_CUR_TITLE)
+        .bind(list_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    match cur_title {
+        Some(title) if title == tierlist.title => {}
+        Some(_) => {
+            sqlx::query("UPDATE tierlist SET title = ? WHERE id = ?")
+                .bind(&tierlist.title)
+                .bind(list_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        None => {
+            sqlx::query("INSERT INTO tierlist(id, title) VALUES (?, ?)")
+                .bind(list_id)
+                .bind(&tierlist.title)
+                .execute(&mut *tx)
+                .await?;
         }
     }
-    let mut qbuilder: QueryBuilder<Sqlite> = QueryBuilder::new(SQL_ITEMS);
-    qbuilder.push_values(tierlist.items.iter(), |mut b, item| {
-        b.push_bind(item.id)
-            .push_bind(&item.name)
-            .push_bind(&item.url)
-            .push_bind(images.get(&item.id))
-            .push_bind(&item.memo);
-    });
-    qbuilder.build().execute(&mut *tx).await?;
 
-    const SQL_POS: &str = "INSERT INTO items_pos(item_id, tier_id, pos) ";
-    let mut pos_list = vec![];
+    // placement_log: compare with what is stored before anything is overwritten
+    log_placements(&mut tx, list_id, tierlist).await?;
+
+    // items_pos: removed rows go first so that deleted tiers/items have nothing left to cascade
+    let mut new_pos = HashMap::new();
     for tier in tierlist.tiers.iter() {
         for (pos, &item_id) in tier.items.iter().enumerate() {
-            pos_list.push((item_id, tier.id, pos));
+            new_pos.insert((item_id, tier.id), pos as i64);
+        }
+    }
+    const SQL_CUR_POS: &str = "SELECT item_id, tier_id, pos FROM items_pos WHERE list_id = ?";
+    let mut cur_pos = HashMap::new();
+    for row in sqlx::query(SQL_CUR_POS)
+        .bind(list_id)
+        .fetch_all(&mut *tx)
+        .await?
+    {
+        let item_id: ItemId = row.try_get("item_id")?;
+        let tier_id: TierId = row.try_get("tier_id")?;
+        let pos: i64 = row.try_get("pos")?;
+        cur_pos.insert((item_id, tier_id), pos);
+    }
+    for &(item_id, tier_id) in cur_pos.keys() {
+        if !new_pos.contains_key(&(item_id, tier_id)) {
+            sqlx::query("DELETE FROM items_pos WHERE list_id = ? AND item_id = ? AND tier_id = ?")
+                .bind(list_id)
+                .bind(item_id)
+                .bind(tier_id)
+                .execute(&mut *tx)
+                .await?;
         }
     }
-    let mut qbuilder: QueryBuilder<Sqlite> = QueryBuilder::new(SQL_POS);
-    qbuilder.push_values(pos_list.iter(), |mut b, (item_id, tier_id, pos)| {
-        b.push_bind(item_id)
-            .push_bind(tier_id)
-            .push_bind(*pos as i64);
-    });
-    qbuilder.build().execute(&mut *tx).await?;
 
-    tx.commit().await?;
-    Ok(())
-}
+    // tiers
+    const SQL_CUR_TIERS: &str =
+        "SELECT id, pos, title, color, text_color, icon FROM tiers WHERE list_id = ?";
+    let mut cur_tiers = HashMap::new();
+    for row in sqlx::query(SQL_CUR_TIERS)
+        .bind(list_id)
+        .fetch_all(&mut *tx)
+        .await?
+    {
+        let tier_id: TierId = row.try_get("id")?;
+        let pos: i64 = row.try_get("pos")?;
+        let title: String = row.try_get("title")?;
+        let color: String = row.try_get("color")?;
+        let text_color: Option<String> = row.try_get("text_color")?;
+        let icon: Option<String> = row.try_get("icon")?;
+        cur_tiers.insert(tier_id, (pos, title, color, text_color, icon));
+    }
+    let new_tier_ids: HashSet<TierId> = tierlist.tiers.iter().map(|t| t.id).collect();
+    for &tier_id in cur_tiers.keys() {
+        if !new_tier_ids.contains(&tier_id) {
+            sqlx::query("DELETE FROM tiers WHERE list_id = ? AND id = ?")
+                .bind(list_id)
+                .bind(tier_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    for (pos, tier) in tierlist.tiers.iter().enumerate() {
+        let pos = pos as i64;
+        match cur_tiers.get(&tier.id) {
+            Some((cur_pos, cur_title, color, text_color, icon))
+                if *cur_pos == pos
+                    && *cur_title == tier.title
+                    && *color == tier.color
+                    && *text_color == tier.text_color
+                    && *icon == tier.icon => {}
+            Some(_) => {
+                const SQL_UPDATE_TIER: &str = "UPDATE tiers SET pos = ?, title = ?, color = ?, \
+                    text_color = ?, icon = ? WHERE list_id = ? AND id = ?";
+                sqlx::query(SQL_UPDATE_TIER)
+                    .bind(pos)
+                    .bind(&tier.title)
+                    .bind(&tier.color)
+                    .bind(&tier.text_color)
+                    .bind(&tier.icon)
+                    .bind(list_id)
+                    .bind(tier.id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            None => {
+                const SQL_INSERT_TIER: &str = "INSERT INTO tiers(list_id, id, pos, title, color, \
+                    text_color, icon) VALUES (?, ?, ?, ?, ?, ?, ?)";
+                sqlx::query(SQL_INSERT_TIER)
+                    .bind(list_id)
+                    .bind(tier.id)
+                    .bind(pos)
+                    .bind(&tier.title)
+                    .bind(&tier.color)
+                    .bind(&tier.text_color)
+                    .bind(&tier.icon)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+    }
 
-async fn open_db(cur_pool: &mut Option<SqlitePool>, path: &Path) -> DbResult<SqlitePool> {
-    if let Some(cur_pool) = cur_pool {
-        cur_pool.close().await;
+    // items
+    const SQL_CUR_ITEMS: &str = "SELECT id, name, url, memo, created_at, updated_at, moved_at, \
+        thumb IS NOT NULL AS has_thumb FROM items WHERE list_id = ?";
+    let mut cur_items = HashMap::new();
+    for row in sqlx::query(SQL_CUR_ITEMS)
+        .bind(list_id)
+        .fetch_all(&mut *tx)
+        .await?
+    {
+        let item_id: ItemId = row.try_get("id")?;
+        let name: String = row.try_get("name")?;
+        let url: String = row.try_get("url")?;
+        let memo: String = row.try_get("memo")?;
+        let has_thumb: bool = row.try_get("has_thumb")?;
+        let times = ItemTimes {
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+            moved_at: row.try_get("moved_at")?,
+        };
+        cur_items.insert(item_id, (name, url, memo, times, has_thumb));
     }
-    connect(&path.to_string_lossy().to_string()).await
-}
+    let mut saved_thumbs = vec![];
+    let new_item_ids: HashSet<ItemId> = tierlist.items.iter().map(|it| it.id).collect();
+    for &item_id in cur_items.keys() {
+        if !new_item_ids.contains(&item_id) {
+            sqlx::query("DELETE FROM items WHERE list_id = ? AND id = ?")
+                .bind(list_id)
+                .bind(item_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    for item in tierlist.items.iter() {
+        let cur = cur_items.get(&item.id);
 
-pub mod commands {
-    use super::*;
-    use tauri::{api::dialog::blocking::FileDialogBuilder, async_runtime::Mutex, State};
-    use tempdir::TempDir;
+        // 読み込み時に展開したサムネイルのパスのままであれば、DB 上の画像から変わっていない
+        let thumb_changed = match (&item.thumb, cur) {
+            (None, Some(&(_, _, _, _, has_thumb))) => has_thumb,
+            (Some(thumb), Some(&(_, _, _, _, true))) => {
+                Path::new(thumb) != thumb_cache_path(&cache_dir, list_id, item.id)
+            }
+            _ => true,
+        };
+        let thumb = match &item.thumb {
+            Some(thumb) if thumb_changed => {
+                let mut thumb_file = File::open(thumb).await?;
+                let mut buf = vec![];
+                thumb_file.read_to_end(&mut buf).await?;
+                let to = thumb_cache_path(&cache_dir, list_id, item.id);
+                if Path::new(thumb) != to {
+                    saved_thumbs.push(SavedThumb {
+                        item: item.id,
+                        from: thumb.clone(),
+                        to: to.to_string_lossy().to_string(),
+                    });
+                }
+                Some(buf)
+            }
+            _ => None,
+        };
 
-    #[tauri::command]
-    pub async fn read_tierlist_from_db(
-        pool: State<'_, Mutex<Option<SqlitePool>>>,
-        thumb_dir: State<'_, TempDir>,
-    ) -> Result<TierList, String> {
-        let mut pool = pool.lock().await;
-        let path = FileDialogBuilder::new()
-            .pick_file()
-            .ok_or("DB not opened".to_owned())?;
-        *pool = Some(
-            open_db(&mut *pool, &path)
-                .await
-                .map_err(|e| e.to_string())?,
-        );
-        if let Some(pool) = &*pool {
-            read_tierlist(pool, thumb_dir.path())
-                .await
-                .map_err(|e| e.to_string())
-        } else {
-            Err("DB not opened".to_owned())
+        match cur {
+            Some((name, url, memo, times, _))
+                if *name == item.name
+                    && *url == item.url
+                    && *memo == item.memo
+                    && *times == item.times =>
+            {
+                if thumb_changed {
+                    sqlx::query("UPDATE items SET thumb = ? WHERE list_id = ? AND id = ?")
+                        .bind(thumb)
+                        .bind(list_id)
+                        .bind(item.id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+            Some(_) => {
+                let mut qbuilder: QueryBuilder<Sqlite> =
+                    QueryBuilder::new("UPDATE items SET name = ");
+                qbuilder
+                    .push_bind(&item.name)
+                    .push(", url = ")
+                    .push_bind(&item.url)
+                    .push(", memo = ")
+                    .push_bind(&item.memo)
+                    .push(", created_at = ")
+                    .push_bind(item.times.created_at)
+                    .push(", updated_at = ")
+                    .push_bind(item.times.updated_at)
+                    .push(", moved_at = ")
+                    .push_bind(item.times.moved_at);
+                if thumb_changed {
+                    qbuilder.push(", thumb = ").push_bind(thumb);
+                }
+                qbuilder
+                    .push(" WHERE list_id = ")
+                    .push_bind(list_id)
+                    .push(" AND id = ")
+                    .push_bind(item.id);
+                qbuilder.build().execute(&mut *tx).await?;
+            }
+            None => {
+                const SQL_INSERT_ITEM: &str =
+                    "INSERT INTO items(list_id, id, name, url, thumb, memo, \
+                    created_at, updated_at, moved_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)";
+                sqlx::query(SQL_INSERT_ITEM)
+                    .bind(list_id)
+                    .bind(item.id)
+                    .bind(&item.name)
+                    .bind(&item.url)
+                    .bind(thumb)
+                    .bind(&item.memo)
+                    .bind(item.times.created_at)
+                    .bind(item.times.updated_at)
+                    .bind(item.times.moved_at)
+                    .execute(&mut *tx)
+                    .await?;
+            }
         }
     }
 
-    #[tauri::command]
-    pub async fn write_tierlist_to_db(
-        pool: State<'_, Mutex<Option<SqlitePool>>>,
-        tierlist: TierList,
-    ) -> Result<(), String> {
-        let mut pool = pool.lock().await;
-        if pool.is_none() {
-            let path = FileDialogBuilder::new()
-                .save_file()
-                .ok_or("DB not opened".to_owned())?;
-            *pool = Some(
-                open_db(&mut *pool, &path)
-                    .await
-                    .map_err(|e| e.to_string())?,
-            );
-        }
-        if let Some(pool) = &*pool {
-            write_tierlist(pool, &tierlist)
-                .await
-                .map_err(|e| e.to_string())
-        } else {
-            Err("DB not opened".to_owned())
+    // tags: deleted items have already taken their item_tags rows with them
+    const SQL_CUR_TAGS: &str = "SELECT id, name FROM tags WHERE list_id = ?";
+    let mut cur_tags = HashMap::new();
+    for row in sqlx::query(SQL_CUR_TAGS)
+        .bind(list_id)
+        .fetch_all(&mut *tx)
+        .await?
+    {
+        let tag_id: i64 = row.try_get("id")?;
+        let name: String = row.try_get("name")?;
+        cur_tags.insert(name, tag_id);
+    }
+    let mut tag_max_id = cur_tags.values().copied().max().unwrap_or(0);
+    let mut new_item_tags = HashSet::new();
+    for item in tierlist.items.iter() {
+        for name in item.tags.iter() {
+            let tag_id = match cur_tags.get(name) {
+                Some(&tag_id) => tag_id,
+                None => {
+                    tag_max_id += 1;
+                    sqlx::query("INSERT INTO tags(list_id, id, name) VALUES (?, ?, ?)")
+                        .bind(list_id)
+                        .bind(tag_max_id)
+                        .bind(name)
+                        .execute(&mut *tx)
+                        .await?;
+                    cur_tags.insert(name.clone(), tag_max_id);
+                    tag_max_id
+                }
+            };
+            new_item_tags.insert((item.id, tag_id));
         }
     }
-}
+    const SQL_CUR_ITEM_TAGS: &str = "SELECT item_id, tag_id FROM item_tags WHERE list_id = ?";
+    let mut cur_item_tags = HashSet::new();
+    for row in sqlx::query(SQL_CUR_ITEM_TAGS)
+        .bind(list_id)
+        .fetch_all(&mut *tx)
+        .await?
+    {
+        let item_id: ItemId = row.try_get("item_id")?;
+        let tag_id: i64 = row.try_get("tag_id")?;
+        cur_item_tags.insert((item_id, tag_id));
+    }
+    for &(item_id, tag_id) in cur_item_tags.difference(&new_item_tags) {
+        sqlx::query("DELETE FROM item_tags WHERE list_id = ? AND item_id = ? AND tag_id = ?")
+            .bind(list_id)
+            .bind(item_id)
+            .bind(tag_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    for &(item_id, tag_id) in new_item_tags.difference(&cur_item_tags) {
+        sqlx::query("INSERT INTO item_tags(list_id, item_id, tag_id) VALUES (?, ?, ?)")
+            .bind(list_id)
+            .bind(item_id)
+            .bind(tag_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    // どのアイテムにも付いていないタグは消す
+    const SQL_UNUSED_TAGS: &str = "DELETE FROM tags WHERE list_id = ? AND id NOT IN \
+        (SELECT tag_id FROM item_tags WHERE list_id = ?)";
+    sqlx::query(SQL_UNUSED_TAGS)
+        .bind(list_id)
+        .bind(list_id)
+        .execute(&mut *tx)
+        .await?;
 
-#[cfg(test)]
-mod tests {
-    use tempdir::TempDir;
+    // fields: removed definitions take their item_fields rows with them
+    const SQL_CUR_FIELDS: &str = "SELECT id, pos, name, type FROM fields WHERE list_id = ?";
+    let mut cur_fields = HashMap::new();
+    for row in sqlx::query(SQL_CUR_FIELDS)
+        .bind(list_id)
+        .fetch_all(&mut *tx)
+        .await?
+    {
+        let field_id: FieldId = row.try_get("id")?;
+        let pos: i64 = row.try_get("pos")?;
+        let name: String = row.try_get("name")?;
+        let field_type: String = row.try_get("type")?;
+        cur_fields.insert(field_id, (pos, name, field_type));
+    }
+    let new_field_ids: HashSet<FieldId> = tierlist.fields.iter().map(|f| f.id).collect();
+    for &field_id in cur_fields.keys() {
+        if !new_field_ids.contains(&field_id) {
+            sqlx::query("DELETE FROM fields WHERE list_id = ? AND id = ?")
+                .bind(list_id)
+                .bind(field_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    for (pos, field) in tierlist.fields.iter().enumerate() {
+        let pos = pos as i64;
+        let field_type = field.field_type.as_str();
+        match cur_fields.get(&field.id) {
+            Some((cur_pos, name, cur_type))
+                if *cur_pos == pos && *name == field.name && cur_type == field_type => {}
+            Some((_, _, cur_type)) if cur_type != field_type => {
+                // 型が変わったら値は読み直せないので消す
+                sqlx::query("DELETE FROM fields WHERE list_id = ? AND id = ?")
+                    .bind(list_id)
+                    .bind(field.id)
+                    .execute(&mut *tx)
+                    .await?;
+                const SQL_INSERT_FIELD: &str =
+                    "INSERT INTO fields(list_id, id, pos, name, type) VALUES (?, ?, ?, ?, ?)";
+                sqlx::query(SQL_INSERT_FIELD)
+                    .bind(list_id)
+                    .bind(field.id)
+                    .bind(pos)
+                    .bind(&field.name)
+                    .bind(field_type)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            Some(_) => {
+                const SQL_UPDATE_FIELD: &str =
+                    "UPDATE fields SET pos = ?, name = ? WHERE list_id = ? AND id = ?";
+                sqlx::query(SQL_UPDATE_FIELD)
+                    .bind(pos)
+                    .bind(&field.name)
+                    .bind(list_id)
+                    .bind(field.id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            None => {
+                const SQL_INSERT_FIELD: &str =
+                    "INSERT INTO fields(list_id, id, pos, name, type) VALUES (?, ?, ?, ?, ?)";
+                sqlx::query(SQL_INSERT_FIELD)
+                    .bind(list_id)
+                    .bind(field.id)
+                    .bind(pos)
+                    .bind(&field.name)
+                    .bind(field_type)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+    }
 
-    use super::*;
+    // item_fields
+    const SQL_CUR_ITEM_FIELDS: &str =
+        "SELECT item_id, field_id, value FROM item_fields WHERE list_id = ?";
+    let mut cur_values = HashMap::new();
+    for row in sqlx::query(SQL_CUR_ITEM_FIELDS)
+        .bind(list_id)
+        .fetch_all(&mut *tx)
+        .await?
+    {
+        let item_id: ItemId = row.try_get("item_id")?;
+        let field_id: FieldId = row.try_get("field_id")?;
+        let value: String = row.try_get("value")?;
+        cur_values.insert((item_id, field_id), value);
+    }
+    let mut new_values = HashMap::new();
+    for item in tierlist.items.iter() {
+        for (&field_id, value) in item.fields.iter() {
+            new_values.insert((item.id, field_id), value);
+        }
+    }
+    for &(item_id, field_id) in cur_values.keys() {
+        if !new_values.contains_key(&(item_id, field_id)) {
+            const SQL_DELETE_VALUE: &str =
+                "DELETE FROM item_fields WHERE list_id = ? AND item_id = ? AND field_id = ?";
+            sqlx::query(SQL_DELETE_VALUE)
+                .bind(list_id)
+                .bind(item_id)
+                .bind(field_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    for (&(item_id, field_id), &value) in new_values.iter() {
+        match cur_values.get(&(item_id, field_id)) {
+            Some(cur) if cur == value => {}
+            Some(_) => {
+                const SQL_UPDATE_VALUE: &str = "UPDATE item_fields SET value = ? \
+                    WHERE list_id = ? AND item_id = ? AND field_id = ?";
+                sqlx::query(SQL_UPDATE_VALUE)
+                    .bind(value)
+                    .bind(list_id)
+                    .bind(item_id)
+                    .bind(field_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            None => {
+                const SQL_INSERT_VALUE: &str =
+                    "INSERT INTO item_fields(list_id, item_id, field_id, value) VALUES (?, ?, ?, ?)";
+                sqlx::query(SQL_INSERT_VALUE)
+                    .bind(list_id)
+                    .bind(item_id)
+                    .bind(field_id)
+                    .bind(value)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+    }
 
-    #[tokio::test]
-    async fn read_tierlist_test() {
-        let dir = TempDir::new("db_test").unwrap();
-        let db_url = dir.path().join("test.db3").to_string_lossy().to_string();
-        let pool = connect(&db_url).await.unwrap();
-        sqlx::migrate!("./sql").run(&pool).await.unwrap();
+    // items_pos: added or moved rows
+    for (&(item_id, tier_id), &pos) in new_pos.iter() {
+        match cur_pos.get(&(item_id, tier_id)) {
+            Some(&cur) if cur == pos => {}
+            Some(_) => {
+                const SQL_UPDATE_POS: &str =
+                    "UPDATE items_pos SET pos = ? WHERE list_id = ? AND item_id = ? AND tier_id = ?";
+                sqlx::query(SQL_UPDATE_POS)
+                    .bind(pos)
+                    .bind(list_id)
+                    .bind(item_id)
+                    .bind(tier_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            None => {
+                const SQL_INSERT_POS: &str =
+                    "INSERT INTO items_pos(list_id, item_id, tier_id, pos) VALUES (?, ?, ?, ?)";
+                sqlx::query(SQL_INSERT_POS)
+                    .bind(list_id)
+                    .bind(item_id)
+                    .bind(tier_id)
+                    .bind(pos)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+    }
 
-        let tierlist_title = "list";
-        const SQL_TIERLIST: &str = "INSERT INTO tierlist(title) VALUES (?)";
-        sqlx::query(SQL_TIERLIST)
-            .bind(tierlist_title)
-            .execute(&pool)
-            .await
-            .unwrap();
+    tx.commit().await?;
+    for thumb in saved_thumbs.iter() {
+        tokio::fs::copy(&thumb.from, &thumb.to).await?;
+    }
+    Ok(saved_thumbs)
+}
 
-        let tiers = vec![(1, 0, "tier1"), (2, 1, "tier2"), (5, 2, "tier3")];
-        const SQL_TIERS: &str = "INSERT INTO tiers(id, pos, title) ";
-        let mut qbuilder: QueryBuilder<Sqlite> = QueryBuilder::new(SQL_TIERS);
-        qbuilder.push_values(tiers.iter(), |mut b, (id, pos, title)| {
-            b.push_bind(id).push_bind(pos).push_bind(*title);
+async fn list_tierlists(pool: &SqlitePool) -> DbResult<Vec<TierListInfo>> {
+    const SQL_LISTS: &str = "SELECT id, title FROM tierlist ORDER BY id ASC";
+    let mut res = sqlx::query(SQL_LISTS).fetch(pool);
+    let mut lists = vec![];
+    while let Some(row) = res.try_next().await? {
+        lists.push(TierListInfo {
+            id: row.try_get("id")?,
+            title: row.try_get("title")?,
         });
-        qbuilder.build().execute(&pool).await.unwrap();
+    }
+    Ok(lists)
+}
 
-        let items = vec![
-            (1, "item1", "url1", Some(vec![0u8, 1, 2]), "memo1"),
-            (2, "item2", "url2", None, "memo2"),
-            (3, "item3", "url3", Some(vec![3u8, 4, 5]), "memo3"),
-        ];
-        const SQL_ITEMS: &str = "INSERT INTO items(id, name, url, thumb) ";
-        let mut qbuilder: QueryBuilder<Sqlite> = QueryBuilder::new(SQL_ITEMS);
-        qbuilder.push_values(items.iter(), |mut b, (id, name, url, thumb, memo)| {
-            b.push_bind(id)
-                .push_bind(*name)
-                .push_bind(*url)
-                .push_bind(thumb)
-                .push_bind(*memo);
-        });
-        qbuilder.build().execute(&pool).await.unwrap();
+async fn create_tierlist(pool: &SqlitePool, title: &str) -> DbResult<ListId> {
+    const SQL_CREATE: &str = "INSERT INTO tierlist(title) VALUES (?)";
+    let res = sqlx::query(SQL_CREATE).bind(title).execute(pool).await?;
+    Ok(res.last_insert_rowid())
+}
 
-        let items_pos = vec![(1, 2, 1), (2, 2, 0)];
-        const SQL_POS: &str = "INSERT INTO items_pos(item_id, tier_id, pos) ";
-        let mut qbuilder: QueryBuilder<Sqlite> = QueryBuilder::new(SQL_POS);
-        qbuilder.push_values(items_pos.iter(), |mut b, (item_id, tier_id, pos)| {
-            b.push_bind(item_id).push_bind(tier_id).push_bind(pos);
-        });
-        qbuilder.build().execute(&pool).await.unwrap();
+async fn rename_tierlist(pool: &SqlitePool, list_id: ListId, title: &str) -> DbResult<()> {
+    const SQL_RENAME: &str = "UPDATE tierlist SET title = ? WHERE id = ?";
+    let res = sqlx::query(SQL_RENAME)
+        .bind(title)
+        .bind(list_id)
+        .execute(pool)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(ListNotFoundError { id: list_id }.into());
+    }
+    Ok(())
+}
 
-        let thumb_dir = TempDir::new("test_thumb").unwrap();
-        let tierlist = read_tierlist(&pool, thumb_dir.path()).await.unwrap();
+// list_id の tierlist をサムネイルごと複製して、新しい tierlist の id を返す
+async fn duplicate_tierlist(pool: &SqlitePool, list_id: ListId, title: &str) -> DbResult<ListId> {
+    let mut tx = pool.begin().await?;
 
-        pool.close().await;
+    const SQL_EXISTS: &str = "SELECT id FROM tierlist WHERE id = ?";
+    sqlx::query(SQL_EXISTS)
+        .bind(list_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(ListNotFoundError { id: list_id })?;
 
-        assert_eq!(tierlist.title, tierlist_title);
-        assert_eq!(tierlist.tiers.len(), 3);
-        assert!(tierlist.items.iter().any(|it| it.id == 1));
-        assert!(tierlist.items.iter().any(|it| it.id == 2));
-        assert!(tierlist.items.iter().any(|it| it.id == 3));
-        assert_eq!(tierlist.items_pool, vec![3]);
+    const SQL_CREATE: &str = "INSERT INTO tierlist(title) VALUES (?)";
+    let new_id = sqlx::query(SQL_CREATE)
+        .bind(title)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
 
-        let tier2 = &tierlist.tiers[1];
-        assert_eq!(tier2.id, 2);
-        assert_eq!(tier2.title, tiers[1].2);
-        assert_eq!(tier2.items, vec![2, 1]);
+    const SQL_COPY_TIERS: &str =
+        "INSERT INTO tiers(list_id, id, pos, title, color, text_color, icon) \
+        SELECT ?, id, pos, title, color, text_color, icon FROM tiers WHERE list_id = ?";
+    const SQL_COPY_ITEMS: &str = "INSERT INTO items(list_id, id, name, url, thumb, memo, \
+        created_at, updated_at, moved_at) \
+        SELECT ?, id, name, url, thumb, memo, created_at, updated_at, moved_at \
+        FROM items WHERE list_id = ?";
+    const SQL_COPY_POS: &str = "INSERT INTO items_pos(list_id, item_id, tier_id, pos) \
+        SELECT ?, item_id, tier_id, pos FROM items_pos WHERE list_id = ?";
+    const SQL_COPY_TAGS: &str = "INSERT INTO tags(list_id, id, name) \
+        SELECT ?, id, name FROM tags WHERE list_id = ?";
+    const SQL_COPY_ITEM_TAGS: &str = "INSERT INTO item_tags(list_id, item_id, tag_id) \
+        SELECT ?, item_id, tag_id FROM item_tags WHERE list_id = ?";
+    const SQL_COPY_FIELDS: &str = "INSERT INTO fields(list_id, id, pos, name, type) \
+        SELECT ?, id, pos, name, type FROM fields WHERE list_id = ?";
+    const SQL_COPY_ITEM_FIELDS: &str =
+        "INSERT INTO item_fields(list_id, item_id, field_id, value) \
+        SELECT ?, item_id, field_id, value FROM item_fields WHERE list_id = ?";
+    const SQL_COPY_LOG: &str = "INSERT INTO placement_log(list_id, item_id, item_name, kind, \
+        from_tier, from_title, to_tier, to_title, pos, at) \
+        SELECT ?, item_id, item_name, kind, from_tier, from_title, to_tier, to_title, pos, at \
+        FROM placement_log WHERE list_id = ? ORDER BY id ASC";
+    for sql in [
+        SQL_COPY_TIERS,
+        SQL_COPY_ITEMS,
+        SQL_COPY_POS,
+        SQL_COPY_TAGS,
+        SQL_COPY_ITEM_TAGS,
+        SQL_COPY_FIELDS,
+        SQL_COPY_ITEM_FIELDS,
+        SQL_COPY_LOG,
+    ] {
+        sqlx::query(sql)
+            .bind(new_id)
+            .bind(list_id)
+            .execute(&mut *tx)
+            .await?;
+    }
 
-        let item1 = tierlist.items.iter().find(|it| it.id == 1).unwrap();
-        assert_eq!(item1.id, 1);
-        assert_eq!(item1.name, "item1");
-        assert_eq!(item1.url, "url1");
-        assert_eq!(item1.memo, "memo1");
+    // スナップショットは id が変わるので、サムネイルは 1 つずつ新しい id に付け替える
+    const SQL_SNAPSHOT_IDS: &str = "SELECT id FROM snapshots WHERE list_id = ? ORDER BY id ASC";
+    const SQL_COPY_SNAPSHOT: &str = "INSERT INTO snapshots(list_id, name, created_at, data) \
+        SELECT ?, name, created_at, data FROM snapshots WHERE id = ?";
+    const SQL_COPY_SNAPSHOT_THUMBS: &str =
+        "INSERT INTO snapshot_thumbs(snapshot_id, item_id, thumb) \
+        SELECT ?, item_id, thumb FROM snapshot_thumbs WHERE snapshot_id = ?";
+    let snapshot_ids: Vec<SnapshotId> = sqlx::query_scalar(SQL_SNAPSHOT_IDS)
+        .bind(list_id)
+        .fetch_all(&mut *tx)
+        .await?;
+    for snapshot_id in snapshot_ids {
+        let new_snapshot_id = sqlx::query(SQL_COPY_SNAPSHOT)
+            .bind(new_id)
+            .bind(snapshot_id)
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
+        sqlx::query(SQL_COPY_SNAPSHOT_THUMBS)
+            .bind(new_snapshot_id)
+            .bind(snapshot_id)
+            .execute(&mut *tx)
+            .await?;
+    }
 
-        let mut thumb1_file = File::open(item1.thumb.as_ref().unwrap()).await.unwrap();
-        let mut thumb1 = vec![];
-        thumb1_file.read_to_end(&mut thumb1).await.unwrap();
-        assert_eq!(thumb1, vec![0, 1, 2]);
+    tx.commit().await?;
+    Ok(new_id)
+}
+
+// tierlist 以外のテーブルは ON DELETE CASCADE で一緒に消える
+async fn delete_tierlist(pool: &SqlitePool, list_id: ListId) -> DbResult<()> {
+    const SQL_DELETE: &str = "DELETE FROM tierlist WHERE id = ?";
+    let res = sqlx::query(SQL_DELETE).bind(list_id).execute(pool).await?;
+    if res.rows_affected() == 0 {
+        return Err(ListNotFoundError { id: list_id }.into());
     }
+    Ok(())
+}
 
-    #[tokio::test]
-    async fn write_tierlist_test() {
-        let img_dir = TempDir::new("test_thumb").unwrap();
-        let thumb1_path = img_dir.path().join("thumb1");
-        {
-            let mut thumb1_file = File::create(&thumb1_path).await.unwrap();
-            thumb1_file.write_all(&[0, 1, 2]).await.unwrap();
-        }
+// 保存されている状態から tier が変わったアイテムを placement_log に追記する
+// 時刻は分かればアイテムを追加した、移した時刻にする。ただし元に戻すと古い時刻に
+// 戻るので、そのアイテムで最後に記録した時刻より前にはしない
+async fn log_placements(
+    conn: &mut SqliteConnection,
+    list_id: ListId,
+    tierlist: &TierList,
+) -> DbResult<()> {
+    const SQL_CUR_TIERS: &str = "SELECT id, title FROM tiers WHERE list_id = ?";
+    let mut cur_titles = HashMap::new();
+    for row in sqlx::query(SQL_CUR_TIERS)
+        .bind(list_id)
+        .fetch_all(&mut *conn)
+        .await?
+    {
+        let tier_id: TierId = row.try_get("id")?;
+        let title: String = row.try_get("title")?;
+        cur_titles.insert(tier_id, title);
+    }
+    const SQL_CUR_PLACES: &str = "SELECT items.id, items.name, items_pos.tier_id FROM items \
+        LEFT JOIN items_pos ON items_pos.list_id = items.list_id AND items_pos.item_id = items.id \
+        WHERE items.list_id = ?";
+    let mut cur_places = HashMap::new();
+    for row in sqlx::query(SQL_CUR_PLACES)
+        .bind(list_id)
+        .fetch_all(&mut *conn)
+        .await?
+    {
+        let item_id: ItemId = row.try_get("id")?;
+        let name: String = row.try_get("name")?;
+        let tier_id: Option<TierId> = row.try_get("tier_id")?;
+        cur_places.insert(item_id, (name, tier_id));
+    }
+    const SQL_LAST_AT: &str =
+        "SELECT item_id, MAX(at) AS at FROM placement_log WHERE list_id = ? GROUP BY item_id";
+    let mut last_at = HashMap::new();
+    for row in sqlx::query(SQL_LAST_AT)
+        .bind(list_id)
+        .fetch_all(&mut *conn)
+        .await?
+    {
+        let item_id: ItemId = row.try_get("item_id")?;
+        let at: Timestamp = row.try_get("at")?;
+        last_at.insert(item_id, at);
+    }
 
-        let tierlist = TierList {
-            title: "list".to_owned(),
-            tiers: vec![
-                Tier {
-                    id: 1,
-                    title: "tier1".to_owned(),
-                    items: vec![],
+    // (item, name, kind, from, to, pos, at)
+    let mut entries = vec![];
+    for item in tierlist.items.iter() {
+        let new_place = tierlist.find_item(item.id);
+        let to = new_place.and_then(|(tier, _)| tier);
+        let pos = new_place
+            .filter(|(tier, _)| tier.is_some())
+            .map(|(_, pos)| pos as i64);
+        match cur_places.remove(&item.id) {
+            None => {
+                let at = Some(item.times.created_at).filter(|&at| at > 0);
+                entries.push((item.id, item.name.clone(), "added", None, to, pos, at));
+            }
+            Some((_, from)) if from != to => {
+                let at = Some(item.times.moved_at).filter(|&at| at > 0);
+                entries.push((item.id, item.name.clone(), "moved", from, to, pos, at));
+            }
+            Some(_) => {}
+        }
+    }
+    let mut removed: Vec<(ItemId, (String, Option<TierId>))> = cur_places.into_iter().collect();
+    removed.sort_by_key(|(id, _)| *id);
+    for (item_id, (name, from)) in removed {
+        entries.push((item_id, name, "removed", from, None, None, None));
+    }
+
+    let at_now = now();
+    for (item_id, name, kind, from, to, pos, at) in entries {
+        let at = at
+            .unwrap_or(at_now)
+            .max(last_at.get(&item_id).copied().unwrap_or(0));
+        let from_title = from.and_then(|id| cur_titles.get(&id).cloned());
+        let to_title = to.and_then(|id| {
+            tierlist
+                .tier_index(id)
+                .ok()
+                .map(|idx| tierlist.tiers[idx].title.clone())
+        });
+        const SQL_LOG: &str = "INSERT INTO placement_log(list_id, item_id, item_name, kind, \
+            from_tier, from_title, to_tier, to_title, pos, at) \
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+        sqlx::query(SQL_LOG)
+            .bind(list_id)
+            .bind(item_id)
+            .bind(name)
+            .bind(kind)
+            .bind(from)
+            .bind(from_title)
+            .bind(to)
+            .bind(to_title)
+            .bind(pos)
+            .bind(at)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+fn placement_from_row(row: &sqlx::sqlite::SqliteRow) -> DbResult<Placement> {
+    let kind: &str = row.try_get("kind")?;
+    let place = |tier: &str, title: &str| -> DbResult<Place> {
+        let tier: Option<TierId> = row.try_get(tier)?;
+        Ok(match tier {
+            Some(id) => Place::Tier {
+                id,
+                title: row.try_get::<Option<String>, _>(title)?.unwrap_or_default(),
+            },
+            None => Place::Pool,
+        })
+    };
+    let from = match kind {
+        "added" => Place::Absent,
+        _ => place("from_tier", "from_title")?,
+    };
+    let to = match kind {
+        "removed" => Place::Absent,
+        _ => place("to_tier", "to_title")?,
+    };
+    let pos: Option<i64> = row.try_get("pos")?;
+    Ok(Placement {
+        item: row.try_get("item_id")?,
+        name: row.try_get("item_name")?,
+        from,
+        to,
+        pos: pos.map(|pos| pos as usize),
+        at: row.try_get("at")?,
+    })
+}
+
+// アイテムの置き場所の履歴 (記録した順)
+pub(crate) async fn item_placements(
+    pool: &SqlitePool,
+    list_id: ListId,
+    item_id: ItemId,
+) -> DbResult<Vec<Placement>> {
+    const SQL_ITEM_LOG: &str = "SELECT * FROM placement_log \
+        WHERE list_id = ? AND item_id = ? ORDER BY id ASC";
+    let rows = sqlx::query(SQL_ITEM_LOG)
+        .bind(list_id)
+        .bind(item_id)
+        .fetch_all(pool)
+        .await?;
+    rows.iter().map(placement_from_row).collect()
+}
+
+// since 以降に記録した tierlist 全体の置き場所の変化 (記録した順)
+pub(crate) async fn placements_since(
+    pool: &SqlitePool,
+    list_id: ListId,
+    since: Timestamp,
+) -> DbResult<Vec<Placement>> {
+    const SQL_LOG_SINCE: &str = "SELECT * FROM placement_log \
+        WHERE list_id = ? AND at >= ? ORDER BY id ASC";
+    let rows = sqlx::query(SQL_LOG_SINCE)
+        .bind(list_id)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+    rows.iter().map(placement_from_row).collect()
+}
+
+// tierlist をそのまま JSON にして残す。サムネイルは一時ディレクトリのパスではなく
+// 画像そのものを snapshot_thumbs に残す
+pub(crate) async fn create_snapshot(
+    pool: &SqlitePool,
+    list_id: ListId,
+    name: &str,
+    tierlist: &TierList,
+) -> DbResult<SnapshotId> {
+    let mut tx = pool.begin().await?;
+
+    const SQL_EXISTS: &str = "SELECT id FROM tierlist WHERE id = ?";
+    sqlx::query(SQL_EXISTS)
+        .bind(list_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(ListNotFoundError { id: list_id })?;
+
+    const SQL_SAME_NAME: &str = "SELECT id FROM snapshots WHERE list_id = ? AND name = ?";
+    let same_name = sqlx::query(SQL_SAME_NAME)
+        .bind(list_id)
+        .bind(name)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if same_name.is_some() {
+        return Err(SnapshotExistsError {
+            name: name.to_owned(),
+        }
+        .into());
+    }
+
+    const SQL_CREATE: &str =
+        "INSERT INTO snapshots(list_id, name, created_at, data) VALUES (?, ?, ?, ?)";
+    let id = sqlx::query(SQL_CREATE)
+        .bind(list_id)
+        .bind(name)
+        .bind(now())
+        .bind(serde_json::to_string(&snapshot::freeze(tierlist))?)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+    const SQL_THUMB: &str =
+        "INSERT INTO snapshot_thumbs(snapshot_id, item_id, thumb) VALUES (?, ?, ?)";
+    for item in tierlist.items.iter() {
+        if let Some(thumb) = &item.thumb {
+            let mut thumb_file = File::open(thumb).await?;
+            let mut buf = vec![];
+            thumb_file.read_to_end(&mut buf).await?;
+            sqlx::query(SQL_THUMB)
+                .bind(id)
+                .bind(item.id)
+                .bind(buf)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(id)
+}
+
+// 新しい順
+pub(crate) async fn list_snapshots(
+    pool: &SqlitePool,
+    list_id: ListId,
+) -> DbResult<Vec<SnapshotInfo>> {
+    const SQL_SNAPSHOTS: &str = "SELECT id, name, created_at FROM snapshots \
+        WHERE list_id = ? ORDER BY created_at DESC, id DESC";
+    let mut res = sqlx::query(SQL_SNAPSHOTS).bind(list_id).fetch(pool);
+    let mut snapshots = vec![];
+    while let Some(row) = res.try_next().await? {
+        snapshots.push(SnapshotInfo {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            created_at: row.try_get("created_at")?,
+        });
+    }
+    Ok(snapshots)
+}
+
+// サムネイルは thumb_dir の下に展開する
+// thumb_dir が None なら展開しない (差分を見るだけのとき)。サムネイルは None になる
+pub(crate) async fn read_snapshot(
+    pool: &SqlitePool,
+    list_id: ListId,
+    id: SnapshotId,
+    thumb_dir: Option<&Path>,
+) -> DbResult<TierList> {
+    const SQL_DATA: &str = "SELECT data FROM snapshots WHERE list_id = ? AND id = ?";
+    let row = sqlx::query(SQL_DATA)
+        .bind(list_id)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(SnapshotNotFoundError { id })?;
+    let data: String = row.try_get("data")?;
+    let mut tierlist: TierList = serde_json::from_str(&data).map_err(|e| ReadError {
+        msg: format!("snapshot {} is broken: {}", id, e),
+    })?;
+
+    let thumb_dir = match thumb_dir {
+        Some(thumb_dir) => thumb_dir,
+        None => {
+            tierlist.repair();
+            return Ok(tierlist);
+        }
+    };
+    const SQL_THUMBS: &str = "SELECT item_id, thumb FROM snapshot_thumbs WHERE snapshot_id = ?";
+    let cache_dir = thumb_cache_dir(pool, thumb_dir).await?;
+    let mut res = sqlx::query(SQL_THUMBS).bind(id).fetch(pool);
+    while let Some(row) = res.try_next().await? {
+        let item_id: ItemId = row.try_get("item_id")?;
+        let thumb: Vec<u8> = row.try_get("thumb")?;
+        let item = match tierlist.items.iter_mut().find(|it| it.id == item_id) {
+            Some(item) => item,
+            None => continue,
+        };
+        let thumb_path = cache_dir.join(format!("snapshot_{}_{}", id, item_id));
+        let mut thumb_file = File::create(&thumb_path).await?;
+        thumb_file.write_all(&thumb).await?;
+        item.thumb = Some(thumb_path.to_string_lossy().to_string());
+    }
+
+    tierlist.repair();
+    Ok(tierlist)
+}
+
+pub(crate) async fn delete_snapshot(
+    pool: &SqlitePool,
+    list_id: ListId,
+    id: SnapshotId,
+) -> DbResult<()> {
+    const SQL_DELETE: &str = "DELETE FROM snapshots WHERE list_id = ? AND id = ?";
+    let res = sqlx::query(SQL_DELETE)
+        .bind(list_id)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(SnapshotNotFoundError { id }.into());
+    }
+    Ok(())
+}
+
+// query の語をすべて名前かメモに含むアイテムの id を、よく合うものから順に返す
+// trigram の索引では 3 文字より短い語を探せないので、それは LIKE で絞り込む
+pub(crate) async fn search_items(
+    pool: &SqlitePool,
+    list_id: ListId,
+    query: &str,
+) -> DbResult<Vec<ItemId>> {
+    let (long, short): (Vec<&str>, Vec<&str>) = query
+        .split_whitespace()
+        .partition(|term| term.chars().count() >= 3);
+    if long.is_empty() && short.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut qbuilder: QueryBuilder<Sqlite> =
+        QueryBuilder::new("SELECT item_id FROM items_fts WHERE list_id = ");
+    qbuilder.push_bind(list_id);
+    if !long.is_empty() {
+        // 語はそれぞれフレーズとして探す (FTS5 の演算子として解釈させない)
+        let phrases: Vec<String> = long
+            .iter()
+            .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+            .collect();
+        qbuilder
+            .push(" AND items_fts MATCH ")
+            .push_bind(phrases.join(" "));
+    }
+    for term in short {
+        let pattern = format!(
+            "%{}%",
+            term.replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        qbuilder
+            .push(" AND (name LIKE ")
+            .push_bind(pattern.clone())
+            .push(" ESCAPE '\\' OR memo LIKE ")
+            .push_bind(pattern)
+            .push(" ESCAPE '\\')");
+    }
+    if long.is_empty() {
+        qbuilder.push(" ORDER BY item_id ASC");
+    } else {
+        // 名前に合うものをメモに合うものより上にする
+        qbuilder.push(" ORDER BY bm25(items_fts, 0.0, 0.0, 10.0, 1.0), item_id ASC");
+    }
+
+    let mut ids = vec![];
+    for row in qbuilder.build().fetch_all(pool).await? {
+        ids.push(row.try_get("item_id")?);
+    }
+    Ok(ids)
+}
+
+// ファイルのスキーマのバージョン。空のファイルなら 0
+async fn file_schema_version(pool: &SqlitePool) -> DbResult<i64> {
+    const SQL_TABLES: &str =
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'";
+    let tables: Vec<String> = sqlx::query_scalar(SQL_TABLES).fetch_all(pool).await?;
+    if !tables.iter().any(|t| t == "_sqlx_migrations") {
+        // 空なら新規作成。他のテーブルがあるファイルに 01_cleanup.sql を流すと消してしまう
+        if tables.is_empty() {
+            return Ok(0);
+        }
+        return Err(NotTierListFileError.into());
+    }
+
+    const SQL_APPLIED: &str = "SELECT MAX(version) FROM _sqlx_migrations";
+    let applied: Option<i64> = sqlx::query_scalar(SQL_APPLIED).fetch_one(pool).await?;
+    let recorded: i64 = sqlx::query_scalar("PRAGMA user_version")
+        .fetch_one(pool)
+        .await?;
+    Ok(applied.unwrap_or(0).max(recorded))
+}
+
+// マイグレーションを適用する前に、このビルドで開いてよいファイルか確かめる
+async fn check_schema(pool: &SqlitePool) -> DbResult<()> {
+    let version = file_schema_version(pool).await?;
+    if version > schema_version() {
+        return Err(SchemaTooNewError { version }.into());
+    }
+    Ok(())
+}
+
+// DB を開いて最新のスキーマにし、そのバージョンをファイルに記録する
+async fn open_db(path: &Path) -> DbResult<SqlitePool> {
+    let pool = connect(&path.to_string_lossy()).await?;
+    if let Err(e) = check_schema(&pool).await {
+        pool.close().await;
+        return Err(e);
+    }
+    MIGRATOR.run(&pool).await?;
+    sqlx::query(&format!("PRAGMA user_version = {}", schema_version()))
+        .execute(&pool)
+        .await?;
+    Ok(pool)
+}
+
+// 他の人のファイルを読むときは書き換えないように読み込み専用で開く。
+// マイグレーションもしないので、スキーマがこのビルドと同じものしか読めない
+async fn open_db_read_only(path: &Path) -> DbResult<SqlitePool> {
+    let opt = SqliteConnectOptions::new().filename(path).read_only(true);
+    let pool = SqlitePoolOptions::new().connect_with(opt).await?;
+    let res = match file_schema_version(&pool).await {
+        Ok(0) => Err(NotTierListFileError.into()),
+        Ok(version) if version > schema_version() => Err(SchemaTooNewError { version }.into()),
+        Ok(version) if version < schema_version() => Err(SchemaTooOldError { version }.into()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = res {
+        pool.close().await;
+        return Err(e);
+    }
+    Ok(pool)
+}
+
+// 開いている DB とは別のファイルにある tierlist の一覧
+pub(crate) async fn list_tierlists_file(path: &Path) -> DbResult<Vec<TierListInfo>> {
+    let pool = open_db_read_only(path).await?;
+    let lists = list_tierlists(&pool).await;
+    pool.close().await;
+    lists
+}
+
+async fn read_only_or_chosen(
+    pool: &SqlitePool,
+    list_id: Option<ListId>,
+    thumb_dir: &Path,
+) -> DbResult<TierList> {
+    let list_id = match list_id {
+        Some(list_id) => list_id,
+        None => {
+            let lists = list_tierlists(pool).await?;
+            if lists.len() != 1 {
+                return Err(ListNotChosenError { count: lists.len() }.into());
+            }
+            lists[0].id
+        }
+    };
+    read_tierlist(pool, list_id, thumb_dir).await
+}
+
+// 開いている DB とは別のファイルから tierlist を読む
+// list_id が None なら、ファイルにある tierlist が 1 つだけのときにそれを読む
+pub(crate) async fn read_tierlist_file(
+    path: &Path,
+    list_id: Option<ListId>,
+    thumb_dir: &Path,
+) -> DbResult<TierList> {
+    let pool = open_db_read_only(path).await?;
+    let tierlist = read_only_or_chosen(&pool, list_id, thumb_dir).await;
+    pool.close().await;
+    tierlist
+}
+
+// 開いている DB を pool に切り替えて、それまで開いていた DB を閉じる
+async fn switch_db(cur_pool: &mut Option<SqlitePool>, pool: SqlitePool) {
+    if let Some(old_pool) = cur_pool.replace(pool) {
+        old_pool.close().await;
+    }
+}
+
+// path に新しい DB を作って保存し、以降はその DB を開いている状態にする
+// 既にあるファイルを選んだときは (ダイアログで上書きを確かめてあるので) 中身を残さず作り直す
+async fn save_tierlist_as(
+    cur_pool: &mut Option<SqlitePool>,
+    path: &Path,
+    tierlist: &TierList,
+    thumb_dir: &Path,
+) -> DbResult<(ListId, Vec<SavedThumb>)> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    for suffix in ["-journal", "-wal", "-shm"] {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        let _ = tokio::fs::remove_file(sidecar).await;
+    }
+    let pool = open_db(path).await?;
+    let list_id = create_tierlist(&pool, &tierlist.title).await?;
+    let saved = write_tierlist(&pool, list_id, tierlist, thumb_dir).await?;
+    switch_db(cur_pool, pool).await;
+    Ok((list_id, saved))
+}
+
+pub mod commands {
+    use super::*;
+    use crate::{
+        autosave,
+        history::History,
+        recent::{self, RecentFiles},
+    };
+    use serde::Serialize;
+    use tauri::{api::dialog::blocking::FileDialogBuilder, async_runtime::Mutex, AppHandle, State};
+    use tempdir::TempDir;
+
+    #[derive(Clone, Debug, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct OpenedDbFile {
+        pub path: String,
+        pub lists: Vec<TierListInfo>,
+        pub list_id: Option<ListId>,
+    }
+
+    fn opened(pool: &Option<SqlitePool>) -> Result<&SqlitePool, String> {
+        pool.as_ref().ok_or_else(|| "DB not opened".to_owned())
+    }
+
+    // path を開いている DB にして、現在のファイルと最近開いたファイルを更新する
+    async fn open_file(
+        app: &AppHandle,
+        pool: &Mutex<Option<SqlitePool>>,
+        cur_file: &Mutex<Option<String>>,
+        cur_list: &Mutex<Option<ListId>>,
+        recent: &Mutex<RecentFiles>,
+        path: &str,
+    ) -> Result<Vec<TierListInfo>, String> {
+        let mut pool = pool.lock().await;
+        let new_pool = open_db(Path::new(path)).await.map_err(|e| e.to_string())?;
+        let lists = list_tierlists(&new_pool).await.map_err(|e| e.to_string())?;
+        switch_db(&mut pool, new_pool).await;
+        *cur_file.lock().await = Some(path.to_owned());
+        // 編集中の tierlist は新しく開いた DB のものではない
+        *cur_list.lock().await = None;
+
+        // 履歴を保存できなくてもファイルは開けている
+        let _ = recent::touch(app, &mut *recent.lock().await, path, None).await;
+        Ok(lists)
+    }
+
+    #[tauri::command]
+    pub async fn pick_db_file_to_open() -> Option<String> {
+        FileDialogBuilder::new()
+            .pick_file()
+            .map(|path| path.to_string_lossy().to_string())
+    }
+
+    #[tauri::command]
+    pub async fn pick_db_file_to_save() -> Option<String> {
+        FileDialogBuilder::new()
+            .save_file()
+            .map(|path| path.to_string_lossy().to_string())
+    }
+
+    #[tauri::command]
+    pub async fn open_db_file(
+        app: AppHandle,
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        cur_file: State<'_, Mutex<Option<String>>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        recent: State<'_, Mutex<RecentFiles>>,
+        path: String,
+    ) -> Result<Vec<TierListInfo>, String> {
+        open_file(&app, &pool, &cur_file, &cur_list, &recent, &path).await
+    }
+
+    // 設定で有効になっていれば、最後に開いたファイルを開き直す
+    #[tauri::command]
+    pub async fn reopen_last_file(
+        app: AppHandle,
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        cur_file: State<'_, Mutex<Option<String>>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        recent: State<'_, Mutex<RecentFiles>>,
+    ) -> Result<Option<OpenedDbFile>, String> {
+        let (path, last_list_id) = {
+            let recent = recent.lock().await;
+            match recent.last() {
+                Some(path) if recent.reopen_last && Path::new(path).exists() => {
+                    (path.to_owned(), recent.last_list_id(path))
+                }
+                _ => return Ok(None),
+            }
+        };
+        let lists = open_file(&app, &pool, &cur_file, &cur_list, &recent, &path).await?;
+        let list_id = last_list_id
+            .filter(|&id| lists.iter().any(|l| l.id == id))
+            .or_else(|| lists.first().map(|l| l.id));
+        Ok(Some(OpenedDbFile {
+            path,
+            lists,
+            list_id,
+        }))
+    }
+
+    // 読み込んだ tierlist を編集中の tierlist にする
+    #[allow(clippy::too_many_arguments)]
+    #[tauri::command]
+    pub async fn read_tierlist_from_db(
+        app: AppHandle,
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        thumb_dir: State<'_, TempDir>,
+        cur_file: State<'_, Mutex<Option<String>>>,
+        recent: State<'_, Mutex<RecentFiles>>,
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        list_id: ListId,
+    ) -> Result<TierList, String> {
+        let pool = pool.lock().await;
+        let read = read_tierlist(opened(&pool)?, list_id, thumb_dir.path())
+            .await
+            .map_err(|e| e.to_string())?;
+        *tierlist.lock().await = read.clone();
+        history.lock().await.clear();
+        *cur_list.lock().await = Some(list_id);
+        if let Some(path) = &*cur_file.lock().await {
+            let _ = recent::touch(&app, &mut *recent.lock().await, path, Some(list_id)).await;
+        }
+        Ok(read)
+    }
+
+    // 保存できたら自動保存の退避ファイルはいらない
+    #[tauri::command]
+    pub async fn write_tierlist_to_db(
+        app: AppHandle,
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        thumb_dir: State<'_, TempDir>,
+        tierlist: State<'_, Mutex<TierList>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        list_id: ListId,
+    ) -> Result<(), String> {
+        let pool = pool.lock().await;
+        let mut tierlist = tierlist.lock().await;
+        let saved = write_tierlist(opened(&pool)?, list_id, &tierlist, thumb_dir.path())
+            .await
+            .map_err(|e| e.to_string())?;
+        use_saved_thumbs(&mut tierlist, &saved);
+        *cur_list.lock().await = Some(list_id);
+        if let Ok(data_dir) = autosave::data_dir(&app) {
+            autosave::remove_recovery(&data_dir).await;
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[tauri::command]
+    pub async fn save_tierlist_as_db_file(
+        app: AppHandle,
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        thumb_dir: State<'_, TempDir>,
+        cur_file: State<'_, Mutex<Option<String>>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        recent: State<'_, Mutex<RecentFiles>>,
+        tierlist: State<'_, Mutex<TierList>>,
+        path: String,
+    ) -> Result<ListId, String> {
+        let mut pool = pool.lock().await;
+        let mut tierlist = tierlist.lock().await;
+        let (list_id, saved) =
+            save_tierlist_as(&mut pool, Path::new(&path), &tierlist, thumb_dir.path())
+                .await
+                .map_err(|e| e.to_string())?;
+        use_saved_thumbs(&mut tierlist, &saved);
+        *cur_file.lock().await = Some(path.clone());
+        *cur_list.lock().await = Some(list_id);
+        let _ = recent::touch(&app, &mut *recent.lock().await, &path, Some(list_id)).await;
+        if let Ok(data_dir) = autosave::data_dir(&app) {
+            autosave::remove_recovery(&data_dir).await;
+        }
+        Ok(list_id)
+    }
+
+    #[tauri::command]
+    pub async fn list_tierlists_in_db(
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+    ) -> Result<Vec<TierListInfo>, String> {
+        let pool = pool.lock().await;
+        list_tierlists(opened(&pool)?)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    #[tauri::command]
+    pub async fn create_tierlist_in_db(
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        title: String,
+    ) -> Result<ListId, String> {
+        let pool = pool.lock().await;
+        create_tierlist(opened(&pool)?, &title)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    #[tauri::command]
+    pub async fn rename_tierlist_in_db(
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        list_id: ListId,
+        title: String,
+    ) -> Result<(), String> {
+        let pool = pool.lock().await;
+        rename_tierlist(opened(&pool)?, list_id, &title)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    #[tauri::command]
+    pub async fn duplicate_tierlist_in_db(
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        list_id: ListId,
+        title: String,
+    ) -> Result<ListId, String> {
+        let pool = pool.lock().await;
+        duplicate_tierlist(opened(&pool)?, list_id, &title)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    #[tauri::command]
+    pub async fn delete_tierlist_in_db(
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        list_id: ListId,
+    ) -> Result<(), String> {
+        let pool = pool.lock().await;
+        delete_tierlist(opened(&pool)?, list_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        // 消した tierlist に自動保存しないようにする
+        let mut cur_list = cur_list.lock().await;
+        if *cur_list == Some(list_id) {
+            *cur_list = None;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::{
+        history::{History, Op},
+        tierlist::ItemData,
+    };
+
+    fn item(id: ItemId, name: &str) -> Item {
+        Item {
+            id,
+            name: name.to_owned(),
+            url: "".to_owned(),
+            thumb: None,
+            memo: "".to_owned(),
+            tags: vec![],
+            fields: Default::default(),
+            times: Default::default(),
+        }
+    }
+
+    fn tier(id: TierId, title: &str, items: Vec<ItemId>) -> Tier {
+        Tier {
+            id,
+            title: title.to_owned(),
+            items,
+            color: "#ff7f7f".to_owned(),
+            text_color: None,
+            icon: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn read_tierlist_test() {
+        let dir = TempDir::new("db_test").unwrap();
+        let db_url = dir.path().join("test.db3").to_string_lossy().to_string();
+        let pool = connect(&db_url).await.unwrap();
+        MIGRATOR.run(&pool).await.unwrap();
+
+        let tierlist_title = "list";
+        const SQL_TIERLIST: &str = "INSERT INTO tierlist(id, title) VALUES (?, ?)";
+        sqlx::query(SQL_TIERLIST)
+            .bind(2)
+            .bind(tierlist_title)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let tiers = [(1, 0, "tier1"), (2, 1, "tier2"), (5, 2, "tier3")];
+        const SQL_TIERS: &str = "INSERT INTO tiers(list_id, id, pos, title) ";
+        let mut qbuilder: QueryBuilder<Sqlite> = QueryBuilder::new(SQL_TIERS);
+        qbuilder.push_values(tiers.iter(), |mut b, (id, pos, title)| {
+            b.push_bind(2)
+                .push_bind(id)
+                .push_bind(pos)
+                .push_bind(*title);
+        });
+        qbuilder.build().execute(&pool).await.unwrap();
+
+        let items = [
+            (1, "item1", "url1", Some(vec![0u8, 1, 2]), "memo1"),
+            (2, "item2", "url2", None, "memo2"),
+            (3, "item3", "url3", Some(vec![3u8, 4, 5]), "memo3"),
+        ];
+        const SQL_ITEMS: &str = "INSERT INTO items(list_id, id, name, url, thumb, memo) ";
+        let mut qbuilder: QueryBuilder<Sqlite> = QueryBuilder::new(SQL_ITEMS);
+        qbuilder.push_values(items.iter(), |mut b, (id, name, url, thumb, memo)| {
+            b.push_bind(2)
+                .push_bind(id)
+                .push_bind(*name)
+                .push_bind(*url)
+                .push_bind(thumb)
+                .push_bind(*memo);
+        });
+        qbuilder.build().execute(&pool).await.unwrap();
+
+        let items_pos = [(1, 2, 1), (2, 2, 0)];
+        const SQL_POS: &str = "INSERT INTO items_pos(list_id, item_id, tier_id, pos) ";
+        let mut qbuilder: QueryBuilder<Sqlite> = QueryBuilder::new(SQL_POS);
+        qbuilder.push_values(items_pos.iter(), |mut b, (item_id, tier_id, pos)| {
+            b.push_bind(2)
+                .push_bind(item_id)
+                .push_bind(tier_id)
+                .push_bind(pos);
+        });
+        qbuilder.build().execute(&pool).await.unwrap();
+
+        let thumb_dir = TempDir::new("test_thumb").unwrap();
+        let tierlist = read_tierlist(&pool, 2, thumb_dir.path()).await.unwrap();
+        assert!(read_tierlist(&pool, 1, thumb_dir.path()).await.is_err());
+
+        pool.close().await;
+
+        assert_eq!(tierlist.title, tierlist_title);
+        assert_eq!(tierlist.tiers.len(), 3);
+        assert!(tierlist.items.iter().any(|it| it.id == 1));
+        assert!(tierlist.items.iter().any(|it| it.id == 2));
+        assert!(tierlist.items.iter().any(|it| it.id == 3));
+        assert_eq!(tierlist.items_pool, vec![3]);
+
+        let tier2 = &tierlist.tiers[1];
+        assert_eq!(tier2.id, 2);
+        assert_eq!(tier2.title, tiers[1].2);
+        assert_eq!(tier2.items, vec![2, 1]);
+
+        let item1 = tierlist.items.iter().find(|it| it.id == 1).unwrap();
+        assert_eq!(item1.id, 1);
+        assert_eq!(item1.name, "item1");
+        assert_eq!(item1.url, "url1");
+        assert_eq!(item1.memo, "memo1");
+
+        let mut thumb1_file = File::open(item1.thumb.as_ref().unwrap()).await.unwrap();
+        let mut thumb1 = vec![];
+        thumb1_file.read_to_end(&mut thumb1).await.unwrap();
+        assert_eq!(thumb1, vec![0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn write_tierlist_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let thumb1_path = img_dir.path().join("thumb1");
+        {
+            let mut thumb1_file = File::create(&thumb1_path).await.unwrap();
+            thumb1_file.write_all(&[0, 1, 2]).await.unwrap();
+        }
+
+        let tierlist = TierList {
+            title: "list".to_owned(),
+            tiers: vec![tier(1, "tier1", vec![]), tier(2, "tier2", vec![2, 1])],
+            tier_max_id: 2,
+            items: vec![
+                Item {
+                    url: "url1".to_owned(),
+                    thumb: Some(thumb1_path.to_string_lossy().to_string()),
+                    memo: "memo1".to_owned(),
+                    ..item(1, "item1")
                 },
-                Tier {
-                    id: 2,
-                    title: "tier2".to_owned(),
-                    items: vec![2, 1],
+                Item {
+                    url: "url2".to_owned(),
+                    memo: "memo2".to_owned(),
+                    ..item(2, "item2")
+                },
+                Item {
+                    url: "url3".to_owned(),
+                    memo: "memo3".to_owned(),
+                    ..item(3, "item3")
                 },
             ],
+            items_pool: vec![3],
+            item_max_id: 3,
+            fields: vec![],
+            field_max_id: 0,
+        };
+
+        let dir = TempDir::new("db_test").unwrap();
+        let pool = open_db(&dir.path().join("test.db3")).await.unwrap();
+        write_tierlist(&pool, 1, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            sqlx::query("SELECT * FROM tierlist")
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+                .get::<String, &str>("title"),
+            tierlist.title
+        );
+
+        let rows = sqlx::query("SELECT * FROM tiers ORDER BY id ASC")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].get::<String, &str>("title"), "tier2");
+
+        let rows = sqlx::query("SELECT * FROM items ORDER BY id ASC")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].get::<Vec<u8>, &str>("thumb"), vec![0, 1, 2]);
+        assert_eq!(rows[0].get::<String, &str>("memo"), "memo1");
+
+        let rows = sqlx::query("SELECT * FROM items_pos ORDER BY item_id ASC")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].get::<i64, &str>("tier_id"), 2);
+        assert_eq!(rows[1].get::<i64, &str>("pos"), 0);
+    }
+
+    #[tokio::test]
+    async fn write_tierlist_incremental_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let thumb1_path = img_dir.path().join("thumb1");
+        let thumb2_path = img_dir.path().join("thumb2");
+        {
+            let mut thumb1_file = File::create(&thumb1_path).await.unwrap();
+            thumb1_file.write_all(&[0, 1, 2]).await.unwrap();
+            let mut thumb2_file = File::create(&thumb2_path).await.unwrap();
+            thumb2_file.write_all(&[3, 4, 5]).await.unwrap();
+        }
+
+        let mut tierlist = TierList {
+            title: "list".to_owned(),
+            tiers: vec![tier(1, "tier1", vec![1]), tier(2, "tier2", vec![2])],
             tier_max_id: 2,
             items: vec![
                 Item {
-                    id: 1,
-                    name: "item1".to_owned(),
                     url: "url1".to_owned(),
                     thumb: Some(thumb1_path.to_string_lossy().to_string()),
                     memo: "memo1".to_owned(),
+                    ..item(1, "item1")
                 },
                 Item {
-                    id: 2,
-                    name: "item2".to_owned(),
                     url: "url2".to_owned(),
-                    thumb: None,
+                    thumb: Some(thumb2_path.to_string_lossy().to_string()),
                     memo: "memo2".to_owned(),
+                    ..item(2, "item2")
                 },
                 Item {
-                    id: 3,
-                    name: "item3".to_owned(),
                     url: "url3".to_owned(),
-                    thumb: None,
                     memo: "memo3".to_owned(),
+                    ..item(3, "item3")
                 },
             ],
             items_pool: vec![3],
             item_max_id: 3,
+            fields: vec![],
+            field_max_id: 0,
+        };
+
+        let dir = TempDir::new("db_test").unwrap();
+        let pool = open_db(&dir.path().join("test.db3")).await.unwrap();
+        write_tierlist(&pool, 1, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+
+        // 展開済みのサムネイルは読み直さない (ファイルが無くても失敗しない)
+        let thumb_dir = TempDir::new("test_cache").unwrap();
+        let cache_dir = thumb_cache_dir(&pool, thumb_dir.path()).await.unwrap();
+        tierlist.items[0].thumb = Some(
+            thumb_cache_path(&cache_dir, 1, 1)
+                .to_string_lossy()
+                .to_string(),
+        );
+        tierlist.items[1].thumb = None;
+        tierlist.items[2].thumb = Some(thumb1_path.to_string_lossy().to_string());
+        tierlist.items[2].memo = "memo3 updated".to_owned();
+        tierlist.items[2].times = ItemTimes {
+            created_at: 100,
+            updated_at: 200,
+            moved_at: 300,
+        };
+        tierlist.items[1].times.moved_at = 400;
+        tierlist.title = "list updated".to_owned();
+        tierlist.tiers.remove(1);
+        tierlist.tiers.push(tier(3, "tier3", vec![3]));
+        tierlist.tiers[0].items = vec![2, 1];
+        tierlist.tiers[0].color = "#123456".to_owned();
+        tierlist.tiers[0].text_color = Some("#ffffff".to_owned());
+        tierlist.tiers[0].icon = Some("⭐".to_owned());
+        tierlist.tier_max_id = 3;
+        tierlist.items_pool = vec![];
+        write_tierlist(&pool, 1, &tierlist, thumb_dir.path())
+            .await
+            .unwrap();
+
+        let rows = sqlx::query("SELECT * FROM tierlist")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get::<String, &str>("title"), "list updated");
+
+        let rows = sqlx::query("SELECT * FROM tiers ORDER BY pos ASC")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get::<i64, &str>("id"), 1);
+        assert_eq!(rows[0].get::<String, &str>("color"), "#123456");
+        assert_eq!(
+            rows[0].get::<Option<String>, &str>("text_color"),
+            Some("#ffffff".to_owned())
+        );
+        assert_eq!(
+            rows[0].get::<Option<String>, &str>("icon"),
+            Some("⭐".to_owned())
+        );
+        assert_eq!(rows[1].get::<i64, &str>("id"), 3);
+
+        let rows = sqlx::query("SELECT * FROM items ORDER BY id ASC")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].get::<Vec<u8>, &str>("thumb"), vec![0, 1, 2]);
+        assert_eq!(rows[1].get::<Option<Vec<u8>>, &str>("thumb"), None);
+        assert_eq!(rows[2].get::<Vec<u8>, &str>("thumb"), vec![0, 1, 2]);
+        assert_eq!(rows[2].get::<String, &str>("memo"), "memo3 updated");
+        let times: Vec<(i64, i64, i64)> = rows
+            .iter()
+            .map(|r| (r.get("created_at"), r.get("updated_at"), r.get("moved_at")))
+            .collect();
+        assert_eq!(times, vec![(0, 0, 0), (0, 0, 400), (100, 200, 300)]);
+        let read = read_tierlist(&pool, 1, thumb_dir.path()).await.unwrap();
+        assert_eq!(read.items[2].times, tierlist.items[2].times);
+
+        let rows = sqlx::query("SELECT * FROM items_pos ORDER BY tier_id ASC, pos ASC")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        let pos: Vec<(i64, i64, i64)> = rows
+            .iter()
+            .map(|r| (r.get("item_id"), r.get("tier_id"), r.get("pos")))
+            .collect();
+        assert_eq!(pos, vec![(2, 1, 0), (1, 1, 1), (3, 3, 0)]);
+    }
+
+    #[tokio::test]
+    async fn saved_thumbs_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let thumb1_path = img_dir.path().join("thumb1");
+        tokio::fs::write(&thumb1_path, [0, 1, 2]).await.unwrap();
+        let dir = TempDir::new("db_test").unwrap();
+        let pool = open_db(&dir.path().join("test.db3")).await.unwrap();
+        let list_id = create_tierlist(&pool, "list").await.unwrap();
+
+        let mut tierlist = TierList::empty();
+        tierlist.items.push(Item {
+            thumb: Some(thumb1_path.to_string_lossy().to_string()),
+            ..item(1, "item1")
+        });
+        tierlist.items.push(item(2, "item2"));
+        tierlist.items_pool = vec![1, 2];
+        tierlist.item_max_id = 2;
+        let saved = write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(saved.len(), 1);
+        use_saved_thumbs(&mut tierlist, &saved);
+        let thumb1 = tierlist.items[0].thumb.clone().unwrap();
+        assert_eq!(thumb1, saved[0].to);
+        assert_eq!(tokio::fs::read(&thumb1).await.unwrap(), vec![0, 1, 2]);
+
+        // 2 回目は元のファイルを読まず、DB の画像も書き換えない
+        tokio::fs::remove_file(&thumb1_path).await.unwrap();
+        sqlx::query("UPDATE items SET thumb = x'09' WHERE id = 1")
+            .execute(&pool)
+            .await
+            .unwrap();
+        tierlist.items[1].memo = "memo2".to_owned();
+        let saved = write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        assert!(saved.is_empty());
+        let thumb: Vec<u8> = sqlx::query_scalar("SELECT thumb FROM items WHERE id = 1")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(thumb, vec![9]);
+
+        // 保存している間に画像を変えたアイテムはそのまま
+        let mut changed = tierlist.clone();
+        changed.items[0].thumb = Some("other".to_owned());
+        use_saved_thumbs(
+            &mut changed,
+            &[SavedThumb {
+                item: 1,
+                from: "before".to_owned(),
+                to: thumb1,
+            }],
+        );
+        assert_eq!(changed.items[0].thumb.as_deref(), Some("other"));
+    }
+
+    #[tokio::test]
+    async fn multiple_tierlists_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let dir = TempDir::new("db_test").unwrap();
+        let db_url = dir.path().join("test.db3").to_string_lossy().to_string();
+        let pool = connect(&db_url).await.unwrap();
+        MIGRATOR.run(&pool).await.unwrap();
+
+        let first = create_tierlist(&pool, "first").await.unwrap();
+        let mut tierlist = TierList::empty();
+        tierlist.title = "first".to_owned();
+        tierlist.tiers.push(tier(1, "tier1", vec![1]));
+        tierlist.items.push(Item {
+            url: "url1".to_owned(),
+            memo: "memo1".to_owned(),
+            ..item(1, "item1")
+        });
+        write_tierlist(&pool, first, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+
+        let second = duplicate_tierlist(&pool, first, "second").await.unwrap();
+        assert_ne!(first, second);
+        rename_tierlist(&pool, first, "first renamed")
+            .await
+            .unwrap();
+        assert!(rename_tierlist(&pool, 100, "none").await.is_err());
+
+        let lists = list_tierlists(&pool).await.unwrap();
+        let titles: Vec<&str> = lists.iter().map(|l| l.title.as_str()).collect();
+        assert_eq!(titles, vec!["first renamed", "second"]);
+
+        let copied = read_tierlist(&pool, second, img_dir.path()).await.unwrap();
+        assert_eq!(copied.title, "second");
+        assert_eq!(copied.tiers[0].items, vec![1]);
+        assert_eq!(copied.items[0].name, "item1");
+
+        delete_tierlist(&pool, first).await.unwrap();
+        let lists = list_tierlists(&pool).await.unwrap();
+        assert_eq!(lists.len(), 1);
+        let rows = sqlx::query("SELECT * FROM items WHERE list_id = ?")
+            .bind(first)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert!(rows.is_empty());
+        let copied = read_tierlist(&pool, second, img_dir.path()).await.unwrap();
+        assert_eq!(copied.tiers[0].items, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn tags_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let dir = TempDir::new("db_test").unwrap();
+        let pool = open_db(&dir.path().join("test.db3")).await.unwrap();
+
+        let list_id = create_tierlist(&pool, "list").await.unwrap();
+        let mut tierlist = TierList::empty();
+        for (id, tags) in [(1, vec!["anime", "2022"]), (2, vec!["anime"]), (3, vec![])] {
+            tierlist.items.push(item(id, &format!("item{}", id)));
+            tierlist.items_pool.push(id);
+            let tags = tags.into_iter().map(|t| t.to_owned()).collect();
+            tierlist.set_item_tags(id, tags).unwrap();
+        }
+        tierlist.item_max_id = 3;
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        let read = read_tierlist(&pool, list_id, img_dir.path()).await.unwrap();
+        assert_eq!(read.items[0].tags, vec!["2022", "anime"]);
+        assert_eq!(read.items[1].tags, vec!["anime"]);
+        assert!(read.items[2].tags.is_empty());
+
+        let copy_id = duplicate_tierlist(&pool, list_id, "copy").await.unwrap();
+
+        // 消したアイテムのタグと、使われなくなったタグは残らない
+        tierlist.delete_item(1).unwrap();
+        tierlist.set_item_tags(3, vec!["manga".to_owned()]).unwrap();
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        let read = read_tierlist(&pool, list_id, img_dir.path()).await.unwrap();
+        assert_eq!(read.items[0].tags, vec!["anime"]);
+        assert_eq!(read.items[1].tags, vec!["manga"]);
+        let names: Vec<String> =
+            sqlx::query_scalar("SELECT name FROM tags WHERE list_id = ? ORDER BY name ASC")
+                .bind(list_id)
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(names, vec!["anime", "manga"]);
+
+        let copy = read_tierlist(&pool, copy_id, img_dir.path()).await.unwrap();
+        assert_eq!(copy.items[0].tags, vec!["2022", "anime"]);
+    }
+
+    #[tokio::test]
+    async fn fields_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let dir = TempDir::new("db_test").unwrap();
+        let pool = open_db(&dir.path().join("test.db3")).await.unwrap();
+
+        let list_id = create_tierlist(&pool, "list").await.unwrap();
+        let mut tierlist = TierList::empty();
+        for id in 1..=2 {
+            tierlist.items.push(item(id, &format!("item{}", id)));
+            tierlist.items_pool.push(id);
+        }
+        tierlist.item_max_id = 2;
+        let year = tierlist.add_field("year", FieldType::Number).unwrap();
+        let seen = tierlist.add_field("seen", FieldType::Boolean).unwrap();
+        tierlist
+            .set_item_field(1, year, Some("1998".to_owned()))
+            .unwrap();
+        tierlist
+            .set_item_field(1, seen, Some("yes".to_owned()))
+            .unwrap();
+        tierlist
+            .set_item_field(2, year, Some("2004".to_owned()))
+            .unwrap();
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        let read = read_tierlist(&pool, list_id, img_dir.path()).await.unwrap();
+        assert_eq!(read.fields, tierlist.fields);
+        assert_eq!(read.field_max_id, 2);
+        assert_eq!(read.items[0].fields.get(&seen).unwrap(), "true");
+        assert_eq!(read.items[1].fields.get(&year).unwrap(), "2004");
+
+        let copy_id = duplicate_tierlist(&pool, list_id, "copy").await.unwrap();
+
+        // 消した field の値は残らない
+        tierlist.delete_field(seen).unwrap();
+        tierlist.rename_field(year, "released").unwrap();
+        tierlist.set_item_field(2, year, None).unwrap();
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        let read = read_tierlist(&pool, list_id, img_dir.path()).await.unwrap();
+        assert_eq!(read.fields.len(), 1);
+        assert_eq!(read.fields[0].name, "released");
+        assert_eq!(read.items[0].fields.len(), 1);
+        assert!(read.items[1].fields.is_empty());
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM item_fields WHERE list_id = ?")
+            .bind(list_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        let copy = read_tierlist(&pool, copy_id, img_dir.path()).await.unwrap();
+        assert_eq!(copy.fields.len(), 2);
+        assert_eq!(copy.items[0].fields.get(&seen).unwrap(), "true");
+        assert_eq!(copy.items[1].fields.get(&year).unwrap(), "2004");
+    }
+
+    #[tokio::test]
+    async fn placement_log_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let dir = TempDir::new("db_test").unwrap();
+        let pool = open_db(&dir.path().join("test.db3")).await.unwrap();
+
+        let list_id = create_tierlist(&pool, "list").await.unwrap();
+        let mut tierlist = TierList::empty();
+        let s = tierlist.add_tier("S");
+        let b = tierlist.add_tier("B");
+        for name in ["item1", "item2", "item3"] {
+            tierlist.add_item(ItemData {
+                name: name.to_owned(),
+                url: "".to_owned(),
+                thumb: None,
+                memo: "".to_owned(),
+            });
+        }
+        tierlist.move_item(1, Some(b), 0).unwrap();
+        for item in tierlist.items.iter_mut() {
+            item.times = ItemTimes::new(100);
+        }
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        // 変わっていなければ何も記録しない
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+
+        tierlist.move_item(1, Some(s), 0).unwrap();
+        tierlist.move_item(2, Some(s), 1).unwrap();
+        tierlist.move_item(3, Some(b), 0).unwrap();
+        tierlist.items[0].times.moved_at = 200;
+        tierlist.items[1].times.moved_at = 200;
+        tierlist.items[2].times.moved_at = 300;
+        tierlist.rename_tier(b, "B-").unwrap();
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        tierlist.delete_item(2).unwrap();
+        tierlist.delete_tier(b).unwrap();
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+
+        let history = item_placements(&pool, list_id, 1).await.unwrap();
+        let places: Vec<(&Place, &Place, Option<usize>, Timestamp)> = history
+            .iter()
+            .map(|p| (&p.from, &p.to, p.pos, p.at))
+            .collect();
+        let tier = |id: TierId, title: &str| Place::Tier {
+            id,
+            title: title.to_owned(),
         };
+        assert_eq!(
+            places,
+            vec![
+                (&Place::Absent, &tier(b, "B"), Some(0), 100),
+                (&tier(b, "B"), &tier(s, "S"), Some(0), 200),
+            ]
+        );
+
+        // 消したアイテムと tier の名前も残る
+        let history = item_placements(&pool, list_id, 2).await.unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[2].name, "item2");
+        assert_eq!(history[2].from, tier(s, "S"));
+        assert_eq!(history[2].to, Place::Absent);
+        let history = item_placements(&pool, list_id, 3).await.unwrap();
+        assert_eq!(history[1].to, tier(b, "B-"));
+        assert_eq!(history[2].from, tier(b, "B-"));
+        assert_eq!(history[2].to, Place::Pool);
 
+        let since = placements_since(&pool, list_id, 300).await.unwrap();
+        let items: Vec<ItemId> = since.iter().map(|p| p.item).collect();
+        assert_eq!(items, vec![3, 3, 2]);
+
+        let copy_id = duplicate_tierlist(&pool, list_id, "copy").await.unwrap();
+        assert_eq!(
+            item_placements(&pool, copy_id, 1).await.unwrap(),
+            item_placements(&pool, list_id, 1).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn placement_log_after_undo_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
         let dir = TempDir::new("db_test").unwrap();
-        let db_url = dir.path().join("test.db3").to_string_lossy().to_string();
-        let pool = connect(&db_url).await.unwrap();
-        write_tierlist(&pool, &tierlist).await.unwrap();
+        let pool = open_db(&dir.path().join("test.db3")).await.unwrap();
+
+        let list_id = create_tierlist(&pool, "list").await.unwrap();
+        let mut tierlist = TierList::empty();
+        let s = tierlist.add_tier("S");
+        let b = tierlist.add_tier("B");
+        tierlist.items.push(item(1, "item1"));
+        tierlist.tiers[1].items.push(1);
+        tierlist.item_max_id = 1;
+        tierlist.items[0].times = ItemTimes::new(100);
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+
+        let mut history = History::new(10);
+        let op = Op::MoveItem {
+            id: 1,
+            tier: Some(s),
+            index: 0,
+        };
+        history.apply(&mut tierlist, op).unwrap();
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        // 元に戻すと移した時刻も 100 に戻るが、記録は前の行より古くしない
+        history.undo(&mut tierlist).unwrap();
+        assert_eq!(tierlist.items[0].times.moved_at, 100);
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
 
+        let history = item_placements(&pool, list_id, 1).await.unwrap();
+        let moves: Vec<(Option<TierId>, Option<TierId>)> = history
+            .iter()
+            .map(|p| {
+                let tier_id = |place: &Place| match place {
+                    Place::Tier { id, .. } => Some(*id),
+                    _ => None,
+                };
+                (tier_id(&p.from), tier_id(&p.to))
+            })
+            .collect();
         assert_eq!(
-            sqlx::query("SELECT * FROM tierlist")
-                .fetch_one(&pool)
+            moves,
+            vec![(None, Some(b)), (Some(b), Some(s)), (Some(s), Some(b))]
+        );
+        assert!(history[1].at > 100);
+        assert_eq!(history[2].at, history[1].at);
+
+        let since = placements_since(&pool, list_id, history[1].at)
+            .await
+            .unwrap();
+        assert_eq!(since, history[1..].to_vec());
+    }
+
+    #[tokio::test]
+    async fn snapshots_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let dir = TempDir::new("db_test").unwrap();
+        let pool = open_db(&dir.path().join("test.db3")).await.unwrap();
+
+        let list_id = create_tierlist(&pool, "list").await.unwrap();
+        let mut tierlist = TierList::empty();
+        let s = tierlist.add_tier("S");
+        tierlist.add_item(ItemData {
+            name: "item1".to_owned(),
+            url: "https://example.com/1".to_owned(),
+            thumb: None,
+            memo: "memo".to_owned(),
+        });
+        tierlist.move_item(1, Some(s), 0).unwrap();
+        let first = create_snapshot(&pool, list_id, "first", &tierlist)
+            .await
+            .unwrap();
+        assert!(create_snapshot(&pool, list_id, "first", &tierlist)
+            .await
+            .is_err());
+
+        tierlist.move_item(1, None, 0).unwrap();
+        tierlist.rename_tier(s, "S+").unwrap();
+        let second = create_snapshot(&pool, list_id, "second", &tierlist)
+            .await
+            .unwrap();
+        let names: Vec<String> = list_snapshots(&pool, list_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, vec!["second", "first"]);
+
+        let read = read_snapshot(&pool, list_id, first, Some(img_dir.path()))
+            .await
+            .unwrap();
+        assert_eq!(read.tiers[0].title, "S");
+        assert_eq!(read.tiers[0].items, vec![1]);
+        assert_eq!(read.items[0].memo, "memo");
+        let read = read_snapshot(&pool, list_id, second, Some(img_dir.path()))
+            .await
+            .unwrap();
+        assert_eq!(read, tierlist);
+
+        // 別の tierlist のものは読めない
+        let other = duplicate_tierlist(&pool, list_id, "copy").await.unwrap();
+        assert!(read_snapshot(&pool, other, first, Some(img_dir.path()))
+            .await
+            .is_err());
+        assert_eq!(list_snapshots(&pool, other).await.unwrap().len(), 2);
+
+        delete_snapshot(&pool, list_id, first).await.unwrap();
+        assert!(delete_snapshot(&pool, list_id, first).await.is_err());
+        assert!(read_snapshot(&pool, list_id, first, Some(img_dir.path()))
+            .await
+            .is_err());
+        assert_eq!(list_snapshots(&pool, list_id).await.unwrap().len(), 1);
+
+        delete_tierlist(&pool, list_id).await.unwrap();
+        assert!(list_snapshots(&pool, list_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn snapshot_thumbs_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let thumb1_path = img_dir.path().join("thumb1");
+        {
+            let mut thumb1_file = File::create(&thumb1_path).await.unwrap();
+            thumb1_file.write_all(&[0, 1, 2]).await.unwrap();
+        }
+        let dir = TempDir::new("db_test").unwrap();
+        let pool = open_db(&dir.path().join("test.db3")).await.unwrap();
+
+        let list_id = create_tierlist(&pool, "list").await.unwrap();
+        let mut tierlist = TierList::empty();
+        tierlist.items.push(Item {
+            thumb: Some(thumb1_path.to_string_lossy().to_string()),
+            ..item(1, "item1")
+        });
+        tierlist.items.push(item(2, "item2"));
+        tierlist.items_pool = vec![1, 2];
+        tierlist.item_max_id = 2;
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        let id = create_snapshot(&pool, list_id, "first", &tierlist)
+            .await
+            .unwrap();
+
+        // 残したあとでアイテムを消して、元の画像ファイルも無くなっても戻せる
+        tierlist.delete_item(1).unwrap();
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        tokio::fs::remove_file(&thumb1_path).await.unwrap();
+
+        let thumb_dir = TempDir::new("test_cache").unwrap();
+        let frozen = read_snapshot(&pool, list_id, id, Some(thumb_dir.path()))
+            .await
+            .unwrap();
+        let restored = snapshot::thaw(frozen, &tierlist);
+        assert_eq!(restored.item(2).unwrap().thumb, None);
+        let mut thumb1_file = File::open(restored.item(1).unwrap().thumb.as_ref().unwrap())
+            .await
+            .unwrap();
+        let mut thumb1 = vec![];
+        thumb1_file.read_to_end(&mut thumb1).await.unwrap();
+        assert_eq!(thumb1, vec![0, 1, 2]);
+
+        // 展開しないで読むこともできる
+        let frozen = read_snapshot(&pool, list_id, id, None).await.unwrap();
+        assert_eq!(frozen.item(1).unwrap().thumb, None);
+
+        // 複製した tierlist のスナップショットにも付いていく
+        let copy_id = duplicate_tierlist(&pool, list_id, "copy").await.unwrap();
+        let copy_snapshot = list_snapshots(&pool, copy_id).await.unwrap()[0].id;
+        let read = read_snapshot(&pool, copy_id, copy_snapshot, Some(thumb_dir.path()))
+            .await
+            .unwrap();
+        assert!(read.item(1).unwrap().thumb.is_some());
+
+        delete_snapshot(&pool, list_id, id).await.unwrap();
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM snapshot_thumbs")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn read_tierlist_file_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let dir = TempDir::new("db_test").unwrap();
+        let path = dir.path().join("other.db3");
+        let pool = open_db(&path).await.unwrap();
+        let first = create_tierlist(&pool, "first").await.unwrap();
+        let mut tierlist = TierList::empty();
+        tierlist.title = "first".to_owned();
+        tierlist.add_tier("S");
+        write_tierlist(&pool, first, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let read = read_tierlist_file(&path, None, img_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(read.tiers[0].title, "S");
+
+        // 複数あればどれを読むか選ばなければならない
+        let pool = open_db(&path).await.unwrap();
+        let second = create_tierlist(&pool, "second").await.unwrap();
+        pool.close().await;
+        assert!(read_tierlist_file(&path, None, img_dir.path())
+            .await
+            .is_err());
+        let read = read_tierlist_file(&path, Some(second), img_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(read.title, "second");
+        assert_eq!(list_tierlists_file(&path).await.unwrap().len(), 2);
+
+        // 無いファイルを作ったり、古いファイルをマイグレーションしたりしない
+        let missing = dir.path().join("missing.db3");
+        assert!(list_tierlists_file(&missing).await.is_err());
+        assert!(!missing.exists());
+        for (name, msg) in [
+            ("schema3_multi_list.db3", "older version"),
+            ("future_schema.db3", "newer version"),
+            ("not_tierlist.db3", "not a Tierlist Maker file"),
+        ] {
+            let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("fixtures/db")
+                .join(name);
+            let path = dir.path().join(name);
+            tokio::fs::copy(&fixture, &path).await.unwrap();
+            let err = list_tierlists_file(&path).await.unwrap_err();
+            assert!(err.to_string().contains(msg), "{}: {}", name, err);
+            let err = read_tierlist_file(&path, None, img_dir.path())
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains(msg), "{}: {}", name, err);
+            let copied = tokio::fs::read(&path).await.unwrap();
+            assert_eq!(copied, tokio::fs::read(&fixture).await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn thumb_cache_per_file_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let dir = TempDir::new("db_test").unwrap();
+        let mut reads = vec![];
+        for (file, bytes) in [("a.db3", [1, 1, 1]), ("b.db3", [2, 2, 2])] {
+            let thumb_path = img_dir.path().join(file);
+            File::create(&thumb_path)
                 .await
                 .unwrap()
-                .get::<String, &str>("title"),
-            tierlist.title
+                .write_all(&bytes)
+                .await
+                .unwrap();
+            let pool = open_db(&dir.path().join(file)).await.unwrap();
+            let list_id = create_tierlist(&pool, "list").await.unwrap();
+            let mut tierlist = TierList::empty();
+            tierlist.add_item(ItemData {
+                name: "item1".to_owned(),
+                url: "".to_owned(),
+                thumb: Some(thumb_path.to_string_lossy().to_string()),
+                memo: "".to_owned(),
+            });
+            write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+                .await
+                .unwrap();
+            reads.push(read_tierlist(&pool, list_id, img_dir.path()).await.unwrap());
+        }
+
+        // 同じ id のアイテムでも、先に開いたファイルのサムネイルは上書きされない
+        let thumbs: Vec<&str> = reads
+            .iter()
+            .map(|t| t.items[0].thumb.as_deref().unwrap())
+            .collect();
+        assert_ne!(thumbs[0], thumbs[1]);
+        assert_eq!(tokio::fs::read(thumbs[0]).await.unwrap(), vec![1, 1, 1]);
+        assert_eq!(tokio::fs::read(thumbs[1]).await.unwrap(), vec![2, 2, 2]);
+    }
+
+    #[tokio::test]
+    async fn search_items_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let dir = TempDir::new("db_test").unwrap();
+        let pool = open_db(&dir.path().join("test.db3")).await.unwrap();
+
+        let list_id = create_tierlist(&pool, "list").await.unwrap();
+        let other_id = create_tierlist(&pool, "other").await.unwrap();
+        let mut tierlist = TierList::empty();
+        for (id, name, memo) in [
+            (1, "Cowboy Bebop", "宇宙を舞台にした賞金稼ぎの話"),
+            (2, "Space Dandy", "宇宙人を探すコメディ。Bebop と同じ監督"),
+            (3, "Mushishi", "静かな話"),
+        ] {
+            tierlist.items.push(Item {
+                memo: memo.to_owned(),
+                ..item(id, name)
+            });
+            tierlist.items_pool.push(id);
+        }
+        tierlist.item_max_id = 3;
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        write_tierlist(&pool, other_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+
+        let search = |query: &'static str| {
+            let pool = pool.clone();
+            async move { search_items(&pool, list_id, query).await.unwrap() }
+        };
+        // 名前に合うものが先
+        assert_eq!(search("bebop").await, vec![1, 2]);
+        assert_eq!(search("賞金稼ぎ").await, vec![1]);
+        // 3 文字より短い語も探せる
+        assert_eq!(search("宇宙").await, vec![1, 2]);
+        assert_eq!(search("宇宙 監督").await, vec![2]);
+        assert_eq!(search("話").await, vec![1, 3]);
+        assert_eq!(search("\"quoted OR").await, Vec::<ItemId>::new());
+        assert_eq!(search("%").await, Vec::<ItemId>::new());
+        assert_eq!(search("  ").await, Vec::<ItemId>::new());
+
+        // 書き込んだ変更が索引に反映される
+        tierlist.delete_item(1).unwrap();
+        tierlist.items[1].memo = "賞金稼ぎは出てこない".to_owned();
+        write_tierlist(&pool, list_id, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(search("賞金稼ぎ").await, vec![3]);
+        assert_eq!(search("bebop").await, vec![2]);
+        assert_eq!(
+            search_items(&pool, other_id, "賞金稼ぎ").await.unwrap(),
+            vec![1]
         );
+    }
 
-        let rows = sqlx::query("SELECT * FROM tiers ORDER BY id ASC")
+    #[tokio::test]
+    async fn repair_on_read_and_write_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let dir = TempDir::new("db_test").unwrap();
+        let db_url = dir.path().join("test.db3").to_string_lossy().to_string();
+        let pool = connect(&db_url).await.unwrap();
+        MIGRATOR.run(&pool).await.unwrap();
+
+        // 同じアイテムが 2 つの tier に置かれている
+        let list_id = create_tierlist(&pool, "list").await.unwrap();
+        for sql in [
+            "INSERT INTO tiers(list_id, id, pos, title) VALUES (?, 1, 0, 'tier1')",
+            "INSERT INTO tiers(list_id, id, pos, title) VALUES (?, 2, 1, 'tier2')",
+            "INSERT INTO items(list_id, id, name, url, memo) VALUES (?, 1, 'item1', '', '')",
+            "INSERT INTO items_pos(list_id, item_id, tier_id, pos) VALUES (?, 1, 1, 0)",
+            "INSERT INTO items_pos(list_id, item_id, tier_id, pos) VALUES (?, 1, 2, 0)",
+        ] {
+            sqlx::query(sql).bind(list_id).execute(&pool).await.unwrap();
+        }
+        let tierlist = read_tierlist(&pool, list_id, img_dir.path()).await.unwrap();
+        assert_eq!(tierlist.tiers[0].items, vec![1]);
+        assert!(tierlist.tiers[1].items.is_empty());
+        assert!(tierlist.validate().is_empty());
+
+        // 書き込む前にも直す
+        let mut broken = tierlist.clone();
+        broken.items_pool.push(1);
+        broken.tiers[1].items.push(5);
+        write_tierlist(&pool, list_id, &broken, img_dir.path())
+            .await
+            .unwrap();
+        let rows = sqlx::query("SELECT * FROM items_pos WHERE list_id = ?")
+            .bind(list_id)
             .fetch_all(&pool)
             .await
             .unwrap();
-        assert_eq!(rows.len(), 2);
-        assert_eq!(rows[1].get::<String, &str>("title"), "tier2");
+        assert_eq!(rows.len(), 1);
+    }
 
-        let rows = sqlx::query("SELECT * FROM items ORDER BY id ASC")
-            .fetch_all(&pool)
+    #[tokio::test]
+    async fn migrate_single_list_db_test() {
+        let dir = TempDir::new("db_test").unwrap();
+        let db_url = dir.path().join("test.db3").to_string_lossy().to_string();
+        let pool = connect(&db_url).await.unwrap();
+
+        // list_id が導入される前のスキーマ
+        let mut migrator = sqlx::migrate!("./sql");
+        migrator.migrations = migrator.migrations[..2].to_vec().into();
+        migrator.run(&pool).await.unwrap();
+        for sql in [
+            "INSERT INTO tierlist(title) VALUES ('old list')",
+            "INSERT INTO tiers(id, pos, title) VALUES (1, 0, 'tier1')",
+            "INSERT INTO items(id, name, url, thumb, memo) VALUES (1, 'item1', 'url1', NULL, '')",
+            "INSERT INTO items(id, name, url, thumb, memo) VALUES (2, 'item2', 'url2', NULL, '')",
+            "INSERT INTO items_pos(item_id, tier_id, pos) VALUES (2, 1, 0)",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+
+        MIGRATOR.run(&pool).await.unwrap();
+        let thumb_dir = TempDir::new("test_thumb").unwrap();
+        let tierlist = read_tierlist(&pool, 1, thumb_dir.path()).await.unwrap();
+        assert_eq!(tierlist.title, "old list");
+        assert_eq!(tierlist.tiers[0].items, vec![2]);
+        assert_eq!(tierlist.items_pool, vec![1]);
+    }
+
+    // fixtures/db 以下のファイルを書き換えないよう、コピーを開く
+    async fn open_fixture(dir: &TempDir, name: &str) -> DbResult<SqlitePool> {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/db")
+            .join(name);
+        let path = dir.path().join(name);
+        tokio::fs::copy(&fixture, &path).await.unwrap();
+        open_db(&path).await
+    }
+
+    async fn user_version(pool: &SqlitePool) -> i64 {
+        sqlx::query_scalar("PRAGMA user_version")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn upgrade_old_files_test() {
+        let dir = TempDir::new("db_test").unwrap();
+        let thumb_dir = TempDir::new("test_thumb").unwrap();
+
+        // 1.0.0 の形式 (tierlist が 1 つだけ)
+        let pool = open_fixture(&dir, "schema2_single_list.db3").await.unwrap();
+        assert_eq!(user_version(&pool).await, schema_version());
+        let lists = list_tierlists(&pool).await.unwrap();
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].title, "Anime 2022");
+        let tierlist = read_tierlist(&pool, lists[0].id, thumb_dir.path())
             .await
             .unwrap();
-        assert_eq!(rows.len(), 3);
-        assert_eq!(rows[0].get::<Vec<u8>, &str>("thumb"), vec![0, 1, 2]);
-        assert_eq!(rows[0].get::<String, &str>("memo"), "memo1");
+        let titles: Vec<&str> = tierlist.tiers.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["S", "A", "B"]);
+        // 古いファイルの tier には上から順に既定の色が付く
+        let colors: Vec<&str> = tierlist.tiers.iter().map(|t| t.color.as_str()).collect();
+        assert_eq!(colors, vec!["#ff7f7f", "#ffbf7f", "#ffdf7f"]);
+        assert_eq!(tierlist.tiers[0].items, vec![1, 3]);
+        assert_eq!(tierlist.tiers[2].items, vec![2]);
+        assert_eq!(tierlist.items_pool, vec![4]);
+        assert_eq!(tierlist.items[0].memo, "must watch");
+        // 索引は移行時に作られる
+        let found = search_items(&pool, lists[0].id, "must").await.unwrap();
+        assert_eq!(found, vec![1]);
+        let thumb = tokio::fs::read(tierlist.items[0].thumb.as_ref().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(thumb, vec![0x89, 0x50, 0x4e, 0x47]);
 
-        let rows = sqlx::query("SELECT * FROM items_pos ORDER BY item_id ASC")
-            .fetch_all(&pool)
+        // 開き直してもマイグレーションは流れ直さない
+        pool.close().await;
+        let pool = open_db(&dir.path().join("schema2_single_list.db3"))
             .await
             .unwrap();
-        assert_eq!(rows.len(), 2);
-        assert_eq!(rows[1].get::<i64, &str>("tier_id"), 2);
-        assert_eq!(rows[1].get::<i64, &str>("pos"), 0);
+        let reread = read_tierlist(&pool, lists[0].id, thumb_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(reread, tierlist);
+
+        let pool = open_fixture(&dir, "schema3_multi_list.db3").await.unwrap();
+        assert_eq!(user_version(&pool).await, schema_version());
+        let lists = list_tierlists(&pool).await.unwrap();
+        let titles: Vec<&str> = lists.iter().map(|l| l.title.as_str()).collect();
+        assert_eq!(titles, vec!["Manga", "Games"]);
+        let manga = read_tierlist(&pool, 1, thumb_dir.path()).await.unwrap();
+        assert_eq!(manga.tiers[0].items, vec![2, 1]);
+        assert!(manga.items[0].thumb.is_some());
+        let games = read_tierlist(&pool, 2, thumb_dir.path()).await.unwrap();
+        assert_eq!(games.tiers[0].items, vec![2]);
+        assert_eq!(games.tiers[0].color, "#ff7f7f");
+        assert_eq!(games.items_pool, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn refuse_unknown_files_test() {
+        let dir = TempDir::new("db_test").unwrap();
+
+        let err = open_fixture(&dir, "future_schema.db3").await.unwrap_err();
+        assert!(err.to_string().contains("newer version"));
+
+        // 他のアプリの DB はテーブルを消さずに断る
+        let err = open_fixture(&dir, "not_tierlist.db3").await.unwrap_err();
+        assert!(err.to_string().contains("not a Tierlist Maker file"));
+        let db_url = dir.path().join("not_tierlist.db3");
+        let pool = connect(&db_url.to_string_lossy()).await.unwrap();
+        let notes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM notes")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(notes, 1);
+    }
+
+    #[tokio::test]
+    async fn save_tierlist_as_test() {
+        let img_dir = TempDir::new("test_thumb").unwrap();
+        let thumb1_path = img_dir.path().join("thumb1");
+        {
+            let mut thumb1_file = File::create(&thumb1_path).await.unwrap();
+            thumb1_file.write_all(&[0, 1, 2]).await.unwrap();
+        }
+
+        let dir = TempDir::new("db_test").unwrap();
+        let old_path = dir.path().join("old.db3");
+        let new_path = dir.path().join("new.db3");
+        let mut cur_pool = Some(open_db(&old_path).await.unwrap());
+
+        let mut tierlist = TierList::empty();
+        tierlist.title = "list".to_owned();
+        tierlist.tiers.push(tier(1, "tier1", vec![1]));
+        tierlist.items.push(Item {
+            url: "url1".to_owned(),
+            thumb: Some(thumb1_path.to_string_lossy().to_string()),
+            memo: "memo1".to_owned(),
+            ..item(1, "item1")
+        });
+        let (list_id, saved) =
+            save_tierlist_as(&mut cur_pool, &new_path, &tierlist, img_dir.path())
+                .await
+                .unwrap();
+        assert_eq!(saved.len(), 1);
+
+        let pool = cur_pool.unwrap();
+        let lists = list_tierlists(&pool).await.unwrap();
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].id, list_id);
+
+        let thumb_dir = TempDir::new("test_cache").unwrap();
+        let saved = read_tierlist(&pool, list_id, thumb_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(saved.title, "list");
+        assert_eq!(saved.tiers[0].items, vec![1]);
+
+        let mut thumb1_file = File::open(saved.items[0].thumb.as_ref().unwrap())
+            .await
+            .unwrap();
+        let mut thumb1 = vec![];
+        thumb1_file.read_to_end(&mut thumb1).await.unwrap();
+        assert_eq!(thumb1, vec![0, 1, 2]);
+
+        // 既にあるファイルに保存すると、前の中身は残らない
+        let mut cur_pool = Some(pool);
+        let other = open_db(&old_path).await.unwrap();
+        create_tierlist(&other, "old1").await.unwrap();
+        create_tierlist(&other, "old2").await.unwrap();
+        other.close().await;
+        let (list_id, _) = save_tierlist_as(&mut cur_pool, &old_path, &tierlist, img_dir.path())
+            .await
+            .unwrap();
+        let lists = list_tierlists(cur_pool.as_ref().unwrap()).await.unwrap();
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].id, list_id);
+        assert_eq!(lists[0].title, "list");
     }
 }
@@ -0,0 +1,300 @@
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::{
+    fields::{FieldDef, FieldId},
+    tierlist::{palette_color, Item, ItemId, ItemTimes, ListId, Tier, TierList},
+};
+
+// 書き出す形式を変えたら上げる
+// 2: tier に color, textColor, icon を追加
+// 3: item に tags を追加
+// 4: fields と item の fields を追加
+// 5: item に createdAt, updatedAt, movedAt を追加
+pub const FORMAT_VERSION: u32 = 5;
+
+// 書き出したファイルの形式。サムネイルは base64 で埋め込む
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedTierList {
+    pub format_version: u32,
+    pub title: String,
+    pub tiers: Vec<Tier>,
+    pub items: Vec<ExportedItem>,
+    pub items_pool: Vec<ItemId>,
+    #[serde(default)]
+    pub fields: Vec<FieldDef>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedItem {
+    pub id: ItemId,
+    pub name: String,
+    pub url: String,
+    pub thumb: Option<String>,
+    pub memo: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub fields: BTreeMap<FieldId, String>,
+    #[serde(flatten)]
+    pub times: ItemTimes,
+}
+
+pub async fn export_tierlist(tierlist: &TierList) -> Result<ExportedTierList, String> {
+    let mut items = vec![];
+    for item in tierlist.items.iter() {
+        let thumb = match &item.thumb {
+            Some(path) => {
+                let buf = fs::read(path).await.map_err(|e| e.to_string())?;
+                Some(base64::encode(buf))
+            }
+            None => None,
+        };
+        items.push(ExportedItem {
+            id: item.id,
+            name: item.name.clone(),
+            url: item.url.clone(),
+            thumb,
+            memo: item.memo.clone(),
+            tags: item.tags.clone(),
+            fields: item.fields.clone(),
+            times: item.times,
+        });
+    }
+    Ok(ExportedTierList {
+        format_version: FORMAT_VERSION,
+        title: tierlist.title.clone(),
+        tiers: tierlist.tiers.clone(),
+        items,
+        items_pool: tierlist.items_pool.clone(),
+        fields: tierlist.fields.clone(),
+    })
+}
+
+// 埋め込まれたサムネイルは img_dir 以下に書き出す
+pub async fn import_tierlist(
+    exported: ExportedTierList,
+    img_dir: &Path,
+) -> Result<TierList, String> {
+    if exported.format_version > FORMAT_VERSION {
+        return Err(format!(
+            "File format version {} is newer than supported version {}",
+            exported.format_version, FORMAT_VERSION
+        ));
+    }
+
+    // 同じファイルを何度読み込んでもサムネイルが上書きされないようにする
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_nanos();
+
+    let mut tierlist = TierList::empty();
+    tierlist.title = exported.title;
+    for item in exported.items {
+        let thumb = match item.thumb {
+            Some(data) => {
+                let buf = base64::decode(&data)
+                    .map_err(|e| format!("Invalid thumbnail of item {}: {}", item.id, e))?;
+                let ext = image::guess_format(&buf)
+                    .ok()
+                    .and_then(|f| f.extensions_str().first().copied())
+                    .unwrap_or("img");
+                let path = img_dir.join(format!("_import_{}_{}.{}", nonce, item.id, ext));
+                fs::write(&path, buf).await.map_err(|e| e.to_string())?;
+                Some(path.to_string_lossy().to_string())
+            }
+            None => None,
+        };
+        tierlist.item_max_id = tierlist.item_max_id.max(item.id);
+        tierlist.items.push(Item {
+            id: item.id,
+            name: item.name,
+            url: item.url,
+            thumb,
+            memo: item.memo,
+            tags: vec![],
+            // 型に合わないものは repair で捨てる
+            fields: item.fields,
+            times: item.times,
+        });
+        tierlist.set_item_tags(item.id, item.tags)?;
+        // タグを付けたことで更新時刻が変わらないようにする
+        if let Some(imported) = tierlist.items.last_mut() {
+            imported.times = item.times;
+        }
+    }
+    tierlist.tier_max_id = exported.tiers.iter().map(|t| t.id).max().unwrap_or(0);
+    tierlist.tiers = exported.tiers;
+    // 色のない (バージョン 1 の) ファイルは既定の色にする
+    for (i, tier) in tierlist.tiers.iter_mut().enumerate() {
+        if tier.color.is_empty() {
+            tier.color = palette_color(i);
+        }
+    }
+    tierlist.items_pool = exported.items_pool;
+    tierlist.field_max_id = exported.fields.iter().map(|f| f.id).max().unwrap_or(0);
+    tierlist.fields = exported.fields;
+    tierlist.repair();
+    Ok(tierlist)
+}
+
+pub async fn write_json(path: &Path, tierlist: &TierList) -> Result<(), String> {
+    let exported = export_tierlist(tierlist).await?;
+    let buf = serde_json::to_vec(&exported).map_err(|e| e.to_string())?;
+    fs::write(path, buf).await.map_err(|e| e.to_string())
+}
+
+pub async fn read_json(path: &Path, img_dir: &Path) -> Result<TierList, String> {
+    let buf = fs::read(path).await.map_err(|e| e.to_string())?;
+    let exported: ExportedTierList =
+        serde_json::from_slice(&buf).map_err(|e| format!("Invalid tierlist file: {}", e))?;
+    import_tierlist(exported, img_dir).await
+}
+
+pub mod commands {
+    use tauri::{async_runtime::Mutex, State};
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::history::History;
+
+    #[tauri::command]
+    pub async fn export_tierlist_json(
+        tierlist: State<'_, Mutex<TierList>>,
+        path: String,
+    ) -> Result<(), String> {
+        let tierlist = tierlist.lock().await.clone();
+        write_json(Path::new(&path), &tierlist).await
+    }
+
+    // 読み込んだ tierlist を編集中の tierlist にする
+    #[tauri::command]
+    pub async fn import_tierlist_json(
+        img_dir: State<'_, TempDir>,
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        path: String,
+    ) -> Result<TierList, String> {
+        let imported = read_json(Path::new(&path), img_dir.path()).await?;
+        // 自動保存が読み込んだ tierlist を前の cur_list に書き込まないように、lock を持ったまま切り替える
+        let mut tierlist = tierlist.lock().await;
+        *tierlist = imported;
+        history.lock().await.clear();
+        *cur_list.lock().await = None;
+        Ok(tierlist.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::fields::FieldType;
+
+    #[tokio::test]
+    async fn export_and_import() {
+        let dir = TempDir::new("export_test").unwrap();
+        let thumb_path = dir.path().join("thumb1");
+        fs::write(&thumb_path, [0u8, 1, 2]).await.unwrap();
+
+        let mut tierlist = TierList::empty();
+        tierlist.title = "list".to_owned();
+        tierlist.tiers = vec![Tier {
+            id: 3,
+            title: "tier3".to_owned(),
+            items: vec![2],
+            color: "#ff7f7f".to_owned(),
+            text_color: None,
+            icon: None,
+        }];
+        tierlist.items = vec![
+            Item {
+                id: 1,
+                name: "item1".to_owned(),
+                url: "url1".to_owned(),
+                thumb: Some(thumb_path.to_string_lossy().to_string()),
+                memo: "memo1".to_owned(),
+                tags: vec![],
+                fields: Default::default(),
+                times: ItemTimes::new(50),
+            },
+            Item {
+                id: 2,
+                name: "item2".to_owned(),
+                url: "url2".to_owned(),
+                thumb: None,
+                memo: "memo2".to_owned(),
+                tags: vec!["tag1".to_owned(), "tag2".to_owned()],
+                fields: Default::default(),
+                times: Default::default(),
+            },
+        ];
+        tierlist.items_pool = vec![1];
+        let year = tierlist.add_field("year", FieldType::Number).unwrap();
+        tierlist
+            .set_item_field(2, year, Some("2022".to_owned()))
+            .unwrap();
+
+        let json_path = dir.path().join("list.json");
+        write_json(&json_path, &tierlist).await.unwrap();
+
+        let img_dir = TempDir::new("import_test").unwrap();
+        let imported = read_json(&json_path, img_dir.path()).await.unwrap();
+        assert_eq!(imported.title, "list");
+        assert_eq!(imported.tiers[0].items, vec![2]);
+        assert_eq!(imported.tier_max_id, 3);
+        assert_eq!(imported.item_max_id, 2);
+        assert_eq!(imported.items_pool, vec![1]);
+        assert_eq!(imported.items[1].thumb, None);
+        assert_eq!(imported.items[1].tags, vec!["tag1", "tag2"]);
+        assert_eq!(imported.fields, tierlist.fields);
+        assert_eq!(imported.field_max_id, 1);
+        assert_eq!(imported.items[1].fields.get(&year).unwrap(), "2022");
+        assert_eq!(imported.items[0].times, ItemTimes::new(50));
+        assert_eq!(imported.items[1].times, tierlist.items[1].times);
+
+        let thumb = imported.items[0].thumb.as_ref().unwrap();
+        assert!(Path::new(thumb).starts_with(img_dir.path()));
+        assert_eq!(fs::read(thumb).await.unwrap(), vec![0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn version1_without_colors() {
+        let json = r#"{"formatVersion":1,"title":"old","tiers":[
+            {"id":1,"title":"S","items":[1]},{"id":2,"title":"A","items":[]}
+        ],"items":[{"id":1,"name":"item1","url":"","thumb":null,"memo":""}],"itemsPool":[]}"#;
+        let exported: ExportedTierList = serde_json::from_str(json).unwrap();
+        let img_dir = TempDir::new("import_test").unwrap();
+        let imported = import_tierlist(exported, img_dir.path()).await.unwrap();
+        assert_eq!(imported.tiers[0].color, palette_color(0));
+        assert_eq!(imported.tiers[1].color, palette_color(1));
+        assert_eq!(imported.tiers[1].text_color, None);
+        assert_eq!(imported.tiers[0].items, vec![1]);
+        assert!(imported.items[0].tags.is_empty());
+    }
+
+    #[tokio::test]
+    async fn newer_format_rejected() {
+        let exported = ExportedTierList {
+            format_version: FORMAT_VERSION + 1,
+            title: "list".to_owned(),
+            tiers: vec![],
+            items: vec![],
+            items_pool: vec![],
+            fields: vec![],
+        };
+        let img_dir = TempDir::new("import_test").unwrap();
+        assert!(import_tierlist(exported, img_dir.path()).await.is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+
+pub type FieldId = i64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FieldType {
+    Text,
+    Number,
+    // "YYYY-MM-DD"
+    Date,
+    Url,
+    // "true" か "false"
+    Boolean,
+}
+
+impl FieldType {
+    // DB に保存する名前
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FieldType::Text => "text",
+            FieldType::Number => "number",
+            FieldType::Date => "date",
+            FieldType::Url => "url",
+            FieldType::Boolean => "boolean",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<FieldType> {
+        match name {
+            "text" => Some(FieldType::Text),
+            "number" => Some(FieldType::Number),
+            "date" => Some(FieldType::Date),
+            "url" => Some(FieldType::Url),
+            "boolean" => Some(FieldType::Boolean),
+            _ => None,
+        }
+    }
+
+    // 型に合う値なら、保存する形に揃えて返す
+    pub fn normalize(&self, value: &str) -> Result<String, String> {
+        let value = value.trim();
+        let invalid = || format!("Invalid {} value: {}", self.as_str(), value);
+        match self {
+            FieldType::Text => Ok(value.to_owned()),
+            FieldType::Number => match value.parse::<f64>() {
+                Ok(n) if n.is_finite() => Ok(n.to_string()),
+                _ => Err(invalid()),
+            },
+            FieldType::Date => parse_date(value)
+                .map(|(y, m, d)| format!("{:04}-{:02}-{:02}", y, m, d))
+                .ok_or_else(invalid),
+            FieldType::Url => match url::Url::parse(value) {
+                Ok(url) if url.has_host() => Ok(url.to_string()),
+                _ => Err(invalid()),
+            },
+            FieldType::Boolean => match value.to_lowercase().as_str() {
+                "true" | "yes" | "1" => Ok("true".to_owned()),
+                "false" | "no" | "0" => Ok("false".to_owned()),
+                _ => Err(invalid()),
+            },
+        }
+    }
+}
+
+// "YYYY-MM-DD" (区切りは / でもよい)
+fn parse_date(value: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = value.split(|c| c == '-' || c == '/');
+    let mut next = || parts.next().and_then(|p| p.parse::<u32>().ok());
+    let (y, m, d) = (next()?, next()?, next()?);
+    if parts.next().is_some() {
+        return None;
+    }
+    let leap = (y % 4 == 0 && y % 100 != 0) || y % 400 == 0;
+    let days = match m {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return None,
+    };
+    if (1..=9999).contains(&y) && (1..=days).contains(&d) {
+        Some((y, m, d))
+    } else {
+        None
+    }
+}
+
+// tierlist ごとに定義する、アイテムに付ける値の名前と型
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldDef {
+    pub id: FieldId,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+}
+
+pub mod commands {
+    use tauri::{async_runtime::Mutex, State};
+
+    use super::*;
+    use crate::{
+        history::{History, Op},
+        tierlist::{commands::apply, ItemId, TierList},
+    };
+
+    #[tauri::command]
+    pub async fn add_field(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        name: String,
+        field_type: FieldType,
+    ) -> Result<TierList, String> {
+        apply(&tierlist, &history, Op::AddField { name, field_type }).await
+    }
+
+    #[tauri::command]
+    pub async fn rename_field(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        id: FieldId,
+        name: String,
+    ) -> Result<TierList, String> {
+        apply(&tierlist, &history, Op::RenameField { id, name }).await
+    }
+
+    // アイテムに付けた値も一緒に消える (元に戻せば戻る)
+    #[tauri::command]
+    pub async fn delete_field(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        id: FieldId,
+    ) -> Result<TierList, String> {
+        apply(&tierlist, &history, Op::DeleteField { id }).await
+    }
+
+    // value が空なら値を消す
+    #[tauri::command]
+    pub async fn set_item_field(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        item: ItemId,
+        field: FieldId,
+        value: String,
+    ) -> Result<TierList, String> {
+        let value = Some(value);
+        apply(&tierlist, &history, Op::SetItemField { item, field, value }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_values() {
+        assert_eq!(FieldType::Text.normalize(" a b ").unwrap(), "a b");
+        assert_eq!(FieldType::Number.normalize("12.50").unwrap(), "12.5");
+        assert_eq!(FieldType::Number.normalize("-3").unwrap(), "-3");
+        assert!(FieldType::Number.normalize("NaN").is_err());
+        assert!(FieldType::Number.normalize("12 eps").is_err());
+        assert_eq!(FieldType::Date.normalize("2022/1/5").unwrap(), "2022-01-05");
+        assert_eq!(
+            FieldType::Date.normalize("2024-02-29").unwrap(),
+            "2024-02-29"
+        );
+        for date in [
+            "2023-02-29",
+            "2022-13-01",
+            "2022-01",
+            "2022-01-01-01",
+            "today",
+        ] {
+            assert!(FieldType::Date.normalize(date).is_err(), "{}", date);
+        }
+        assert_eq!(
+            FieldType::Url.normalize("https://example.com").unwrap(),
+            "https://example.com/"
+        );
+        assert!(FieldType::Url.normalize("example.com").is_err());
+        assert_eq!(FieldType::Boolean.normalize("Yes").unwrap(), "true");
+        assert_eq!(FieldType::Boolean.normalize("0").unwrap(), "false");
+        assert!(FieldType::Boolean.normalize("maybe").is_err());
+
+        for t in [
+            FieldType::Text,
+            FieldType::Number,
+            FieldType::Date,
+            FieldType::Url,
+            FieldType::Boolean,
+        ] {
+            assert_eq!(FieldType::from_name(t.as_str()), Some(t));
+        }
+    }
+}
@@ -0,0 +1,743 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::Serialize;
+
+use crate::{
+    fields::{FieldDef, FieldId, FieldType},
+    tierlist::{Item, ItemData, ItemId, ItemTimes, Tier, TierId, TierList, TierStyle},
+};
+
+// 履歴に残す操作の既定の数
+pub const DEFAULT_MAX_DEPTH: usize = 100;
+
+// TierList に対する 1 回の操作。apply は逆操作を返す
+#[derive(Clone, Debug, PartialEq)]
+pub enum Op {
+    SetTitle {
+        title: String,
+    },
+    AddItem {
+        data: ItemData,
+    },
+    InsertItem {
+        item: Item,
+        tier: Option<TierId>,
+        index: usize,
+    },
+    UpdateItem {
+        id: ItemId,
+        data: ItemData,
+    },
+    DeleteItem {
+        id: ItemId,
+    },
+    MoveItem {
+        id: ItemId,
+        tier: Option<TierId>,
+        index: usize,
+    },
+    // tier (None ならプール) の中の並び順を変える
+    ReorderItems {
+        tier: Option<TierId>,
+        order: Vec<ItemId>,
+    },
+    // タグの名前変更などで複数のアイテムのタグをまとめて置き換える
+    SetItemTags {
+        tags: Vec<(ItemId, Vec<String>)>,
+    },
+    SetItemField {
+        item: ItemId,
+        field: FieldId,
+        value: Option<String>,
+    },
+    AddField {
+        name: String,
+        field_type: FieldType,
+    },
+    InsertField {
+        field: FieldDef,
+        index: usize,
+        values: Vec<(ItemId, String)>,
+    },
+    RenameField {
+        id: FieldId,
+        name: String,
+    },
+    DeleteField {
+        id: FieldId,
+    },
+    AddTier {
+        title: String,
+    },
+    InsertTier {
+        tier: Tier,
+        index: usize,
+    },
+    RenameTier {
+        id: TierId,
+        title: String,
+    },
+    SetTierStyle {
+        id: TierId,
+        style: TierStyle,
+    },
+    ReorderTiers {
+        order: Vec<TierId>,
+    },
+    DeleteTier {
+        id: TierId,
+    },
+    // 戻すときに、操作で変わったアイテムの時刻を元に戻す
+    SetItemTimes {
+        times: Vec<(ItemId, ItemTimes)>,
+    },
+    // 取り込みなどの大きな変更は丸ごと置き換える
+    Replace(Box<TierList>),
+    Group(Vec<Op>),
+}
+
+impl Op {
+    pub fn apply(self, tierlist: &mut TierList) -> Result<Op, String> {
+        match self {
+            Op::SetTitle { title } => {
+                let title = std::mem::replace(&mut tierlist.title, title);
+                Ok(Op::SetTitle { title })
+            }
+            Op::AddItem { data } => {
+                let id = tierlist.add_item(data);
+                Ok(Op::DeleteItem { id })
+            }
+            Op::InsertItem { item, tier, index } => {
+                let id = item.id;
+                tierlist.insert_item(item, tier, index)?;
+                Ok(Op::DeleteItem { id })
+            }
+            Op::UpdateItem { id, data } => {
+                let old = tierlist
+                    .item(id)
+                    .map(ItemData::from)
+                    .ok_or_else(|| format!("Item {} not found", id))?;
+                tierlist.update_item(id, data)?;
+                Ok(Op::UpdateItem { id, data: old })
+            }
+            Op::DeleteItem { id } => {
+                let (tier, index) = tierlist
+                    .find_item(id)
+                    .unwrap_or((None, tierlist.items_pool.len()));
+                let item = tierlist.delete_item(id)?;
+                Ok(Op::InsertItem { item, tier, index })
+            }
+            Op::MoveItem { id, tier, index } => {
+                let (from, pos) = tierlist
+                    .find_item(id)
+                    .ok_or_else(|| format!("Item {} not found", id))?;
+                tierlist.move_item(id, tier, index)?;
+                Ok(Op::MoveItem {
+                    id,
+                    tier: from,
+                    index: pos,
+                })
+            }
+            Op::ReorderItems { tier, order } => {
+                let order = tierlist.reorder_items(tier, order)?;
+                Ok(Op::ReorderItems { tier, order })
+            }
+            Op::SetItemTags { tags } => {
+                // 途中で失敗しないよう、先にすべてのアイテムがあることを確かめる
+                for (id, _) in tags.iter() {
+                    tierlist.item_index(*id)?;
+                }
+                let mut old = vec![];
+                for (id, item_tags) in tags {
+                    old.push((id, tierlist.set_item_tags(id, item_tags)?));
+                }
+                Ok(Op::SetItemTags { tags: old })
+            }
+            Op::SetItemField { item, field, value } => {
+                let value = tierlist.set_item_field(item, field, value)?;
+                Ok(Op::SetItemField { item, field, value })
+            }
+            Op::AddField { name, field_type } => {
+                let id = tierlist.add_field(&name, field_type)?;
+                Ok(Op::DeleteField { id })
+            }
+            Op::InsertField {
+                field,
+                index,
+                values,
+            } => {
+                let id = field.id;
+                tierlist.insert_field(field, index, values)?;
+                Ok(Op::DeleteField { id })
+            }
+            Op::RenameField { id, name } => {
+                let name = tierlist.rename_field(id, &name)?;
+                Ok(Op::RenameField { id, name })
+            }
+            Op::DeleteField { id } => {
+                let index = tierlist.field_index(id)?;
+                let (field, values) = tierlist.delete_field(id)?;
+                Ok(Op::InsertField {
+                    field,
+                    index,
+                    values,
+                })
+            }
+            Op::AddTier { title } => {
+                let id = tierlist.add_tier(&title);
+                Ok(Op::DeleteTier { id })
+            }
+            Op::InsertTier { tier, index } => {
+                let id = tier.id;
+                tierlist.insert_tier(tier, index)?;
+                Ok(Op::DeleteTier { id })
+            }
+            Op::RenameTier { id, title } => {
+                let idx = tierlist.tier_index(id)?;
+                let title = std::mem::replace(&mut tierlist.tiers[idx].title, title);
+                Ok(Op::RenameTier { id, title })
+            }
+            Op::SetTierStyle { id, style } => {
+                let style = tierlist.set_tier_style(id, style)?;
+                Ok(Op::SetTierStyle { id, style })
+            }
+            Op::ReorderTiers { order } => {
+                let old = tierlist.tiers.iter().map(|t| t.id).collect();
+                tierlist.reorder_tiers(&order)?;
+                Ok(Op::ReorderTiers { order: old })
+            }
+            Op::DeleteTier { id } => {
+                let index = tierlist.tier_index(id)?;
+                let tier = tierlist.delete_tier(id)?;
+                Ok(Op::InsertTier { tier, index })
+            }
+            Op::SetItemTimes { times } => {
+                for (id, _) in times.iter() {
+                    tierlist.item_index(*id)?;
+                }
+                let mut old = vec![];
+                for (id, at) in times {
+                    let idx = tierlist.item_index(id)?;
+                    old.push((id, std::mem::replace(&mut tierlist.items[idx].times, at)));
+                }
+                Ok(Op::SetItemTimes { times: old })
+            }
+            Op::Replace(new) => {
+                let old = std::mem::replace(tierlist, *new);
+                Ok(Op::Replace(Box::new(old)))
+            }
+            Op::Group(ops) => {
+                let mut inverses = vec![];
+                for op in ops {
+                    match op.apply(tierlist) {
+                        Ok(inverse) => inverses.push(inverse),
+                        Err(e) => {
+                            // 途中で失敗したら適用済みの分を戻す
+                            for inverse in inverses.into_iter().rev() {
+                                let _ = inverse.apply(tierlist);
+                            }
+                            return Err(e);
+                        }
+                    }
+                }
+                inverses.reverse();
+                Ok(Op::Group(inverses))
+            }
+        }
+    }
+
+    // apply と同じだが、逆操作はアイテムの時刻も操作前の値に戻す
+    // (戻す操作自体が時刻を今に変えてしまうため)
+    fn apply_keeping_times(self, tierlist: &mut TierList) -> Result<Op, String> {
+        let before: HashMap<ItemId, ItemTimes> =
+            tierlist.items.iter().map(|it| (it.id, it.times)).collect();
+        let inverse = self.apply(tierlist)?;
+        let times: Vec<(ItemId, ItemTimes)> = tierlist
+            .items
+            .iter()
+            .filter_map(|it| match before.get(&it.id) {
+                Some(&at) if at != it.times => Some((it.id, at)),
+                _ => None,
+            })
+            .collect();
+        Ok(if times.is_empty() {
+            inverse
+        } else {
+            Op::Group(vec![inverse, Op::SetItemTimes { times }])
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryStatus {
+    pub can_undo: bool,
+    pub can_redo: bool,
+}
+
+// 逆操作を積んでおく undo/redo の履歴
+// begin_group から end_group までの操作はまとめて 1 回で戻す
+pub struct History {
+    undo_stack: VecDeque<Op>,
+    redo_stack: Vec<Op>,
+    max_depth: usize,
+    group: Vec<Op>,
+    group_depth: usize,
+}
+
+impl History {
+    pub fn new(max_depth: usize) -> Self {
+        History {
+            undo_stack: VecDeque::new(),
+            redo_stack: vec![],
+            max_depth,
+            group: vec![],
+            group_depth: 0,
+        }
+    }
+
+    fn push_undo(&mut self, op: Op) {
+        self.undo_stack.push_back(op);
+        while self.undo_stack.len() > self.max_depth {
+            self.undo_stack.pop_front();
+        }
+    }
+
+    pub fn apply(&mut self, tierlist: &mut TierList, op: Op) -> Result<(), String> {
+        let inverse = op.apply_keeping_times(tierlist)?;
+        if self.group_depth > 0 {
+            self.group.push(inverse);
+        } else {
+            self.push_undo(inverse);
+        }
+        self.redo_stack.clear();
+        Ok(())
+    }
+
+    pub fn begin_group(&mut self) {
+        self.group_depth += 1;
+    }
+
+    pub fn end_group(&mut self) {
+        if self.group_depth == 0 {
+            return;
+        }
+        self.group_depth -= 1;
+        if self.group_depth == 0 && !self.group.is_empty() {
+            let mut ops = std::mem::take(&mut self.group);
+            ops.reverse();
+            self.push_undo(Op::Group(ops));
+        }
+    }
+
+    // 開いたままのグループは閉じてから戻す
+    fn close_groups(&mut self) {
+        while self.group_depth > 0 {
+            self.end_group();
+        }
+    }
+
+    // 戻す操作がなければ false
+    pub fn undo(&mut self, tierlist: &mut TierList) -> Result<bool, String> {
+        self.close_groups();
+        match self.undo_stack.pop_back() {
+            Some(op) => match op.clone().apply_keeping_times(tierlist) {
+                Ok(inverse) => {
+                    self.redo_stack.push(inverse);
+                    Ok(true)
+                }
+                Err(e) => {
+                    // 失敗した操作は捨てずに戻しておく
+                    self.undo_stack.push_back(op);
+                    Err(e)
+                }
+            },
+            None => Ok(false),
+        }
+    }
+
+    pub fn redo(&mut self, tierlist: &mut TierList) -> Result<bool, String> {
+        self.close_groups();
+        match self.redo_stack.pop() {
+            Some(op) => match op.clone().apply_keeping_times(tierlist) {
+                Ok(inverse) => {
+                    self.push_undo(inverse);
+                    Ok(true)
+                }
+                Err(e) => {
+                    self.redo_stack.push(op);
+                    Err(e)
+                }
+            },
+            None => Ok(false),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.group.clear();
+        self.group_depth = 0;
+    }
+
+    pub fn status(&self) -> HistoryStatus {
+        HistoryStatus {
+            can_undo: !self.undo_stack.is_empty() || !self.group.is_empty(),
+            can_redo: !self.redo_stack.is_empty(),
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new(DEFAULT_MAX_DEPTH)
+    }
+}
+
+pub mod commands {
+    use tauri::{async_runtime::Mutex, State};
+
+    use super::*;
+
+    #[tauri::command]
+    pub async fn undo(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+    ) -> Result<TierList, String> {
+        let mut tierlist = tierlist.lock().await;
+        history.lock().await.undo(&mut tierlist)?;
+        Ok(tierlist.clone())
+    }
+
+    #[tauri::command]
+    pub async fn redo(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+    ) -> Result<TierList, String> {
+        let mut tierlist = tierlist.lock().await;
+        history.lock().await.redo(&mut tierlist)?;
+        Ok(tierlist.clone())
+    }
+
+    #[tauri::command]
+    pub async fn get_history_status(
+        history: State<'_, Mutex<History>>,
+    ) -> Result<HistoryStatus, String> {
+        Ok(history.lock().await.status())
+    }
+
+    #[tauri::command]
+    pub async fn begin_history_group(history: State<'_, Mutex<History>>) -> Result<(), String> {
+        history.lock().await.begin_group();
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn end_history_group(history: State<'_, Mutex<History>>) -> Result<(), String> {
+        history.lock().await.end_group();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(name: &str) -> ItemData {
+        ItemData {
+            name: name.to_owned(),
+            url: String::new(),
+            thumb: None,
+            memo: String::new(),
+        }
+    }
+
+    fn sample() -> TierList {
+        let mut tierlist = TierList::empty();
+        let s = tierlist.add_tier("S");
+        tierlist.add_tier("A");
+        for name in ["1", "2", "3"] {
+            tierlist.add_item(data(name));
+        }
+        tierlist.move_item(1, Some(s), 0).unwrap();
+        tierlist.move_item(2, Some(s), 1).unwrap();
+        tierlist
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut tierlist = sample();
+        let original = tierlist.clone();
+        let mut history = History::default();
+        history
+            .apply(
+                &mut tierlist,
+                Op::MoveItem {
+                    id: 3,
+                    tier: Some(2),
+                    index: 0,
+                },
+            )
+            .unwrap();
+        history
+            .apply(
+                &mut tierlist,
+                Op::UpdateItem {
+                    id: 1,
+                    data: data("one"),
+                },
+            )
+            .unwrap();
+        history
+            .apply(&mut tierlist, Op::DeleteItem { id: 2 })
+            .unwrap();
+        let edited = tierlist.clone();
+
+        while history.undo(&mut tierlist).unwrap() {}
+        assert_eq!(tierlist, original);
+        assert_eq!(
+            history.status(),
+            HistoryStatus {
+                can_undo: false,
+                can_redo: true
+            }
+        );
+
+        while history.redo(&mut tierlist).unwrap() {}
+        assert_eq!(tierlist, edited);
+    }
+
+    #[test]
+    fn undo_field_changes() {
+        let mut tierlist = sample();
+        let original = tierlist.clone();
+        let mut history = History::default();
+        history
+            .apply(
+                &mut tierlist,
+                Op::AddField {
+                    name: "score".to_owned(),
+                    field_type: FieldType::Number,
+                },
+            )
+            .unwrap();
+        let id = tierlist.fields[0].id;
+        history
+            .apply(
+                &mut tierlist,
+                Op::SetItemField {
+                    item: 1,
+                    field: id,
+                    value: Some("8.5".to_owned()),
+                },
+            )
+            .unwrap();
+        let with_value = tierlist.clone();
+        history
+            .apply(&mut tierlist, Op::DeleteField { id })
+            .unwrap();
+        assert!(tierlist.items[0].fields.is_empty());
+
+        assert!(history.undo(&mut tierlist).unwrap());
+        assert_eq!(tierlist, with_value);
+        while history.undo(&mut tierlist).unwrap() {}
+        // 一度使った id は使い回さない
+        assert_eq!(tierlist.field_max_id, 1);
+        tierlist.field_max_id = 0;
+        assert_eq!(tierlist, original);
+    }
+
+    #[test]
+    fn undo_delete_tier() {
+        let mut tierlist = sample();
+        let original = tierlist.clone();
+        let mut history = History::default();
+        history
+            .apply(&mut tierlist, Op::DeleteTier { id: 1 })
+            .unwrap();
+        assert_eq!(tierlist.items_pool, vec![3, 1, 2]);
+
+        assert!(history.undo(&mut tierlist).unwrap());
+        assert_eq!(tierlist, original);
+        assert!(history.redo(&mut tierlist).unwrap());
+        assert_eq!(tierlist.tiers.len(), 1);
+    }
+
+    #[test]
+    fn undo_added_item_and_tier() {
+        let mut tierlist = sample();
+        let mut history = History::default();
+        history
+            .apply(&mut tierlist, Op::AddItem { data: data("4") })
+            .unwrap();
+        history
+            .apply(
+                &mut tierlist,
+                Op::AddTier {
+                    title: "B".to_owned(),
+                },
+            )
+            .unwrap();
+        let edited = tierlist.clone();
+
+        history.undo(&mut tierlist).unwrap();
+        history.undo(&mut tierlist).unwrap();
+        assert!(tierlist.item(4).is_none());
+        assert_eq!(tierlist.tiers.len(), 2);
+
+        history.redo(&mut tierlist).unwrap();
+        history.redo(&mut tierlist).unwrap();
+        assert_eq!(tierlist, edited);
+    }
+
+    #[test]
+    fn new_op_clears_redo() {
+        let mut tierlist = sample();
+        let mut history = History::default();
+        history
+            .apply(
+                &mut tierlist,
+                Op::SetTitle {
+                    title: "a".to_owned(),
+                },
+            )
+            .unwrap();
+        history.undo(&mut tierlist).unwrap();
+        history
+            .apply(
+                &mut tierlist,
+                Op::SetTitle {
+                    title: "b".to_owned(),
+                },
+            )
+            .unwrap();
+        assert!(!history.redo(&mut tierlist).unwrap());
+        assert_eq!(tierlist.title, "b");
+    }
+
+    #[test]
+    fn group_undone_at_once() {
+        let mut tierlist = sample();
+        let original = tierlist.clone();
+        let mut history = History::default();
+        history.begin_group();
+        history
+            .apply(
+                &mut tierlist,
+                Op::RenameTier {
+                    id: 1,
+                    title: "SS".to_owned(),
+                },
+            )
+            .unwrap();
+        history.begin_group();
+        history
+            .apply(&mut tierlist, Op::ReorderTiers { order: vec![2, 1] })
+            .unwrap();
+        history.end_group();
+        history.end_group();
+
+        assert!(history.undo(&mut tierlist).unwrap());
+        assert_eq!(tierlist, original);
+        assert!(!history.undo(&mut tierlist).unwrap());
+    }
+
+    #[test]
+    fn failed_group_rolled_back() {
+        let mut tierlist = sample();
+        let original = tierlist.clone();
+        let op = Op::Group(vec![
+            Op::MoveItem {
+                id: 3,
+                tier: Some(2),
+                index: 0,
+            },
+            Op::DeleteTier { id: 9 },
+        ]);
+        assert!(op.apply(&mut tierlist).is_err());
+        assert_eq!(tierlist, original);
+    }
+
+    #[test]
+    fn failed_undo_kept() {
+        let mut tierlist = sample();
+        let mut history = History::default();
+        let op = Op::MoveItem {
+            id: 3,
+            tier: Some(2),
+            index: 0,
+        };
+        history.apply(&mut tierlist, op).unwrap();
+        let undo = history.undo_stack.clone();
+
+        // 履歴を通さずにアイテムを消すと戻せないが、履歴は失わない
+        let item = tierlist.delete_item(3).unwrap();
+        assert!(history.undo(&mut tierlist).is_err());
+        assert_eq!(history.undo_stack, undo);
+
+        tierlist.insert_item(item, Some(2), 0).unwrap();
+        assert!(history.undo(&mut tierlist).unwrap());
+        let redo = history.redo_stack.clone();
+        let item = tierlist.delete_item(3).unwrap();
+        assert!(history.redo(&mut tierlist).is_err());
+        assert_eq!(history.redo_stack, redo);
+
+        tierlist.insert_item(item, None, 0).unwrap();
+        assert!(history.redo(&mut tierlist).unwrap());
+        assert_eq!(tierlist.tiers[1].items, vec![3]);
+    }
+
+    #[test]
+    fn depth_bounded() {
+        let mut tierlist = sample();
+        let mut history = History::new(2);
+        for title in ["a", "b", "c"] {
+            history
+                .apply(
+                    &mut tierlist,
+                    Op::SetTitle {
+                        title: title.to_owned(),
+                    },
+                )
+                .unwrap();
+        }
+        while history.undo(&mut tierlist).unwrap() {}
+        assert_eq!(tierlist.title, "a");
+    }
+
+    #[test]
+    fn undo_restores_item_times() {
+        let mut tierlist = sample();
+        for item in tierlist.items.iter_mut() {
+            item.times = ItemTimes::new(1);
+        }
+        let original = tierlist.clone();
+        let mut history = History::default();
+        history
+            .apply(
+                &mut tierlist,
+                Op::MoveItem {
+                    id: 3,
+                    tier: Some(1),
+                    index: 0,
+                },
+            )
+            .unwrap();
+        history
+            .apply(
+                &mut tierlist,
+                Op::UpdateItem {
+                    id: 3,
+                    data: data("three"),
+                },
+            )
+            .unwrap();
+        let edited = tierlist.clone();
+        assert!(edited.item(3).unwrap().times.moved_at > 1);
+
+        while history.undo(&mut tierlist).unwrap() {}
+        assert_eq!(tierlist, original);
+        while history.redo(&mut tierlist).unwrap() {}
+        assert_eq!(tierlist, edited);
+    }
+}
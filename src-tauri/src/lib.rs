@@ -1,3 +1,18 @@
+pub mod autosave;
+pub mod compare;
+pub mod csv_import;
 pub mod db;
+pub mod export;
+pub mod fields;
+pub mod history;
+pub mod placement;
+pub mod recent;
+pub mod render;
 pub mod scraping;
+pub mod search;
+pub mod snapshot;
+pub mod sort;
+pub mod tags;
+pub mod template;
+pub mod thumbnail;
 pub mod tierlist;
@@ -6,24 +6,117 @@
 use sqlx::SqlitePool;
 use tauri::{async_runtime::Mutex, Manager};
 use tempdir::TempDir;
-use tierlist_maker::{db, scraping, tierlist};
+use tierlist_maker::{
+    autosave, compare, csv_import, db, export, fields, history, placement, recent, render,
+    scraping, search, snapshot, sort, tags, template, thumbnail, tierlist,
+};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let img_tmp_dir = TempDir::new("imgs")?;
-    let tierlist = Mutex::new(tierlist::TierList::empty());
+    let tierlist = Mutex::new(tierlist::TierList::with_default_tiers());
     let cur_sqlite_pool: Mutex<Option<SqlitePool>> = Mutex::new(None);
     let cur_file: Mutex<Option<String>> = Mutex::new(None);
+    let cur_list: Mutex<Option<tierlist::ListId>> = Mutex::new(None);
     tauri::Builder::default()
         .invoke_handler(tauri::generate_handler![
+            autosave::commands::get_autosave_options,
+            autosave::commands::set_autosave_options,
+            autosave::commands::check_recovery,
+            autosave::commands::restore_recovery,
+            autosave::commands::discard_recovery,
+            compare::commands::list_tierlists_in_file,
+            compare::commands::compare_tierlists,
+            compare::commands::export_comparison_csv,
+            compare::commands::export_comparison_png,
+            csv_import::commands::import_items_csv,
+            db::commands::pick_db_file_to_open,
+            db::commands::pick_db_file_to_save,
+            db::commands::open_db_file,
+            db::commands::reopen_last_file,
             db::commands::read_tierlist_from_db,
             db::commands::write_tierlist_to_db,
-            scraping::commands::scrape_amazon,
+            db::commands::save_tierlist_as_db_file,
+            db::commands::list_tierlists_in_db,
+            db::commands::create_tierlist_in_db,
+            db::commands::rename_tierlist_in_db,
+            db::commands::duplicate_tierlist_in_db,
+            db::commands::delete_tierlist_in_db,
+            export::commands::export_tierlist_json,
+            export::commands::import_tierlist_json,
+            fields::commands::add_field,
+            fields::commands::rename_field,
+            fields::commands::delete_field,
+            fields::commands::set_item_field,
+            history::commands::undo,
+            history::commands::redo,
+            history::commands::get_history_status,
+            history::commands::begin_history_group,
+            history::commands::end_history_group,
+            placement::commands::get_item_history,
+            placement::commands::get_changes_since,
+            recent::commands::get_recent_files,
+            recent::commands::remove_recent_file,
+            recent::commands::set_reopen_last,
+            recent::commands::get_current_file,
+            render::commands::export_tierlist_png,
+            scraping::commands::scrape_item,
+            search::commands::search_items,
+            snapshot::commands::list_snapshots,
+            snapshot::commands::create_snapshot,
+            snapshot::commands::restore_snapshot,
+            snapshot::commands::delete_snapshot,
+            snapshot::commands::diff_versions,
+            sort::commands::sort_items,
+            tags::commands::list_tags,
+            tags::commands::add_item_tag,
+            tags::commands::remove_item_tag,
+            tags::commands::rename_tag,
+            tags::commands::delete_tag,
+            tags::commands::query_items_by_tags,
+            template::commands::list_templates,
+            template::commands::save_template,
+            template::commands::delete_template,
+            template::commands::new_tierlist_from_template,
+            thumbnail::commands::get_thumbnail_options,
+            thumbnail::commands::set_thumbnail_options,
+            thumbnail::commands::import_thumbnail,
+            tierlist::commands::get_tierlist,
+            tierlist::commands::validate_tierlist,
+            tierlist::commands::new_tierlist,
+            tierlist::commands::set_tierlist_title,
+            tierlist::commands::add_item,
+            tierlist::commands::update_item,
+            tierlist::commands::delete_item,
+            tierlist::commands::move_item,
+            tierlist::commands::add_tier,
+            tierlist::commands::rename_tier,
+            tierlist::commands::set_tier_style,
+            tierlist::commands::reorder_tiers,
+            tierlist::commands::delete_tier,
         ])
         .setup(|app| {
+            let (recent_files, thumb_options, autosave_options) =
+                tauri::async_runtime::block_on(async {
+                    match app.path_resolver().app_config_dir() {
+                        Some(config_dir) => (
+                            recent::load(&config_dir).await,
+                            thumbnail::load_options(&config_dir).await,
+                            autosave::load_options(&config_dir).await,
+                        ),
+                        None => Default::default(),
+                    }
+                });
             app.manage(img_tmp_dir);
+            app.manage(scraping::ScraperRegistry::default());
+            app.manage(Mutex::new(thumb_options));
             app.manage(tierlist);
+            app.manage(Mutex::new(history::History::default()));
             app.manage(cur_sqlite_pool);
             app.manage(cur_file);
+            app.manage(cur_list);
+            app.manage(Mutex::new(recent_files));
+            app.manage(Mutex::new(autosave_options));
+            tauri::async_runtime::spawn(autosave::run(app.handle()));
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::tierlist::{ItemId, TierId, Timestamp};
+
+// アイテムが置かれていた場所
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Place {
+    // tierlist になかった (追加する前か削除した後)
+    Absent,
+    Pool,
+    // title はそのときの tier の名前
+    Tier { id: TierId, title: String },
+}
+
+impl Place {
+    // tier は名前が変わっていても id が同じなら同じ場所
+    pub fn same(&self, other: &Place) -> bool {
+        match (self, other) {
+            (Place::Tier { id: a, .. }, Place::Tier { id: b, .. }) => a == b,
+            (Place::Absent, Place::Absent) | (Place::Pool, Place::Pool) => true,
+            _ => false,
+        }
+    }
+}
+
+// 保存したときに記録した、アイテムを置いた場所の変化
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Placement {
+    pub item: ItemId,
+    pub name: String,
+    pub from: Place,
+    pub to: Place,
+    // 移した先の tier での位置
+    pub pos: Option<usize>,
+    pub at: Timestamp,
+}
+
+// ある時点からのアイテムごとの変化をまとめたもの
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemChange {
+    pub item: ItemId,
+    pub name: String,
+    // 最初の変化の前の場所と、最後の変化の後の場所
+    pub from: Place,
+    pub to: Place,
+    pub moves: usize,
+    pub at: Timestamp,
+}
+
+// placements は記録した順。結局元の場所に戻ったアイテムは含めず、最後に変わったのが新しい順に返す
+pub fn changes(placements: Vec<Placement>) -> Vec<ItemChange> {
+    let mut changes: Vec<ItemChange> = vec![];
+    let mut index = HashMap::new();
+    for placement in placements {
+        match index.get(&placement.item) {
+            Some(&i) => {
+                let change: &mut ItemChange = &mut changes[i];
+                change.name = placement.name;
+                change.to = placement.to;
+                change.moves += 1;
+                change.at = placement.at;
+            }
+            None => {
+                index.insert(placement.item, changes.len());
+                changes.push(ItemChange {
+                    item: placement.item,
+                    name: placement.name,
+                    from: placement.from,
+                    to: placement.to,
+                    moves: 1,
+                    at: placement.at,
+                });
+            }
+        }
+    }
+    changes.retain(|c| !c.from.same(&c.to));
+    changes.sort_by(|a, b| b.at.cmp(&a.at).then(a.item.cmp(&b.item)));
+    changes
+}
+
+pub mod commands {
+    use sqlx::SqlitePool;
+    use tauri::{async_runtime::Mutex, State};
+
+    use super::*;
+    use crate::{db, tierlist::ListId};
+
+    async fn list_id_and_pool(
+        pool: &Mutex<Option<SqlitePool>>,
+        cur_list: &Mutex<Option<ListId>>,
+    ) -> Result<(ListId, SqlitePool), String> {
+        let list_id = cur_list
+            .lock()
+            .await
+            .ok_or("Tier list has not been saved")?;
+        let pool = pool.lock().await.clone().ok_or("DB not opened")?;
+        Ok((list_id, pool))
+    }
+
+    // 保存した時点の変化だけが記録されている
+    #[tauri::command]
+    pub async fn get_item_history(
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        item: ItemId,
+    ) -> Result<Vec<Placement>, String> {
+        let (list_id, pool) = list_id_and_pool(&pool, &cur_list).await?;
+        db::item_placements(&pool, list_id, item)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    #[tauri::command]
+    pub async fn get_changes_since(
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        since: Timestamp,
+    ) -> Result<Vec<ItemChange>, String> {
+        let (list_id, pool) = list_id_and_pool(&pool, &cur_list).await?;
+        let placements = db::placements_since(&pool, list_id, since)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(changes(placements))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tier(id: TierId, title: &str) -> Place {
+        Place::Tier {
+            id,
+            title: title.to_owned(),
+        }
+    }
+
+    fn placement(item: ItemId, from: Place, to: Place, at: Timestamp) -> Placement {
+        Placement {
+            item,
+            name: format!("item{}", item),
+            from,
+            to,
+            pos: None,
+            at,
+        }
+    }
+
+    #[test]
+    fn summarize_changes() {
+        let placements = vec![
+            placement(1, Place::Absent, Place::Pool, 10),
+            placement(2, tier(1, "B"), tier(2, "A"), 20),
+            placement(1, Place::Pool, tier(1, "B"), 30),
+            placement(3, tier(2, "A"), tier(1, "B"), 40),
+            // 名前が変わっただけの同じ tier に戻った
+            placement(3, tier(1, "B"), tier(2, "S"), 50),
+            placement(4, tier(1, "B"), Place::Absent, 20),
+        ];
+        let changes = changes(placements);
+        let summary: Vec<(ItemId, &Place, &Place, usize, Timestamp)> = changes
+            .iter()
+            .map(|c| (c.item, &c.from, &c.to, c.moves, c.at))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, &Place::Absent, &tier(1, "B"), 2, 30),
+                (2, &tier(1, "B"), &tier(2, "A"), 1, 20),
+                (4, &tier(1, "B"), &Place::Absent, 1, 20),
+            ]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::tierlist::ListId;
+
+const RECENT_FILES_NAME: &str = "recent_files.json";
+const MAX_RECENT_FILES: usize = 10;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentFiles {
+    // 新しく開いたものが先頭
+    pub paths: Vec<String>,
+    // ファイルごとに最後に開いた tierlist
+    #[serde(default)]
+    pub last_list_ids: BTreeMap<String, ListId>,
+    pub reopen_last: bool,
+}
+
+impl RecentFiles {
+    pub fn push(&mut self, path: &str) {
+        self.paths.retain(|p| p != path);
+        self.paths.insert(0, path.to_owned());
+        self.paths.truncate(MAX_RECENT_FILES);
+        let paths = &self.paths;
+        self.last_list_ids.retain(|p, _| paths.contains(p));
+    }
+
+    pub fn remove(&mut self, path: &str) {
+        self.paths.retain(|p| p != path);
+        self.last_list_ids.remove(path);
+    }
+
+    pub fn last_list_id(&self, path: &str) -> Option<ListId> {
+        self.last_list_ids.get(path).copied()
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.paths.first().map(|p| p.as_str())
+    }
+}
+
+pub fn recent_files_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(RECENT_FILES_NAME)
+}
+
+// 読めなかった場合は空のリストとして扱う
+pub async fn load(config_dir: &Path) -> RecentFiles {
+    match fs::read(recent_files_path(config_dir)).await {
+        Ok(buf) => serde_json::from_slice(&buf).unwrap_or_default(),
+        Err(_) => RecentFiles::default(),
+    }
+}
+
+pub async fn save(config_dir: &Path, recent: &RecentFiles) -> Result<(), String> {
+    fs::create_dir_all(config_dir)
+        .await
+        .map_err(|e| e.to_string())?;
+    let buf = serde_json::to_vec_pretty(recent).map_err(|e| e.to_string())?;
+    fs::write(recent_files_path(config_dir), buf)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+pub fn config_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path_resolver()
+        .app_config_dir()
+        .ok_or_else(|| "Config directory not found".to_owned())
+}
+
+// path を最近開いたファイルの先頭に追加して保存する
+pub async fn touch(
+    app: &tauri::AppHandle,
+    recent: &mut RecentFiles,
+    path: &str,
+    list_id: Option<ListId>,
+) -> Result<(), String> {
+    recent.push(path);
+    if let Some(list_id) = list_id {
+        recent.last_list_ids.insert(path.to_owned(), list_id);
+    }
+    save(&config_dir(app)?, recent).await
+}
+
+pub mod commands {
+    use tauri::{async_runtime::Mutex, AppHandle, State};
+
+    use super::*;
+
+    #[tauri::command]
+    pub async fn get_recent_files(
+        recent: State<'_, Mutex<RecentFiles>>,
+    ) -> Result<RecentFiles, String> {
+        Ok(recent.lock().await.clone())
+    }
+
+    #[tauri::command]
+    pub async fn remove_recent_file(
+        app: AppHandle,
+        recent: State<'_, Mutex<RecentFiles>>,
+        path: String,
+    ) -> Result<RecentFiles, String> {
+        let mut recent = recent.lock().await;
+        recent.remove(&path);
+        save(&config_dir(&app)?, &recent).await?;
+        Ok(recent.clone())
+    }
+
+    #[tauri::command]
+    pub async fn set_reopen_last(
+        app: AppHandle,
+        recent: State<'_, Mutex<RecentFiles>>,
+        reopen_last: bool,
+    ) -> Result<(), String> {
+        let mut recent = recent.lock().await;
+        recent.reopen_last = reopen_last;
+        save(&config_dir(&app)?, &recent).await
+    }
+
+    #[tauri::command]
+    pub async fn get_current_file(
+        cur_file: State<'_, Mutex<Option<String>>>,
+    ) -> Result<Option<String>, String> {
+        Ok(cur_file.lock().await.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn push_moves_to_front() {
+        let mut recent = RecentFiles::default();
+        recent.push("a");
+        recent.push("b");
+        recent.push("a");
+        assert_eq!(recent.paths, vec!["a", "b"]);
+        assert_eq!(recent.last(), Some("a"));
+
+        for i in 0..MAX_RECENT_FILES {
+            recent.push(&i.to_string());
+        }
+        assert_eq!(recent.paths.len(), MAX_RECENT_FILES);
+        assert_eq!(recent.last(), Some("9"));
+    }
+
+    #[test]
+    fn last_list_id_per_file() {
+        let mut recent = RecentFiles::default();
+        recent.push("a");
+        recent.last_list_ids.insert("a".to_owned(), 3);
+        recent.push("b");
+        // 別のファイルの tierlist の id は使わない
+        assert_eq!(recent.last_list_id("b"), None);
+        assert_eq!(recent.last_list_id("a"), Some(3));
+
+        for i in 0..MAX_RECENT_FILES {
+            recent.push(&i.to_string());
+        }
+        assert_eq!(recent.last_list_id("a"), None);
+        assert!(recent.last_list_ids.is_empty());
+    }
+
+    #[tokio::test]
+    async fn save_and_load() {
+        let dir = TempDir::new("recent_test").unwrap();
+        let config_dir = dir.path().join("config");
+        assert_eq!(load(&config_dir).await, RecentFiles::default());
+
+        let recent = RecentFiles {
+            paths: vec!["a.db3".to_owned(), "b.db3".to_owned()],
+            last_list_ids: [("b.db3".to_owned(), 2)].into_iter().collect(),
+            reopen_last: true,
+        };
+        save(&config_dir, &recent).await.unwrap();
+        assert_eq!(load(&config_dir).await, recent);
+    }
+}
@@ -0,0 +1,680 @@
+use std::collections::HashMap;
+
+use image::{imageops, imageops::FilterType, ImageFormat, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compare::{ComparedItem, Comparison},
+    placement::Place,
+    sort::fold_name,
+    tierlist::{palette_color, parse_color, Item, ItemId, Tier, TierList},
+};
+
+const BACKGROUND: Rgb<u8> = Rgb([26, 26, 23]);
+const PLACEHOLDER: Rgb<u8> = Rgb([64, 64, 60]);
+const LABEL_TEXT: Rgb<u8> = Rgb([0, 0, 0]);
+const POOL_LABEL: Rgb<u8> = Rgb([160, 160, 160]);
+// 段と段の間の隙間
+const GAP: u32 = 2;
+// 大きすぎる画像を作ろうとしないように、マスの大きさと列の数を制限する
+const MAX_THUMB_SIZE: u32 = 1024;
+const MAX_COLUMNS: u32 = 64;
+// これより画素の多い画像は作らない (RGB で 192MB)
+const MAX_PIXELS: u64 = 64 * 1024 * 1024;
+
+fn too_large() -> String {
+    "Image would be too large".to_owned()
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderOptions {
+    // サムネイル 1 つ分のマスの大きさ (正方形)
+    pub thumb_size: u32,
+    // 1 行に並べるサムネイルの数
+    pub columns: u32,
+    pub include_pool: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            thumb_size: 100,
+            columns: 8,
+            include_pool: false,
+        }
+    }
+}
+
+// フォントに無い文字の代わりに描く四角
+const MISSING_GLYPH: [u8; 7] = [0x1F, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1F];
+
+// 5x7 のビットマップフォント (各行の下位 5 ビットを左から使う)
+// 英数字と一部の記号しか持たない。仮名は romanize でローマ字にしてから描き、
+// 漢字などそれ以外の文字は四角になる
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '*' => [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '?' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        ' ' => [0x00; 7],
+        // フォントに無い文字は四角で表示する
+        _ => MISSING_GLYPH,
+    }
+}
+
+// 平仮名と、それに対応するローマ字 (空白区切り)
+const KANA: &str =
+    "あいうえおかきくけこがぎぐげごさしすせそざじずぜぞたちつてとだぢづでどなにぬねの\
+    はひふへほばびぶべぼぱぴぷぺぽまみむめもやゆよらりるれろわゐゑをんゔぁぃぅぇぉゃゅょゎ";
+const ROMAJI: &str = "a i u e o ka ki ku ke ko ga gi gu ge go sa shi su se so za ji zu ze zo \
+    ta chi tsu te to da ji zu de do na ni nu ne no ha hi fu he ho ba bi bu be bo pa pi pu pe po \
+    ma mi mu me mo ya yu yo ra ri ru re ro wa i e o n vu a i u e o ya yu yo wa";
+
+fn kana_romaji(c: char) -> Option<&'static str> {
+    let i = KANA.chars().position(|k| k == c)?;
+    ROMAJI.split_whitespace().nth(i)
+}
+
+// フォントに無い仮名をローマ字にして、全角英数字を半角にする
+fn romanize(text: &str) -> String {
+    let mut out = String::new();
+    // 直前が促音 (っ) なら次の子音を重ねる
+    let mut double = false;
+    for c in fold_name(text) {
+        let ends_with_vowel = out.ends_with(|v| "aiueo".contains(v));
+        match c {
+            'っ' => {
+                double = true;
+                continue;
+            }
+            // 拗音は直前の i を置き換える (き + ゃ = kya、し + ゃ = sha)
+            'ゃ' | 'ゅ' | 'ょ' if out.ends_with('i') => {
+                out.pop();
+                if !(out.ends_with("sh") || out.ends_with("ch") || out.ends_with('j')) {
+                    out.push('y');
+                }
+                out.push_str(kana_romaji(c).map_or("", |r| &r[1..]));
+            }
+            // 小さい母音は直前の母音を置き換える (ふ + ぁ = fa)
+            'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' if ends_with_vowel => {
+                out.pop();
+                out.push_str(kana_romaji(c).unwrap_or(""));
+            }
+            // 長音は直前の母音を伸ばす
+            'ー' if ends_with_vowel => {
+                let vowel = out.chars().last().unwrap_or('-');
+                out.push(vowel);
+            }
+            'ー' => out.push('-'),
+            '・' | '\u{3000}' => out.push(' '),
+            _ => match kana_romaji(c) {
+                Some(romaji) => {
+                    if double && !romaji.starts_with(|v| "aiueon".contains(v)) {
+                        out.push_str(&romaji[..1]);
+                    }
+                    out.push_str(romaji);
+                }
+                None => out.push(c),
+            },
+        }
+        double = false;
+    }
+    out
+}
+
+const GLYPH_W: u32 = 6; // 字間を含む
+const GLYPH_H: u32 = 8; // 行間を含む
+
+// text を幅 max_chars 文字で単語ごとに折り返す
+fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for word in text.split_whitespace() {
+        let word: Vec<char> = word.chars().collect();
+        for chunk in word.chunks(max_chars.max(1)) {
+            let chunk: String = chunk.iter().collect();
+            match lines.last_mut() {
+                Some(line) if line.chars().count() + 1 + chunk.chars().count() <= max_chars => {
+                    line.push(' ');
+                    line.push_str(&chunk);
+                }
+                _ => lines.push(chunk),
+            }
+        }
+    }
+    lines
+}
+
+// text を四角なしで描けるか
+fn can_draw(text: &str) -> bool {
+    romanize(text).chars().all(|c| glyph(c) != MISSING_GLYPH)
+}
+
+// 漢字など描けない文字を含むタイトルは、描けるならアイコンで代わりにする
+fn tier_label(tier: &Tier) -> &str {
+    match tier.icon.as_deref() {
+        Some(icon) if !can_draw(&tier.title) && !icon.trim().is_empty() && can_draw(icon) => icon,
+        _ => tier.title.as_str(),
+    }
+}
+
+// (x, y, w, h) の中央に text を収まる最大の大きさで描く
+fn draw_label(img: &mut RgbImage, text: &str, x: u32, y: u32, w: u32, h: u32, color: Rgb<u8>) {
+    let text = &romanize(text);
+    let mut layout = None;
+    for scale in (1..=8).rev() {
+        let max_chars = (w.saturating_sub(4) / (GLYPH_W * scale)) as usize;
+        if max_chars == 0 {
+            continue;
+        }
+        let lines = wrap(text, max_chars);
+        if lines.len() as u32 * GLYPH_H * scale <= h.saturating_sub(4) {
+            layout = Some((scale, lines));
+            break;
+        }
+    }
+    let (scale, lines) = match layout {
+        Some(layout) => layout,
+        None => (1, wrap(text, (w / GLYPH_W) as usize)),
+    };
+
+    let text_h = lines.len() as u32 * GLYPH_H * scale;
+    let top = y + h.saturating_sub(text_h) / 2;
+    for (row, line) in lines.iter().enumerate() {
+        let line_w = line.chars().count() as u32 * GLYPH_W * scale;
+        let left = x + w.saturating_sub(line_w) / 2;
+        let line_top = top + row as u32 * GLYPH_H * scale;
+        for (col, c) in line.chars().enumerate() {
+            let gx = left + col as u32 * GLYPH_W * scale;
+            for (gy, bits) in glyph(c).iter().enumerate() {
+                for bit in 0..5 {
+                    if bits & (0x10 >> bit) == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let px = gx + bit * scale + dx;
+                            let py = line_top + gy as u32 * scale + dy;
+                            if px < x + w && py < y + h {
+                                img.put_pixel(px, py, color);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn fill(img: &mut RgbImage, x: u32, y: u32, w: u32, h: u32, color: Rgb<u8>) {
+    for py in y..(y + h).min(img.height()) {
+        for px in x..(x + w).min(img.width()) {
+            img.put_pixel(px, py, color);
+        }
+    }
+}
+
+// サムネイルをマスに収まるよう縮小して中央に置く。読めなければプレースホルダにする
+fn draw_thumb(img: &mut RgbImage, item: Option<&Item>, x: u32, y: u32, size: u32) {
+    let thumb = item
+        .and_then(|item| item.thumb.as_ref())
+        .and_then(|path| image::open(path).ok());
+    match thumb {
+        Some(thumb) => {
+            let thumb = thumb.resize(size, size, FilterType::Triangle).to_rgb8();
+            let ox = x + (size - thumb.width()) / 2;
+            let oy = y + (size - thumb.height()) / 2;
+            imageops::overlay(img, &thumb, ox as i64, oy as i64);
+        }
+        None => fill(img, x + 1, y + 1, size - 2, size - 2, PLACEHOLDER),
+    }
+}
+
+// (ラベル, 背景色, 文字色, 並べるアイテム)
+type Row<'a> = (&'a str, Rgb<u8>, Rgb<u8>, &'a [ItemId]);
+
+// 左にラベルの列、右にサムネイルを columns 個ずつ折り返して並べた画像を作る
+pub fn render(tierlist: &TierList, options: &RenderOptions) -> Result<RgbImage, String> {
+    if !(8..=MAX_THUMB_SIZE).contains(&options.thumb_size)
+        || !(1..=MAX_COLUMNS).contains(&options.columns)
+    {
+        return Err("Invalid render options".to_owned());
+    }
+    let size = options.thumb_size;
+    let columns = options.columns;
+    let label_w = size * 3 / 2;
+
+    // 色が読めない tier は位置に応じた既定の色にする
+    let mut rows: Vec<Row> = tierlist
+        .tiers
+        .iter()
+        .enumerate()
+        .map(|(i, tier)| {
+            let color = parse_color(&tier.color)
+                .or_else(|| parse_color(&palette_color(i)))
+                .map(Rgb)
+                .unwrap_or(POOL_LABEL);
+            let text_color = tier
+                .text_color
+                .as_deref()
+                .and_then(parse_color)
+                .map(Rgb)
+                .unwrap_or(LABEL_TEXT);
+            (tier_label(tier), color, text_color, tier.items.as_slice())
+        })
+        .collect();
+    if options.include_pool {
+        rows.push(("", POOL_LABEL, LABEL_TEXT, tierlist.items_pool.as_slice()));
+    }
+
+    let row_heights: Vec<u32> = rows
+        .iter()
+        .map(|(_, _, _, items)| {
+            let items = u32::try_from(items.len()).ok()?;
+            let lines = items.checked_add(columns - 1)? / columns;
+            lines.max(1).checked_mul(size)
+        })
+        .collect::<Option<_>>()
+        .ok_or_else(too_large)?;
+    let width = columns
+        .checked_mul(size)
+        .and_then(|w| w.checked_add(label_w))
+        .ok_or_else(too_large)?;
+    let height = row_heights
+        .iter()
+        .try_fold(0u32, |sum, h| sum.checked_add(*h)?.checked_add(GAP))
+        .ok_or_else(too_large)?
+        .max(1);
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err(too_large());
+    }
+    let mut img = RgbImage::from_pixel(width, height, BACKGROUND);
+
+    let items: HashMap<ItemId, &Item> = tierlist.items.iter().map(|it| (it.id, it)).collect();
+    let mut y = 0;
+    for ((title, color, text_color, row_items), row_h) in rows.iter().zip(row_heights) {
+        fill(&mut img, 0, y, label_w, row_h, *color);
+        draw_label(&mut img, title, 0, y, label_w, row_h, *text_color);
+        for (i, item_id) in row_items.iter().enumerate() {
+            let i = i as u32;
+            let x = label_w + (i % columns) * size;
+            let item_y = y + (i / columns) * size;
+            draw_thumb(&mut img, items.get(item_id).copied(), x, item_y, size);
+        }
+        y += row_h + GAP;
+    }
+
+    Ok(img)
+}
+
+// 比べた結果の表の 1 行の高さと、名前、左右の tier、差の列の幅
+const COMPARE_ROW_H: u32 = 32;
+const COMPARE_COLUMNS: [u32; 4] = [360, 120, 120, 80];
+// 差が 0%、25% (5 段の tierlist で 1 段) 以内、それより大きいときの差の列の色
+const AGREE: Rgb<u8> = Rgb([127, 255, 127]);
+const NEAR: Rgb<u8> = Rgb([255, 223, 127]);
+const FAR: Rgb<u8> = Rgb([255, 127, 127]);
+
+// 見出しの後に、両方が置いたアイテム、左だけ、右だけの順に 1 行ずつ並べた表を作る
+pub fn render_comparison(comparison: &Comparison) -> Result<RgbImage, String> {
+    let place_label = |place: &Place| match place {
+        Place::Tier { title, .. } => title.clone(),
+        Place::Pool | Place::Absent => "-".to_owned(),
+    };
+    let agreement = comparison
+        .agreement
+        .map_or("-".to_owned(), |a| format!("{:.0}%", a * 100.0));
+    let mut rows = vec![(
+        [
+            "ITEM".to_owned(),
+            comparison.left_title.clone(),
+            comparison.right_title.clone(),
+            agreement,
+        ],
+        POOL_LABEL,
+    )];
+    let items: Vec<&ComparedItem> = comparison
+        .both
+        .iter()
+        .chain(comparison.only_left.iter())
+        .chain(comparison.only_right.iter())
+        .collect();
+    for item in items {
+        let (diff, color) = match item.diff.map(|d| (d * 100.0).round() as i64) {
+            Some(0) => ("0%".to_owned(), AGREE),
+            Some(d) if d.abs() <= 25 => (format!("{:+}%", d), NEAR),
+            Some(d) => (format!("{:+}%", d), FAR),
+            None => ("-".to_owned(), PLACEHOLDER),
+        };
+        let cells = [
+            item.name.clone(),
+            place_label(&item.left),
+            place_label(&item.right),
+            diff,
+        ];
+        rows.push((cells, color));
+    }
+
+    let width = COMPARE_COLUMNS.iter().sum::<u32>() + GAP * 3;
+    let height = u32::try_from(rows.len())
+        .ok()
+        .and_then(|rows| rows.checked_mul(COMPARE_ROW_H + GAP))
+        .ok_or_else(too_large)?;
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err(too_large());
+    }
+    let mut img = RgbImage::from_pixel(width, height, BACKGROUND);
+    for (row, (cells, diff_color)) in rows.iter().enumerate() {
+        let y = row as u32 * (COMPARE_ROW_H + GAP);
+        let mut x = 0;
+        for (col, (text, &w)) in cells.iter().zip(COMPARE_COLUMNS.iter()).enumerate() {
+            let (color, text_color) = match col {
+                0 if row > 0 => (BACKGROUND, POOL_LABEL),
+                3 => (*diff_color, LABEL_TEXT),
+                _ => (POOL_LABEL, LABEL_TEXT),
+            };
+            fill(&mut img, x, y, w, COMPARE_ROW_H, color);
+            draw_label(&mut img, text, x, y, w, COMPARE_ROW_H, text_color);
+            x += w + GAP;
+        }
+    }
+    Ok(img)
+}
+
+pub async fn save_comparison_png(comparison: Comparison, path: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        render_comparison(&comparison)?
+            .save_with_format(&path, ImageFormat::Png)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// 描画は重いのでブロッキングスレッドで行う
+pub async fn save_png(
+    tierlist: TierList,
+    path: String,
+    options: RenderOptions,
+) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        render(&tierlist, &options)?
+            .save_with_format(&path, ImageFormat::Png)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+pub mod commands {
+    use tauri::{async_runtime::Mutex, State};
+
+    use super::*;
+
+    // ラベルは内蔵の 5x7 フォントで描く。英数字と仮名 (ローマ字にする) しか描けないので、
+    // 漢字などを含む tier はアイコンが描ければアイコンを、描けなければ四角の入ったタイトルを描く
+    #[tauri::command]
+    pub async fn export_tierlist_png(
+        tierlist: State<'_, Mutex<TierList>>,
+        path: String,
+        options: RenderOptions,
+    ) -> Result<(), String> {
+        let tierlist = tierlist.lock().await.clone();
+        save_png(tierlist, path, options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn tierlist_with_thumb(thumb: &str) -> TierList {
+        let mut tierlist = TierList::empty();
+        tierlist.tiers = vec![
+            Tier {
+                id: 1,
+                title: "S".to_owned(),
+                items: vec![1, 2, 3],
+                color: "#ff7f7f".to_owned(),
+                text_color: None,
+                icon: None,
+            },
+            Tier {
+                id: 2,
+                title: "A".to_owned(),
+                items: vec![],
+                color: "#123456".to_owned(),
+                text_color: Some("#ffffff".to_owned()),
+                icon: None,
+            },
+        ];
+        for id in 1..=4 {
+            tierlist.items.push(Item {
+                id,
+                name: format!("item{}", id),
+                url: "".to_owned(),
+                thumb: if id == 1 {
+                    Some(thumb.to_owned())
+                } else {
+                    None
+                },
+                memo: "".to_owned(),
+                tags: vec![],
+                fields: Default::default(),
+                times: Default::default(),
+            });
+        }
+        tierlist.items_pool = vec![4];
+        tierlist
+    }
+
+    #[test]
+    fn render_layout() {
+        let dir = TempDir::new("render_test").unwrap();
+        let thumb = dir.path().join("thumb.png");
+        RgbImage::from_pixel(50, 100, Rgb([0, 0, 255]))
+            .save(&thumb)
+            .unwrap();
+        let tierlist = tierlist_with_thumb(&thumb.to_string_lossy());
+
+        let options = RenderOptions {
+            thumb_size: 20,
+            columns: 2,
+            include_pool: false,
+        };
+        let img = render(&tierlist, &options).unwrap();
+        // S は 2 行, A は空でも 1 行
+        assert_eq!(img.width(), 30 + 2 * 20);
+        assert_eq!(img.height(), (40 + GAP) + (20 + GAP));
+
+        assert_eq!(*img.get_pixel(1, 1), Rgb([0xff, 0x7f, 0x7f]));
+        assert_eq!(*img.get_pixel(1, 40 + GAP + 1), Rgb([0x12, 0x34, 0x56]));
+        // 縦長のサムネイルは左右に余白を空けて中央に置かれる
+        assert_eq!(*img.get_pixel(30 + 10, 10), Rgb([0, 0, 255]));
+        assert_eq!(*img.get_pixel(30 + 1, 10), BACKGROUND);
+        // 3 つ目は 2 行目に折り返す
+        assert_eq!(*img.get_pixel(30 + 10, 20 + 10), PLACEHOLDER);
+
+        let options = RenderOptions {
+            include_pool: true,
+            ..options
+        };
+        let img = render(&tierlist, &options).unwrap();
+        assert_eq!(img.height(), (40 + GAP) + (20 + GAP) + (20 + GAP));
+    }
+
+    #[test]
+    fn render_invalid_options() {
+        let tierlist = TierList::empty();
+        let options = RenderOptions {
+            columns: 0,
+            ..Default::default()
+        };
+        assert!(render(&tierlist, &options).is_err());
+    }
+
+    #[test]
+    fn render_too_large() {
+        let mut tierlist = TierList::empty();
+        for (thumb_size, columns) in [(2048, 8), (100, 65)] {
+            let options = RenderOptions {
+                thumb_size,
+                columns,
+                ..Default::default()
+            };
+            assert!(render(&tierlist, &options).is_err());
+        }
+
+        // 1 列に大きなマスを並べると画素が多すぎる
+        tierlist.add_tier("S");
+        tierlist.tiers[0].items = (1..=100_000).collect();
+        let options = RenderOptions {
+            thumb_size: MAX_THUMB_SIZE,
+            columns: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            render(&tierlist, &options).unwrap_err(),
+            "Image would be too large"
+        );
+    }
+
+    #[test]
+    fn romanize_kana() {
+        assert_eq!(romanize("ドラゴンクエスト"), "doragonkuesuto");
+        assert_eq!(romanize("ファイナルファンタジー"), "fainarufantajii");
+        assert_eq!(romanize("チョコボ"), "chokobo");
+        assert_eq!(romanize("ポケットモンスター"), "pokettomonsutaa");
+        assert_eq!(romanize("ｶﾞﾝﾀﾞﾑ・ＳＥＥＤ"), "gandamu seed");
+        // 漢字はそのまま (フォントに無いので四角になる)
+        assert_eq!(romanize("神ゲー"), "神gee");
+    }
+
+    #[test]
+    fn icon_instead_of_kanji_title() {
+        let labeled = |title: &str, icon: Option<&str>| {
+            let mut tierlist = TierList::empty();
+            tierlist.add_tier(title);
+            tierlist.tiers[0].icon = icon.map(|i| i.to_owned());
+            render(&tierlist, &RenderOptions::default()).unwrap()
+        };
+        let plain = labeled("S", None);
+        assert_eq!(labeled("神", Some("S")), plain);
+        // 描けるタイトルはそのまま、描けないアイコンは使わない
+        assert_eq!(labeled("S", Some("A")), plain);
+        assert_ne!(labeled("神", Some("🔥")), plain);
+        assert_eq!(labeled("神", Some("🔥")), labeled("神", None));
+    }
+
+    #[test]
+    fn render_comparison_table() {
+        let item = |name: &str, diff: Option<f64>| ComparedItem {
+            name: name.to_owned(),
+            left: Place::Pool,
+            right: Place::Tier {
+                id: 1,
+                title: "S".to_owned(),
+            },
+            diff,
+        };
+        let comparison = Comparison {
+            left_title: "alice".to_owned(),
+            right_title: "bob".to_owned(),
+            agreement: Some(0.5),
+            both: vec![
+                item("a", Some(0.0)),
+                item("b", Some(-0.75)),
+                item("d", Some(0.25)),
+            ],
+            only_left: vec![],
+            only_right: vec![item("c", None)],
+        };
+        let img = render_comparison(&comparison).unwrap();
+        assert_eq!(img.width(), 360 + 120 + 120 + 80 + GAP * 3);
+        assert_eq!(img.height(), 5 * (COMPARE_ROW_H + GAP));
+        // 差の列の左上は差に応じた色
+        let diff_x = img.width() - 80;
+        let row_y = |row: u32| row * (COMPARE_ROW_H + GAP);
+        assert_eq!(*img.get_pixel(diff_x, row_y(1)), AGREE);
+        assert_eq!(*img.get_pixel(diff_x, row_y(2)), FAR);
+        assert_eq!(*img.get_pixel(diff_x, row_y(3)), NEAR);
+        assert_eq!(*img.get_pixel(diff_x, row_y(4)), PLACEHOLDER);
+        // 一致度と差の % も四角にならない
+        assert_ne!(glyph('%'), glyph('\u{25a1}'));
+
+        // 行が多すぎると画素が多すぎる
+        let comparison = Comparison {
+            only_left: vec![item("e", None); 100_000],
+            ..comparison
+        };
+        assert_eq!(
+            render_comparison(&comparison).unwrap_err(),
+            "Image would be too large"
+        );
+    }
+
+    #[test]
+    fn wrap_words() {
+        assert_eq!(wrap("Must watch", 10), vec!["Must watch"]);
+        assert_eq!(wrap("Must watch", 6), vec!["Must", "watch"]);
+        assert_eq!(wrap("Skip", 2), vec!["Sk", "ip"]);
+    }
+
+    #[tokio::test]
+    async fn export_png() {
+        let dir = TempDir::new("render_test").unwrap();
+        let path = dir.path().join("list.png");
+        save_png(
+            tierlist_with_thumb("missing.png"),
+            path.to_string_lossy().to_string(),
+            RenderOptions::default(),
+        )
+        .await
+        .unwrap();
+        let img = image::open(&path).unwrap();
+        assert_eq!(img.width(), 150 + 8 * 100);
+    }
+}
@@ -1,121 +1,467 @@
+use std::{path::Path, time::Duration};
+
 use reqwest;
-use scraper;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
 use tempdir::TempDir;
-use tokio::fs;
+use url::Url;
+
+use crate::thumbnail::{self, ThumbnailOptions};
+
+// ページから取り出した情報
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metadata {
+    pub title: String,
+    pub image_url: Option<String>,
+    pub description: Option<String>,
+}
+
+pub trait Scraper: Send + Sync {
+    // host のページをこの Scraper で扱えるか
+    fn matches(&self, host: &str) -> bool;
+
+    fn parse(&self, url: &Url, document: &Html) -> Result<Metadata, String>;
+}
+
+// selector に最初にマッチした要素の attr を返す
+fn select_attr(document: &Html, selector: &str, attr: &str) -> Option<String> {
+    let selector = Selector::parse(selector).unwrap();
+    document
+        .select(&selector)
+        .filter_map(|e| e.value().attr(attr))
+        .map(|v| v.trim().to_owned())
+        .find(|v| !v.is_empty())
+}
+
+fn select_text(document: &Html, selector: &str) -> Option<String> {
+    let selector = Selector::parse(selector).unwrap();
+    document
+        .select(&selector)
+        .map(|e| e.text().collect::<Vec<&str>>().join("").trim().to_owned())
+        .find(|v| !v.is_empty())
+}
+
+// <meta property="..."> と <meta name="..."> のどちらで書かれていても読む
+fn select_meta(document: &Html, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| {
+        let selector = format!(r#"meta[property="{}"], meta[name="{}"]"#, key, key);
+        select_attr(document, &selector, "content")
+    })
+}
+
+pub struct AmazonScraper;
+
+impl Scraper for AmazonScraper {
+    fn matches(&self, host: &str) -> bool {
+        host.split('.').any(|label| label == "amazon") || host == "amzn.to" || host == "amzn.asia"
+    }
+
+    fn parse(&self, _url: &Url, document: &Html) -> Result<Metadata, String> {
+        let img_url = select_attr(document, "img#ebooksImgBlkFront, img#imgBlkFront", "src")
+            .ok_or("Image not found")?;
+        let title = select_text(document, "#productTitle").ok_or("Product title not found")?;
+        let description = select_meta(document, &["description"]);
+
+        Ok(Metadata {
+            title,
+            image_url: Some(img_url),
+            description,
+        })
+    }
+}
+
+// OpenGraph / Twitter Card の meta タグから情報を読む汎用の Scraper
+pub struct OpenGraphScraper;
+
+impl Scraper for OpenGraphScraper {
+    fn matches(&self, _host: &str) -> bool {
+        true
+    }
+
+    fn parse(&self, url: &Url, document: &Html) -> Result<Metadata, String> {
+        let title = select_meta(document, &["og:title", "twitter:title"])
+            .or_else(|| select_text(document, "title"))
+            .ok_or("Page title not found")?;
+        let image_url = select_meta(
+            document,
+            &[
+                "og:image",
+                "og:image:url",
+                "og:image:secure_url",
+                "twitter:image",
+                "twitter:image:src",
+            ],
+        )
+        .and_then(|img| url.join(&img).ok())
+        .map(|img| img.to_string());
+        let description = select_meta(
+            document,
+            &["og:description", "twitter:description", "description"],
+        );
 
-// (img url, product title) を返す
-async fn scrape_amazon(url: &str) -> Result<(String, String), String> {
-    let img_selector = scraper::Selector::parse("img#ebooksImgBlkFront, img#imgBlkFront").unwrap();
-    let title_selector = scraper::Selector::parse("#productTitle").unwrap();
-    let body = reqwest::ClientBuilder::new()
+        Ok(Metadata {
+            title,
+            image_url,
+            description,
+        })
+    }
+}
+
+// URL のホストから使う Scraper を選ぶ
+// どれにも当てはまらないか、当てはまった Scraper で読めなかったときは fallback を使う
+pub struct ScraperRegistry {
+    scrapers: Vec<Box<dyn Scraper>>,
+    fallback: Box<dyn Scraper>,
+}
+
+impl ScraperRegistry {
+    pub fn new(fallback: Box<dyn Scraper>) -> Self {
+        ScraperRegistry {
+            scrapers: vec![],
+            fallback,
+        }
+    }
+
+    pub fn register(&mut self, scraper: Box<dyn Scraper>) {
+        self.scrapers.push(scraper);
+    }
+
+    pub fn find(&self, url: &Url) -> Option<&dyn Scraper> {
+        let host = url.host_str()?;
+        self.scrapers
+            .iter()
+            .find(|s| s.matches(host))
+            .map(|s| s.as_ref())
+    }
+
+    pub fn parse(&self, url: &Url, document: &Html) -> Result<Metadata, String> {
+        match self.find(url) {
+            Some(scraper) => scraper
+                .parse(url, document)
+                .or_else(|e| self.fallback.parse(url, document).map_err(|_| e)),
+            None => self.fallback.parse(url, document),
+        }
+    }
+}
+
+impl Default for ScraperRegistry {
+    fn default() -> Self {
+        let mut registry = ScraperRegistry::new(Box::new(OpenGraphScraper));
+        registry.register(Box::new(AmazonScraper));
+        registry
+    }
+}
+
+// 応答しないサーバーで取り込みなどが止まったままにならないようにする
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+// ダウンロードする画像の大きさの上限
+const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+fn client() -> Result<reqwest::Client, String> {
+    reqwest::ClientBuilder::new()
         .gzip(true)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(FETCH_TIMEOUT)
         .build()
-        .unwrap()
-        .get(url)
+        .map_err(|e| e.to_string())
+}
+
+async fn fetch_page(url: &Url) -> Result<String, String> {
+    client()?
+        .get(url.clone())
         .send()
         .await
+        .and_then(|res| res.error_for_status())
         .map_err(|e| e.to_string())?
         .text()
         .await
-        .map_err(|e| e.to_string())?;
-
-    let document = scraper::Html::parse_document(&body);
-
-    let img = document
-        .select(&img_selector)
-        .next()
-        .ok_or("Image not found")?;
-    let img_url = img
-        .value()
-        .attr("src")
-        .ok_or("Could not retrieve image URL".to_owned())
-        .map(|url| url.to_owned())?;
+        .map_err(|e| e.to_string())
+}
 
-    let title = document
-        .select(&title_selector)
-        .next()
-        .ok_or("Product title not found")?;
-    let product_title = title.text().collect::<Vec<&str>>().join("");
+pub async fn scrape(registry: &ScraperRegistry, url: &str) -> Result<Metadata, String> {
+    let url = Url::parse(url).map_err(|e| e.to_string())?;
+    let body = fetch_page(&url).await?;
+    let document = Html::parse_document(&body);
+    registry.parse(&url, &document)
+}
 
-    Ok((img_url, product_title.trim().to_owned()))
+// MAX_IMAGE_BYTES より大きければ、全部読む前にやめる
+pub async fn fetch_bytes(url: &str) -> Result<Vec<u8>, String> {
+    let too_large = || format!("Image is larger than {} MB", MAX_IMAGE_BYTES / 1024 / 1024);
+    let mut res = client()?
+        .get(url)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(|e| e.to_string())?;
+    if res.content_length().unwrap_or(0) > MAX_IMAGE_BYTES as u64 {
+        return Err(too_large());
+    }
+    let mut bytes = vec![];
+    while let Some(chunk) = res.chunk().await.map_err(|e| e.to_string())? {
+        if bytes.len() + chunk.len() > MAX_IMAGE_BYTES {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
 }
 
-// url の画像を img_dir 以下にダウンロードしてパスを返す
-async fn download_img(img_dir: &TempDir, url: &str) -> Result<String, String> {
-    let img = reqwest::get(url).await.map_err(|e| e.to_string())?;
+// url の画像を img_dir 以下にダウンロードし、サムネイルとして正規化してパスを返す
+async fn download_img(
+    img_dir: &TempDir,
+    url: &str,
+    options: &ThumbnailOptions,
+) -> Result<String, String> {
+    let bytes = fetch_bytes(url).await?;
 
-    let img_url = url::Url::parse(&url).map_err(|e| e.to_string())?;
+    let img_url = Url::parse(url).map_err(|e| e.to_string())?;
     let mut path = img_url
         .path_segments()
         .ok_or("Invalid image URL".to_owned())?;
     let filename = path.next_back().ok_or("Filename not found")?;
-    let filepath = img_dir.path().join(filename);
-
-    fs::write(&filepath, img.bytes().await.map_err(|e| e.to_string())?)
-        .await
-        .map_err(|e| e.to_string())?;
+    let name = Path::new(filename)
+        .file_stem()
+        .ok_or("Filename not found")?
+        .to_string_lossy()
+        .to_string();
 
-    Ok(filepath.to_string_lossy().to_string())
+    thumbnail::save_normalized(bytes, img_dir.path(), &name, options).await
 }
 
 pub mod commands {
-    use tauri::State;
+    use tauri::{async_runtime::Mutex, State};
 
     use super::*;
 
+    // フロントエンドに返す、アイテムの元になる情報
+    #[derive(Clone, Debug, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ScrapedItem {
+        pub name: String,
+        pub url: String,
+        pub thumb: Option<String>,
+        pub description: Option<String>,
+    }
+
     #[tauri::command]
-    pub async fn scrape_amazon(
+    pub async fn scrape_item(
         img_dir: State<'_, TempDir>,
-        amazon_url: &str,
-    ) -> Result<(String, String), String> {
-        let (img_url, title) = super::scrape_amazon(amazon_url).await?;
-        let img_path = download_img(&*img_dir, &img_url).await?;
-        Ok((img_path, title))
+        registry: State<'_, ScraperRegistry>,
+        thumb_options: State<'_, Mutex<ThumbnailOptions>>,
+        url: &str,
+    ) -> Result<ScrapedItem, String> {
+        let metadata = scrape(&registry, url).await?;
+        let thumb_options = thumb_options.lock().await.clone();
+        let thumb = match &metadata.image_url {
+            Some(img_url) => Some(download_img(&img_dir, img_url, &thumb_options).await?),
+            None => None,
+        };
+        Ok(ScrapedItem {
+            name: metadata.title,
+            url: url.to_owned(),
+            thumb,
+            description: metadata.description,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, ImageOutputFormat, RgbImage};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
     use super::*;
 
-    #[tokio::test]
-    async fn long_kindle_url_ok() {
-        let url = "https://www.amazon.co.jp/gp/product/B07N3NRSKF?pf_rd_m=AN1VRQENFRJN5&storeType=ebooks&pageType=manga-store&pf_rd_p=2e113e83-cc39-4b92-bd2a-b0ee4c341c16&pf_rd_r=M8X29G7H4BJPVSCW4HP7&pf_rd_s=desktop-center-3&pf_rd_t=&ref_=msw_m_2293143051_rwt_0000_vol_dc3_gr_2293143051_2&pf_rd_i=store-2293143051";
-        let (image_url, title) = scrape_amazon(url).await.unwrap();
-        assert!(image_url.starts_with("https://m.media-amazon.com/images/I/51gWu2+kUvL"));
-        assert!(title.starts_with("ぼっち・ざ・ろっく！"));
+    const AMAZON_KINDLE_HTML: &str = include_str!("../fixtures/scraping/amazon_kindle.html");
+    const AMAZON_BOOK_HTML: &str = include_str!("../fixtures/scraping/amazon_book.html");
+    const AMAZON_UNKNOWN_LAYOUT_HTML: &str =
+        include_str!("../fixtures/scraping/amazon_unknown_layout.html");
+    const OPEN_GRAPH_HTML: &str = include_str!("../fixtures/scraping/open_graph.html");
+    const NO_METADATA_HTML: &str = include_str!("../fixtures/scraping/no_metadata.html");
+
+    const LONG_KINDLE_URL: &str = "https://www.amazon.co.jp/gp/product/B07N3NRSKF?pf_rd_m=AN1VRQENFRJN5&storeType=ebooks&pageType=manga-store&pf_rd_p=2e113e83-cc39-4b92-bd2a-b0ee4c341c16&pf_rd_r=M8X29G7H4BJPVSCW4HP7&pf_rd_s=desktop-center-3&pf_rd_t=&ref_=msw_m_2293143051_rwt_0000_vol_dc3_gr_2293143051_2&pf_rd_i=store-2293143051";
+    const SHORT_KINDLE_URL: &str = "https://www.amazon.co.jp/gp/product/B07N3NRSKF";
+    const LONG_BOOK_URL: &str = "https://www.amazon.co.jp/%E3%81%BC%E3%81%A3%E3%81%A1%E3%83%BB%E3%81%96%E3%83%BB%E3%82%8D%E3%81%A3%E3%81%8F%EF%BC%81-1-%E3%81%BE%E3%82%93%E3%81%8C%E3%82%BF%E3%82%A4%E3%83%A0KR%E3%82%B3%E3%83%9F%E3%83%83%E3%82%AF%E3%82%B9-%E3%81%AF%E3%81%BE%E3%81%98%E3%81%82%E3%81%8D/dp/4832270729/ref=tmm_other_meta_binding_swatch_0?_encoding=UTF8&qid=&sr=";
+    const SHORT_BOOK_URL: &str = "https://www.amazon.co.jp/dp/4832270729/";
+
+    fn parse_fixture(url: &str, html: &str) -> Result<Metadata, String> {
+        let url = Url::parse(url).unwrap();
+        ScraperRegistry::default().parse(&url, &Html::parse_document(html))
+    }
+
+    fn amazon_ok(metadata: &Metadata, image_prefix: &str) {
+        assert!(metadata
+            .image_url
+            .as_ref()
+            .unwrap()
+            .starts_with(image_prefix));
+        assert!(metadata.title.starts_with("ぼっち・ざ・ろっく！"));
+    }
+
+    // 1 回だけ body を返す HTTP サーバーを立てて、その URL を返す
+    async fn serve_once(status: &'static str, path: &str, body: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut req = vec![];
+            let mut buf = [0u8; 1024];
+            while !req.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                req.extend_from_slice(&buf[..n]);
+            }
+            let header = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            );
+            // 大きすぎる body は途中で切られることがある
+            let _ = socket.write_all(header.as_bytes()).await;
+            let _ = socket.write_all(&body).await;
+        });
+        format!("http://{}{}", addr, path)
+    }
+
+    #[test]
+    fn kindle_page_ok() {
+        for url in [LONG_KINDLE_URL, SHORT_KINDLE_URL] {
+            let metadata = parse_fixture(url, AMAZON_KINDLE_HTML).unwrap();
+            amazon_ok(&metadata, "https://m.media-amazon.com/images/I/51gWu2+kUvL");
+        }
+    }
+
+    #[test]
+    fn book_page_ok() {
+        for url in [LONG_BOOK_URL, SHORT_BOOK_URL] {
+            let metadata = parse_fixture(url, AMAZON_BOOK_HTML).unwrap();
+            amazon_ok(&metadata, "https://m.media-amazon.com/images/I/51WSIfaeliL");
+            assert!(metadata.description.unwrap().starts_with("Amazon"));
+        }
+    }
+
+    #[test]
+    fn amazon_unknown_layout_falls_back() {
+        let metadata = parse_fixture(SHORT_BOOK_URL, AMAZON_UNKNOWN_LAYOUT_HTML).unwrap();
+        assert_eq!(metadata.title, "結束バンド");
+        assert_eq!(
+            metadata.image_url.unwrap(),
+            "https://m.media-amazon.com/images/I/81DYWG1pKxL._SL1500_.jpg"
+        );
+    }
+
+    #[test]
+    fn no_metadata_err() {
+        assert!(parse_fixture("https://example.com/", NO_METADATA_HTML).is_err());
+        assert!(parse_fixture(SHORT_BOOK_URL, NO_METADATA_HTML).is_err());
+    }
+
+    #[test]
+    fn registry_chooses_by_host() {
+        let registry = ScraperRegistry::default();
+        for url in [
+            LONG_KINDLE_URL,
+            SHORT_KINDLE_URL,
+            LONG_BOOK_URL,
+            SHORT_BOOK_URL,
+            "https://amzn.asia/d/abcdefg",
+        ] {
+            assert!(registry.find(&Url::parse(url).unwrap()).is_some());
+        }
+        let other = Url::parse("https://example.com/anime/1").unwrap();
+        assert!(registry.find(&other).is_none());
+    }
+
+    #[test]
+    fn open_graph_parse() {
+        let metadata = parse_fixture("https://bocchi.rocks/", OPEN_GRAPH_HTML).unwrap();
+        assert_eq!(
+            metadata,
+            Metadata {
+                title: "TVアニメ「ぼっち・ざ・ろっく！」".to_owned(),
+                image_url: Some("https://bocchi.rocks/assets/img/ogp.png".to_owned()),
+                description: Some(
+                    "“ぼっちちゃん”こと後藤ひとりは、ギターを愛する孤独な少女。".to_owned()
+                ),
+            }
+        );
     }
 
     #[tokio::test]
-    async fn short_kindle_url_ok() {
-        let url = "https://www.amazon.co.jp/gp/product/B07N3NRSKF";
-        let (image_url, title) = scrape_amazon(url).await.unwrap();
-        assert!(image_url.starts_with("https://m.media-amazon.com/images/I/51gWu2+kUvL"));
-        assert!(title.starts_with("ぼっち・ざ・ろっく！"));
+    async fn scrape_local_page() {
+        let url = serve_once("200 OK", "/anime/1", OPEN_GRAPH_HTML.as_bytes().to_vec()).await;
+        let metadata = scrape(&ScraperRegistry::default(), &url).await.unwrap();
+        assert_eq!(metadata.title, "TVアニメ「ぼっち・ざ・ろっく！」");
+        assert!(metadata.image_url.unwrap().ends_with("/assets/img/ogp.png"));
     }
 
     #[tokio::test]
-    async fn long_book_url_ok() {
-        let url= "https://www.amazon.co.jp/%E3%81%BC%E3%81%A3%E3%81%A1%E3%83%BB%E3%81%96%E3%83%BB%E3%82%8D%E3%81%A3%E3%81%8F%EF%BC%81-1-%E3%81%BE%E3%82%93%E3%81%8C%E3%82%BF%E3%82%A4%E3%83%A0KR%E3%82%B3%E3%83%9F%E3%83%83%E3%82%AF%E3%82%B9-%E3%81%AF%E3%81%BE%E3%81%98%E3%81%82%E3%81%8D/dp/4832270729/ref=tmm_other_meta_binding_swatch_0?_encoding=UTF8&qid=&sr=";
-        let (image_url, title) = scrape_amazon(url).await.unwrap();
-        assert!(image_url.starts_with("https://m.media-amazon.com/images/I/51WSIfaeliL"));
-        assert!(title.starts_with("ぼっち・ざ・ろっく！"));
+    async fn dl_img() {
+        let mut png = vec![];
+        DynamicImage::ImageRgb8(RgbImage::new(1000, 500))
+            .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .unwrap();
+        let url = serve_once("200 OK", "/images/cover.png", png).await;
+        let dir = TempDir::new("test").unwrap();
+        let options = ThumbnailOptions::default();
+        let path = download_img(&dir, &url, &options).await.unwrap();
+        let file_name = Path::new(&path).file_name().unwrap().to_string_lossy();
+        assert!(file_name.starts_with("cover_") && file_name.ends_with(".jpg"));
+
+        let img = image::open(&path).unwrap();
+        assert_eq!(img.width(), options.max_size);
     }
 
     #[tokio::test]
-    async fn short_book_url_ok() {
-        let url = "https://www.amazon.co.jp/dp/4832270729/";
-        let (image_url, title) = scrape_amazon(url).await.unwrap();
-        assert!(image_url.starts_with("https://m.media-amazon.com/images/I/51WSIfaeliL"));
-        assert!(title.starts_with("ぼっち・ざ・ろっく！"));
+    async fn dl_img_not_image() {
+        let url = serve_once("200 OK", "/images/cover.jpg", b"<html></html>".to_vec()).await;
+        let dir = TempDir::new("test").unwrap();
+        let options = ThumbnailOptions::default();
+        assert!(download_img(&dir, &url, &options).await.is_err());
     }
 
     #[tokio::test]
-    async fn dl_img() {
-        let url = "https://www.amazon.co.jp/gp/product/B07N3NRSKF";
-        let (image_url, _) = scrape_amazon(url).await.unwrap();
+    async fn dl_img_not_found() {
+        let url = serve_once("404 Not Found", "/images/cover.jpg", vec![]).await;
         let dir = TempDir::new("test").unwrap();
-        let path = download_img(&dir, &image_url).await.unwrap();
-        assert!(fs::metadata(&path).await.is_ok())
+        let options = ThumbnailOptions::default();
+        assert!(download_img(&dir, &url, &options).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn dl_img_too_large() {
+        let url = serve_once("200 OK", "/images/huge.png", vec![0; MAX_IMAGE_BYTES + 1]).await;
+        let err = fetch_bytes(&url).await.unwrap_err();
+        assert!(err.contains("larger than"), "{}", err);
+    }
+
+    // 実際の Amazon のページを読む (ネットワークが必要なので普段は実行しない)
+    #[tokio::test]
+    #[ignore]
+    async fn live_amazon_ok() {
+        let registry = ScraperRegistry::default();
+        for (url, image_prefix) in [
+            (
+                SHORT_KINDLE_URL,
+                "https://m.media-amazon.com/images/I/51gWu2+kUvL",
+            ),
+            (
+                SHORT_BOOK_URL,
+                "https://m.media-amazon.com/images/I/51WSIfaeliL",
+            ),
+        ] {
+            let metadata = scrape(&registry, url).await.unwrap();
+            amazon_ok(&metadata, image_prefix);
+        }
     }
 }
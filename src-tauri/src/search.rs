@@ -0,0 +1,184 @@
+use serde::Serialize;
+
+use crate::tierlist::{ItemId, TierList};
+
+// メモは最初に合った所を含むこの文字数だけ返す
+const SNIPPET_LEN: usize = 40;
+const ELLIPSIS: char = '…';
+
+// 表示する文字列の一部。matched なら検索語に合った部分
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Segment {
+    pub text: String,
+    pub matched: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub id: ItemId,
+    pub name: Vec<Segment>,
+    pub memo: Vec<Segment>,
+}
+
+// FTS5 の trigram と同じく大文字小文字を区別しない
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn terms(query: &str) -> Vec<Vec<char>> {
+    query
+        .split_whitespace()
+        .map(|term| term.chars().map(fold).collect())
+        .collect()
+}
+
+// 文字ごとに、どれかの語に合った部分かどうか
+fn mark(text: &[char], terms: &[Vec<char>]) -> Vec<bool> {
+    let mut matched = vec![false; text.len()];
+    for term in terms {
+        if term.is_empty() || term.len() > text.len() {
+            continue;
+        }
+        for start in 0..=text.len() - term.len() {
+            if term.iter().zip(&text[start..]).all(|(&t, &c)| t == fold(c)) {
+                for m in matched[start..start + term.len()].iter_mut() {
+                    *m = true;
+                }
+            }
+        }
+    }
+    matched
+}
+
+fn segments(chars: impl Iterator<Item = (char, bool)>) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = vec![];
+    for (c, matched) in chars {
+        match segments.last_mut() {
+            Some(segment) if segment.matched == matched => segment.text.push(c),
+            _ => segments.push(Segment {
+                text: c.to_string(),
+                matched,
+            }),
+        }
+    }
+    segments
+}
+
+pub fn highlight(text: &str, terms: &[Vec<char>]) -> Vec<Segment> {
+    let chars: Vec<char> = text.chars().collect();
+    let matched = mark(&chars, terms);
+    segments(chars.into_iter().zip(matched))
+}
+
+// 最初に合った所の少し前から len 文字を切り出し、省いた側に … を付ける
+pub fn snippet(text: &str, terms: &[Vec<char>], len: usize) -> Vec<Segment> {
+    let chars: Vec<char> = text.chars().collect();
+    let matched = mark(&chars, terms);
+    if chars.len() <= len {
+        return segments(chars.into_iter().zip(matched));
+    }
+    let first = matched.iter().position(|&m| m).unwrap_or(0);
+    let start = first.saturating_sub(len / 4).min(chars.len() - len);
+    let end = start + len;
+    let head = if start > 0 {
+        Some((ELLIPSIS, false))
+    } else {
+        None
+    };
+    let tail = if end < chars.len() {
+        Some((ELLIPSIS, false))
+    } else {
+        None
+    };
+    let body = chars[start..end]
+        .iter()
+        .copied()
+        .zip(matched[start..end].iter().copied());
+    segments(head.into_iter().chain(body).chain(tail))
+}
+
+// ids の順に、tierlist にあるアイテムだけを強調を付けて返す
+pub fn hits(tierlist: &TierList, ids: &[ItemId], query: &str) -> Vec<SearchHit> {
+    let terms = terms(query);
+    ids.iter()
+        .filter_map(|&id| tierlist.item(id))
+        .map(|item| SearchHit {
+            id: item.id,
+            name: highlight(&item.name, &terms),
+            memo: snippet(&item.memo, &terms, SNIPPET_LEN),
+        })
+        .collect()
+}
+
+pub mod commands {
+    use sqlx::SqlitePool;
+    use tauri::{async_runtime::Mutex, State};
+
+    use super::*;
+    use crate::{db, tierlist::ListId};
+
+    // DB に保存された内容から探すので、まだ保存していない変更は反映されない
+    #[tauri::command]
+    pub async fn search_items(
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        tierlist: State<'_, Mutex<TierList>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        query: String,
+    ) -> Result<Vec<SearchHit>, String> {
+        let list_id = cur_list
+            .lock()
+            .await
+            .ok_or("Tier list has not been saved")?;
+        let pool = pool.lock().await;
+        let pool = pool.as_ref().ok_or("DB not opened")?;
+        let ids = db::search_items(pool, list_id, &query)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(hits(&*tierlist.lock().await, &ids, &query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seg(text: &str, matched: bool) -> Segment {
+        Segment {
+            text: text.to_owned(),
+            matched,
+        }
+    }
+
+    #[test]
+    fn highlight_terms() {
+        let terms = terms("cat 猫");
+        assert_eq!(
+            highlight("Cats and 子猫", &terms),
+            vec![seg("Cat", true), seg("s and 子", false), seg("猫", true)]
+        );
+        assert_eq!(highlight("dog", &terms), vec![seg("dog", false)]);
+        assert!(highlight("", &terms).is_empty());
+    }
+
+    #[test]
+    fn snippet_around_first_match() {
+        let terms = terms("target");
+        let text = format!("{}target{}", "a".repeat(30), "b".repeat(30));
+        assert_eq!(
+            snippet(&text, &terms, 20),
+            vec![
+                seg(&format!("…{}", "a".repeat(5)), false),
+                seg("target", true),
+                seg(&format!("{}…", "b".repeat(9)), false),
+            ]
+        );
+        // 合った所がなければ先頭から
+        assert_eq!(
+            snippet(&"x".repeat(30), &terms, 20),
+            vec![seg(&format!("{}…", "x".repeat(20)), false)]
+        );
+        assert_eq!(snippet("short", &terms, 20), vec![seg("short", false)]);
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    placement::Place,
+    tierlist::{ItemId, TierList, Timestamp},
+};
+
+pub type SnapshotId = i64;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotInfo {
+    pub id: SnapshotId,
+    pub name: String,
+    pub created_at: Timestamp,
+}
+
+// 比べる tierlist。Current は今編集している (保存していない変更も含む) もの
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Version {
+    Current,
+    Snapshot { id: SnapshotId },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemDiff {
+    pub item: ItemId,
+    pub name: String,
+    pub from: Place,
+    pub to: Place,
+}
+
+// 2 つの版の違い。どれも id 順
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListDiff {
+    pub added: Vec<ItemDiff>,
+    pub removed: Vec<ItemDiff>,
+    pub promoted: Vec<ItemDiff>,
+    pub demoted: Vec<ItemDiff>,
+}
+
+// アイテムの場所と、上から何番目の tier か。プールはどの tier よりも下とみなす
+pub(crate) fn places(tierlist: &TierList) -> HashMap<ItemId, (Place, usize)> {
+    let mut places = HashMap::new();
+    for (rank, tier) in tierlist.tiers.iter().enumerate() {
+        for &id in tier.items.iter() {
+            let place = Place::Tier {
+                id: tier.id,
+                title: tier.title.clone(),
+            };
+            places.insert(id, (place, rank));
+        }
+    }
+    for &id in tierlist.items_pool.iter() {
+        places.insert(id, (Place::Pool, tierlist.tiers.len()));
+    }
+    places
+}
+
+// 同じ tier (id が同じ) のままのアイテムは、tier の順番が変わっていても含めない
+pub fn diff(old: &TierList, new: &TierList) -> ListDiff {
+    let (old_places, new_places) = (places(old), places(new));
+    let mut diff = ListDiff::default();
+    for item in new.items.iter() {
+        let to = new_places.get(&item.id);
+        let from = old_places.get(&item.id);
+        let (list, from, to) = match (from, to) {
+            (None, Some((to, _))) => (&mut diff.added, Place::Absent, to),
+            (Some((from, _)), Some((to, _))) if from.same(to) => continue,
+            (Some((from, old_rank)), Some((to, new_rank))) if new_rank < old_rank => {
+                (&mut diff.promoted, from.clone(), to)
+            }
+            (Some((from, _)), Some((to, _))) => (&mut diff.demoted, from.clone(), to),
+            (_, None) => continue,
+        };
+        list.push(ItemDiff {
+            item: item.id,
+            name: item.name.clone(),
+            from,
+            to: to.clone(),
+        });
+    }
+    for item in old.items.iter() {
+        if new.item(item.id).is_some() {
+            continue;
+        }
+        if let Some((from, _)) = old_places.get(&item.id) {
+            diff.removed.push(ItemDiff {
+                item: item.id,
+                name: item.name.clone(),
+                from: from.clone(),
+                to: Place::Absent,
+            });
+        }
+    }
+    diff
+}
+
+// 残すときにサムネイルのパス (一時ディレクトリの中) は捨てる。画像は DB に別に残す
+pub fn freeze(tierlist: &TierList) -> TierList {
+    let mut frozen = tierlist.clone();
+    for item in frozen.items.iter_mut() {
+        item.thumb = None;
+    }
+    frozen
+}
+
+// 戻すときは、今もあるアイテムのサムネイルをそのまま使う。
+// 消されたアイテムは残しておいたサムネイル (frozen を読んだときに展開したもの) を使う
+pub fn thaw(mut frozen: TierList, current: &TierList) -> TierList {
+    for item in frozen.items.iter_mut() {
+        if let Some(cur) = current.item(item.id) {
+            item.thumb = cur.thumb.clone();
+        }
+    }
+    frozen.repair();
+    frozen
+}
+
+pub mod commands {
+    use sqlx::SqlitePool;
+    use tauri::{async_runtime::Mutex, State};
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::{
+        db,
+        history::{History, Op},
+        tierlist::ListId,
+    };
+
+    async fn list_id_and_pool(
+        pool: &Mutex<Option<SqlitePool>>,
+        cur_list: &Mutex<Option<ListId>>,
+    ) -> Result<(ListId, SqlitePool), String> {
+        let list_id = cur_list
+            .lock()
+            .await
+            .ok_or("Tier list has not been saved")?;
+        let pool = pool.lock().await.clone().ok_or("DB not opened")?;
+        Ok((list_id, pool))
+    }
+
+    #[tauri::command]
+    pub async fn list_snapshots(
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+    ) -> Result<Vec<SnapshotInfo>, String> {
+        let (list_id, pool) = list_id_and_pool(&pool, &cur_list).await?;
+        db::list_snapshots(&pool, list_id)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    // 今編集している状態を残す (保存していない変更も含む)
+    #[tauri::command]
+    pub async fn create_snapshot(
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        tierlist: State<'_, Mutex<TierList>>,
+        name: String,
+    ) -> Result<Vec<SnapshotInfo>, String> {
+        if name.trim().is_empty() {
+            return Err("Snapshot name is empty".to_owned());
+        }
+        let (list_id, pool) = list_id_and_pool(&pool, &cur_list).await?;
+        let tierlist = tierlist.lock().await.clone();
+        db::create_snapshot(&pool, list_id, name.trim(), &tierlist)
+            .await
+            .map_err(|e| e.to_string())?;
+        db::list_snapshots(&pool, list_id)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    // 元に戻せるように履歴に記録する
+    #[tauri::command]
+    pub async fn restore_snapshot(
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        img_dir: State<'_, TempDir>,
+        id: SnapshotId,
+    ) -> Result<TierList, String> {
+        let (list_id, pool) = list_id_and_pool(&pool, &cur_list).await?;
+        let frozen = db::read_snapshot(&pool, list_id, id, Some(img_dir.path()))
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut tierlist = tierlist.lock().await;
+        let restored = thaw(frozen, &tierlist);
+        history
+            .lock()
+            .await
+            .apply(&mut tierlist, Op::Replace(Box::new(restored)))?;
+        Ok(tierlist.clone())
+    }
+
+    #[tauri::command]
+    pub async fn delete_snapshot(
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        id: SnapshotId,
+    ) -> Result<Vec<SnapshotInfo>, String> {
+        let (list_id, pool) = list_id_and_pool(&pool, &cur_list).await?;
+        db::delete_snapshot(&pool, list_id, id)
+            .await
+            .map_err(|e| e.to_string())?;
+        db::list_snapshots(&pool, list_id)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    // from から to への変化
+    #[tauri::command]
+    pub async fn diff_versions(
+        pool: State<'_, Mutex<Option<SqlitePool>>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        tierlist: State<'_, Mutex<TierList>>,
+        from: Version,
+        to: Version,
+    ) -> Result<ListDiff, String> {
+        let (list_id, pool) = list_id_and_pool(&pool, &cur_list).await?;
+        let mut lists = vec![];
+        for version in [from, to] {
+            lists.push(match version {
+                Version::Current => tierlist.lock().await.clone(),
+                // 差分にはサムネイルは要らないので展開しない
+                Version::Snapshot { id } => db::read_snapshot(&pool, list_id, id, None)
+                    .await
+                    .map_err(|e| e.to_string())?,
+            });
+        }
+        Ok(diff(&lists[0], &lists[1]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tierlist::ItemData;
+
+    fn ids(diffs: &[ItemDiff]) -> Vec<ItemId> {
+        diffs.iter().map(|d| d.item).collect()
+    }
+
+    #[test]
+    fn diff_between_versions() {
+        let mut old = TierList::empty();
+        let s = old.add_tier("S");
+        let a = old.add_tier("A");
+        let b = old.add_tier("B");
+        for name in ["1", "2", "3", "4", "5", "6"] {
+            old.add_item(ItemData {
+                name: name.to_owned(),
+                url: "".to_owned(),
+                thumb: Some(format!("/tmp/{}.png", name)),
+                memo: "".to_owned(),
+            });
+        }
+        old.move_item(1, Some(a), 0).unwrap();
+        old.move_item(2, Some(a), 1).unwrap();
+        old.move_item(3, Some(b), 0).unwrap();
+        old.move_item(4, Some(s), 0).unwrap();
+        old.move_item(5, Some(b), 1).unwrap();
+
+        let mut new = old.clone();
+        new.move_item(1, Some(s), 0).unwrap();
+        new.move_item(2, None, 0).unwrap();
+        new.move_item(6, Some(b), 0).unwrap();
+        new.delete_item(4).unwrap();
+        new.add_item(ItemData {
+            name: "7".to_owned(),
+            url: "".to_owned(),
+            thumb: None,
+            memo: "".to_owned(),
+        });
+        // 並べ替えただけ、tier の名前や順番が変わっただけなら変化ではない
+        new.move_item(5, Some(b), 0).unwrap();
+        new.rename_tier(b, "B+").unwrap();
+        new.reorder_tiers(&[s, b, a]).unwrap();
+
+        let diff = diff(&old, &new);
+        assert_eq!(ids(&diff.added), vec![7]);
+        assert_eq!(diff.added[0].to, Place::Pool);
+        assert_eq!(ids(&diff.removed), vec![4]);
+        assert_eq!(ids(&diff.promoted), vec![1, 6]);
+        assert_eq!(ids(&diff.demoted), vec![2]);
+        assert_eq!(diff.demoted[0].to, Place::Pool);
+        assert_eq!(
+            diff.promoted[1].to,
+            Place::Tier {
+                id: b,
+                title: "B+".to_owned()
+            }
+        );
+
+        // サムネイルのパスは残さず、戻すときは今あるものを使う。
+        // 消されたアイテムは DB から展開したものを使う
+        let mut frozen = freeze(&old);
+        assert!(frozen.items.iter().all(|it| it.thumb.is_none()));
+        for item in frozen.items.iter_mut() {
+            item.thumb = Some(format!("/tmp/snapshot_{}.png", item.id));
+        }
+        let restored = thaw(frozen, &new);
+        assert_eq!(restored.item(1).unwrap().thumb, old.item(1).unwrap().thumb);
+        assert_eq!(
+            restored.item(4).unwrap().thumb.as_deref(),
+            Some("/tmp/snapshot_4.png")
+        );
+        assert_eq!(restored.tiers, old.tiers);
+    }
+}
@@ -0,0 +1,431 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fields::{FieldId, FieldType},
+    history::Op,
+    tierlist::{Item, ItemId, TierId, TierList},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SortKey {
+    Name,
+    // 値のないアイテムは順序に関わらず後ろに置く
+    Field { field: FieldId },
+    // 追加した時刻が同じか不明なら id (追加した順に振られる) の順
+    Added,
+    Updated,
+    Moved,
+    Random,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SortTarget {
+    Tier { id: TierId },
+    AllTiers,
+    Pool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortOptions {
+    pub key: SortKey,
+    #[serde(default)]
+    pub descending: bool,
+    // true なら同じ順位のアイテムは今の (手で並べた) 順のまま。false なら名前、id 順にする
+    #[serde(default)]
+    pub stable: bool,
+}
+
+// 半角カタカナ (U+FF66..=U+FF9D) に対応するひらがな
+const HALFWIDTH_KANA: &str = "をぁぃぅぇぉゃゅょっーあいうえおかきくけこさしすせそたちつてとなにぬねのはひふへほまみむめもやゆよらりるれろわん";
+
+// 名前を比べるときの形にする
+// カタカナはひらがなに、全角英数字は半角にして、大文字小文字は区別しない
+// 漢字は読みがわからないので文字コード順になる
+pub(crate) fn fold_name(name: &str) -> Vec<char> {
+    let mut folded: Vec<char> = vec![];
+    for c in name.chars() {
+        let c = match c as u32 {
+            0x30a1..=0x30f6 => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            0xff01..=0xff5e => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+            0xff66..=0xff9d => HALFWIDTH_KANA
+                .chars()
+                .nth((c as u32 - 0xff66) as usize)
+                .unwrap_or(c),
+            // 半角の濁点、半濁点は前の文字と合わせる
+            0xff9e | 0xff9f => {
+                let voiced = c == '\u{ff9e}';
+                match folded.last_mut() {
+                    Some(last) if voiced && *last == 'う' => *last = 'ゔ',
+                    Some(last)
+                        if voiced && "かきくけこさしすせそたちつてとはひふへほ".contains(*last) =>
+                    {
+                        *last = char::from_u32(*last as u32 + 1).unwrap_or(*last)
+                    }
+                    Some(last) if !voiced && "はひふへほ".contains(*last) => {
+                        *last = char::from_u32(*last as u32 + 2).unwrap_or(*last)
+                    }
+                    _ => folded.push(c),
+                }
+                continue;
+            }
+            _ => c,
+        };
+        folded.extend(c.to_lowercase());
+    }
+    folded
+}
+
+fn digits_end(chars: &[char], start: usize) -> usize {
+    chars[start..]
+        .iter()
+        .position(|c| !c.is_ascii_digit())
+        .map_or(chars.len(), |len| start + len)
+}
+
+// 名前の順。数字の並びは数として比べる ("item2" < "item10")
+pub fn compare_names(a: &str, b: &str) -> Ordering {
+    let (a, b) = (fold_name(a), fold_name(b));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            let (end_a, end_b) = (digits_end(&a, i), digits_end(&b, j));
+            let trim = |digits: &[char]| {
+                let zeros = digits.iter().take_while(|&&c| c == '0').count();
+                digits[zeros..].to_vec()
+            };
+            let (num_a, num_b) = (trim(&a[i..end_a]), trim(&b[j..end_b]));
+            let ord = num_a.len().cmp(&num_b.len()).then(num_a.cmp(&num_b));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            i = end_a;
+            j = end_b;
+        } else {
+            let ord = a[i].cmp(&b[j]);
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            i += 1;
+            j += 1;
+        }
+    }
+    (a.len() - i).cmp(&(b.len() - j))
+}
+
+fn compare_values(field_type: FieldType, a: &str, b: &str) -> Ordering {
+    match field_type {
+        FieldType::Number => {
+            let (a, b) = (a.parse::<f64>(), b.parse::<f64>());
+            match (a, b) {
+                (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                _ => Ordering::Equal,
+            }
+        }
+        // "YYYY-MM-DD" と "false" < "true" はそのまま比べられる
+        FieldType::Date | FieldType::Boolean => a.cmp(b),
+        FieldType::Text | FieldType::Url => compare_names(a, b),
+    }
+}
+
+// シードを決めて同じ順に並べられる乱数 (splitmix64)
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+fn sorted(
+    tierlist: &TierList,
+    order: &[ItemId],
+    options: &SortOptions,
+    rng: &mut Rng,
+) -> Vec<ItemId> {
+    let mut items: Vec<&Item> = order.iter().filter_map(|&id| tierlist.item(id)).collect();
+    let direction = |ord: Ordering| {
+        if options.descending {
+            ord.reverse()
+        } else {
+            ord
+        }
+    };
+    match options.key {
+        SortKey::Random => rng.shuffle(&mut items),
+        key => items.sort_by(|a, b| {
+            let ord = match key {
+                SortKey::Name => direction(compare_names(&a.name, &b.name)),
+                SortKey::Field { field } => {
+                    let field_type = tierlist
+                        .fields
+                        .iter()
+                        .find(|f| f.id == field)
+                        .map_or(FieldType::Text, |f| f.field_type);
+                    match (a.fields.get(&field), b.fields.get(&field)) {
+                        (Some(x), Some(y)) => direction(compare_values(field_type, x, y)),
+                        (Some(_), None) => Ordering::Less,
+                        (None, Some(_)) => Ordering::Greater,
+                        (None, None) => Ordering::Equal,
+                    }
+                }
+                SortKey::Added => {
+                    direction((a.times.created_at, a.id).cmp(&(b.times.created_at, b.id)))
+                }
+                SortKey::Updated => direction(a.times.updated_at.cmp(&b.times.updated_at)),
+                SortKey::Moved => direction(a.times.moved_at.cmp(&b.times.moved_at)),
+                SortKey::Random => Ordering::Equal,
+            };
+            if options.stable {
+                ord
+            } else {
+                ord.then_with(|| compare_names(&a.name, &b.name))
+                    .then_with(|| a.name.cmp(&b.name))
+                    .then(a.id.cmp(&b.id))
+            }
+        }),
+    }
+    items.into_iter().map(|it| it.id).collect()
+}
+
+// target のアイテムを並べ替える操作。1 回の undo で戻せるようにまとめる
+pub fn sort_op(
+    tierlist: &TierList,
+    target: SortTarget,
+    options: &SortOptions,
+    seed: u64,
+) -> Result<Op, String> {
+    if let SortKey::Field { field } = options.key {
+        tierlist.field_index(field)?;
+    }
+    let places: Vec<(Option<TierId>, &[ItemId])> = match target {
+        SortTarget::Tier { id } => {
+            let idx = tierlist.tier_index(id)?;
+            vec![(Some(id), &tierlist.tiers[idx].items)]
+        }
+        SortTarget::AllTiers => tierlist
+            .tiers
+            .iter()
+            .map(|t| (Some(t.id), t.items.as_slice()))
+            .collect(),
+        SortTarget::Pool => vec![(None, &tierlist.items_pool)],
+    };
+    let mut rng = Rng(seed);
+    let ops = places
+        .into_iter()
+        .map(|(tier, order)| Op::ReorderItems {
+            tier,
+            order: sorted(tierlist, order, options, &mut rng),
+        })
+        .collect();
+    Ok(Op::Group(ops))
+}
+
+pub mod commands {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use tauri::{async_runtime::Mutex, State};
+
+    use super::*;
+    use crate::history::History;
+
+    #[tauri::command]
+    pub async fn sort_items(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        target: SortTarget,
+        options: SortOptions,
+    ) -> Result<TierList, String> {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        let mut tierlist = tierlist.lock().await;
+        let op = sort_op(&tierlist, target, &options, seed)?;
+        history.lock().await.apply(&mut tierlist, op)?;
+        Ok(tierlist.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        history::History,
+        tierlist::{ItemData, ItemTimes},
+    };
+
+    fn options(key: SortKey, descending: bool, stable: bool) -> SortOptions {
+        SortOptions {
+            key,
+            descending,
+            stable,
+        }
+    }
+
+    fn names(tierlist: &TierList, order: &[ItemId]) -> Vec<String> {
+        order
+            .iter()
+            .map(|&id| tierlist.item(id).unwrap().name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn name_order() {
+        let mut names = vec![
+            "item10",
+            "Item2",
+            "ｲﾇ",
+            "いぬ",
+            "ウサギ",
+            "ｶﾞｯｺｳ",
+            "かっこう",
+            "Ｂｅｅ",
+            "apple",
+            "猫",
+        ];
+        names.sort_by(|a, b| compare_names(a, b));
+        assert_eq!(
+            names,
+            vec![
+                "apple",
+                "Ｂｅｅ",
+                "Item2",
+                "item10",
+                "ｲﾇ",
+                "いぬ",
+                "ウサギ",
+                "かっこう",
+                "ｶﾞｯｺｳ",
+                "猫"
+            ]
+        );
+        assert_eq!(compare_names("ネコ", "ねこ"), Ordering::Equal);
+        assert_eq!(compare_names("a007", "a7"), Ordering::Equal);
+    }
+
+    #[test]
+    fn sort_tiers_and_pool() {
+        let mut tierlist = TierList::empty();
+        let s = tierlist.add_tier("S");
+        let a = tierlist.add_tier("A");
+        let year = tierlist.add_field("year", FieldType::Number).unwrap();
+        for (name, tier, value) in [
+            ("b", Some(s), Some("2001")),
+            ("a", Some(s), Some("1999")),
+            ("c", Some(s), None),
+            ("b", Some(s), Some("1999")),
+            ("y", Some(a), None),
+            ("x", Some(a), None),
+            ("pool", None, None),
+        ] {
+            let id = tierlist.add_item(ItemData {
+                name: name.to_owned(),
+                url: "".to_owned(),
+                thumb: None,
+                memo: "".to_owned(),
+            });
+            tierlist.move_item(id, tier, usize::MAX).unwrap();
+            tierlist
+                .set_item_field(id, year, value.map(|v| v.to_owned()))
+                .unwrap();
+        }
+        let original = tierlist.clone();
+        let mut history = History::default();
+
+        let by_year = options(SortKey::Field { field: year }, true, true);
+        let op = sort_op(&tierlist, SortTarget::Tier { id: s }, &by_year, 0).unwrap();
+        history.apply(&mut tierlist, op).unwrap();
+        // 同じ年のものは今の順のまま、値のないものは最後
+        assert_eq!(tierlist.tiers[0].items, vec![1, 2, 4, 3]);
+        assert_eq!(tierlist.tiers[1].items, vec![5, 6]);
+
+        let by_year = options(SortKey::Field { field: year }, false, false);
+        let op = sort_op(&tierlist, SortTarget::Tier { id: s }, &by_year, 0).unwrap();
+        history.apply(&mut tierlist, op).unwrap();
+        assert_eq!(tierlist.tiers[0].items, vec![2, 4, 1, 3]);
+
+        let by_name = options(SortKey::Name, false, false);
+        let op = sort_op(&tierlist, SortTarget::AllTiers, &by_name, 0).unwrap();
+        history.apply(&mut tierlist, op).unwrap();
+        assert_eq!(
+            names(&tierlist, &tierlist.tiers[0].items),
+            vec!["a", "b", "b", "c"]
+        );
+        assert_eq!(tierlist.tiers[0].items, vec![2, 1, 4, 3]);
+        assert_eq!(tierlist.tiers[1].items, vec![6, 5]);
+
+        let by_added = options(SortKey::Added, true, false);
+        let op = sort_op(&tierlist, SortTarget::Pool, &by_added, 0).unwrap();
+        history.apply(&mut tierlist, op).unwrap();
+        assert_eq!(tierlist.items_pool, vec![7]);
+
+        // 同じシードなら同じ順になり、アイテムの集まりは変わらない
+        let random = options(SortKey::Random, false, false);
+        let first = sort_op(&tierlist, SortTarget::Tier { id: s }, &random, 42).unwrap();
+        assert_eq!(
+            first,
+            sort_op(&tierlist, SortTarget::Tier { id: s }, &random, 42).unwrap()
+        );
+        history.apply(&mut tierlist, first).unwrap();
+        let mut shuffled = tierlist.tiers[0].items.clone();
+        shuffled.sort_unstable();
+        assert_eq!(shuffled, vec![1, 2, 3, 4]);
+
+        while history.undo(&mut tierlist).unwrap() {}
+        assert_eq!(tierlist, original);
+
+        let unknown = options(SortKey::Field { field: 9 }, false, false);
+        assert!(sort_op(&tierlist, SortTarget::Pool, &unknown, 0).is_err());
+        assert!(sort_op(&tierlist, SortTarget::Tier { id: 9 }, &by_name, 0).is_err());
+        assert!(tierlist.reorder_items(Some(s), vec![1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn sort_by_times() {
+        let mut tierlist = TierList::empty();
+        for (created_at, moved_at) in [(0, 30), (20, 10), (0, 20), (10, 30)] {
+            let id = tierlist.add_item(ItemData {
+                name: "item".to_owned(),
+                url: "".to_owned(),
+                thumb: None,
+                memo: "".to_owned(),
+            });
+            let idx = tierlist.item_index(id).unwrap();
+            tierlist.items[idx].times = ItemTimes {
+                created_at,
+                updated_at: created_at,
+                moved_at,
+            };
+        }
+        let order = |key: SortKey, stable: bool| {
+            let op = sort_op(&tierlist, SortTarget::Pool, &options(key, false, stable), 0).unwrap();
+            match op {
+                Op::Group(ops) => match &ops[0] {
+                    Op::ReorderItems { order, .. } => order.clone(),
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            }
+        };
+        // 時刻が不明 (0) なものは id 順で先頭に
+        assert_eq!(order(SortKey::Added, true), vec![1, 3, 4, 2]);
+        assert_eq!(order(SortKey::Moved, true), vec![2, 3, 1, 4]);
+        assert_eq!(order(SortKey::Updated, false), vec![1, 3, 4, 2]);
+    }
+}
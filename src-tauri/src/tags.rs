@@ -0,0 +1,415 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::tierlist::{same_tag, ItemId, TierList};
+
+// タグの条件式
+//   expr := and ("OR" | "|") and ...
+//   and  := not [("AND" | "&")] not ...   (並べただけでも AND)
+//   not  := ("NOT" | "!" | "-") not | "(" expr ")" | tag
+// タグは空白や記号を含まない語か "..." で囲んだもの。大文字小文字は区別しない
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagExpr {
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Vec<TagExpr>),
+    Or(Vec<TagExpr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    // "..." で囲んだもの。AND などのキーワードとはみなさない
+    Quoted(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn is_symbol(c: char) -> bool {
+    matches!(c, '(' | ')' | '&' | '|' | '!' | '"')
+}
+
+fn quoted(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some('"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                s.push('"');
+            }
+            Some('"') => return Ok(s),
+            Some(c) => s.push(c),
+            None => return Err("Unterminated quoted tag".to_owned()),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let token = match c {
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' => Token::And,
+            '|' => Token::Or,
+            '!' | '-' => Token::Not,
+            '"' => {
+                chars.next();
+                tokens.push(Token::Quoted(quoted(&mut chars)?));
+                continue;
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || is_symbol(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+                continue;
+            }
+        };
+        chars.next();
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<TagExpr, String> {
+        let mut terms = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            terms.push(self.and()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            TagExpr::Or(terms)
+        })
+    }
+
+    fn and(&mut self) -> Result<TagExpr, String> {
+        let mut terms = vec![self.not()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                Some(Token::Or) | Some(Token::Close) | None => break,
+                Some(_) => {}
+            }
+            terms.push(self.not()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            TagExpr::And(terms)
+        })
+    }
+
+    fn not(&mut self) -> Result<TagExpr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(TagExpr::Not(Box::new(self.not()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("Missing \")\"".to_owned()),
+                }
+            }
+            Some(Token::Word(tag)) | Some(Token::Quoted(tag)) => Ok(TagExpr::Tag(tag)),
+            Some(_) => Err("Unexpected operator".to_owned()),
+            None => Err("Unexpected end of expression".to_owned()),
+        }
+    }
+}
+
+impl TagExpr {
+    pub fn parse(text: &str) -> Result<TagExpr, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+        };
+        if parser.peek().is_none() {
+            return Err("Tag expression is empty".to_owned());
+        }
+        let expr = parser.or()?;
+        if parser.peek().is_some() {
+            return Err("Unexpected \")\"".to_owned());
+        }
+        Ok(expr)
+    }
+
+    pub fn matches(&self, tags: &[String]) -> bool {
+        match self {
+            TagExpr::Tag(tag) => tags.iter().any(|t| same_tag(t, tag)),
+            TagExpr::Not(expr) => !expr.matches(tags),
+            TagExpr::And(exprs) => exprs.iter().all(|e| e.matches(tags)),
+            TagExpr::Or(exprs) => exprs.iter().any(|e| e.matches(tags)),
+        }
+    }
+}
+
+// 条件に合うアイテムの id (id 順)
+pub fn query(tierlist: &TierList, expr: &TagExpr) -> Vec<ItemId> {
+    tierlist
+        .items
+        .iter()
+        .filter(|it| expr.matches(&it.tags))
+        .map(|it| it.id)
+        .collect()
+}
+
+// old のタグを new に置き換えた後の、変わるアイテムのタグ。new が None なら外す
+// 大文字小文字だけを変える名前の変更もできる
+fn replace_tag(tierlist: &TierList, old: &str, new: Option<&str>) -> Vec<(ItemId, Vec<String>)> {
+    tierlist
+        .items
+        .iter()
+        .filter(|it| it.tags.iter().any(|t| same_tag(t, old)))
+        .map(|it| {
+            let tags = it
+                .tags
+                .iter()
+                .filter(|t| !same_tag(t, old))
+                .cloned()
+                .chain(new.map(|t| t.to_owned()))
+                .collect();
+            (it.id, tags)
+        })
+        .collect()
+}
+
+pub mod commands {
+    use tauri::{async_runtime::Mutex, State};
+
+    use super::*;
+    use crate::{
+        history::{History, Op},
+        tierlist::TagCount,
+    };
+
+    async fn set_tags(
+        tierlist: &Mutex<TierList>,
+        history: &Mutex<History>,
+        change: impl FnOnce(&TierList) -> Result<Vec<(ItemId, Vec<String>)>, String>,
+    ) -> Result<TierList, String> {
+        let mut tierlist = tierlist.lock().await;
+        let tags = change(&tierlist)?;
+        history
+            .lock()
+            .await
+            .apply(&mut tierlist, Op::SetItemTags { tags })?;
+        Ok(tierlist.clone())
+    }
+
+    #[tauri::command]
+    pub async fn list_tags(tierlist: State<'_, Mutex<TierList>>) -> Result<Vec<TagCount>, String> {
+        Ok(tierlist.lock().await.tag_counts())
+    }
+
+    #[tauri::command]
+    pub async fn add_item_tag(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        id: ItemId,
+        tag: String,
+    ) -> Result<TierList, String> {
+        set_tags(&tierlist, &history, |tierlist| {
+            let item = tierlist
+                .item(id)
+                .ok_or_else(|| format!("Item {} not found", id))?;
+            let mut tags = item.tags.clone();
+            tags.push(tag);
+            Ok(vec![(id, tags)])
+        })
+        .await
+    }
+
+    #[tauri::command]
+    pub async fn remove_item_tag(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        id: ItemId,
+        tag: String,
+    ) -> Result<TierList, String> {
+        set_tags(&tierlist, &history, |tierlist| {
+            let item = tierlist
+                .item(id)
+                .ok_or_else(|| format!("Item {} not found", id))?;
+            let tags = item
+                .tags
+                .iter()
+                .filter(|t| !same_tag(t, &tag))
+                .cloned()
+                .collect();
+            Ok(vec![(id, tags)])
+        })
+        .await
+    }
+
+    // new がすでにあるタグなら 2 つのタグをまとめる
+    #[tauri::command]
+    pub async fn rename_tag(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        old: String,
+        new: String,
+    ) -> Result<TierList, String> {
+        if new.trim().is_empty() {
+            return Err("Tag name is empty".to_owned());
+        }
+        set_tags(&tierlist, &history, |tierlist| {
+            Ok(replace_tag(tierlist, &old, Some(&new)))
+        })
+        .await
+    }
+
+    #[tauri::command]
+    pub async fn delete_tag(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        tag: String,
+    ) -> Result<TierList, String> {
+        set_tags(&tierlist, &history, |tierlist| {
+            Ok(replace_tag(tierlist, &tag, None))
+        })
+        .await
+    }
+
+    #[tauri::command]
+    pub async fn query_items_by_tags(
+        tierlist: State<'_, Mutex<TierList>>,
+        expr: String,
+    ) -> Result<Vec<ItemId>, String> {
+        let expr = TagExpr::parse(&expr)?;
+        Ok(query(&*tierlist.lock().await, &expr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tierlist::ItemData;
+
+    fn tag(name: &str) -> TagExpr {
+        TagExpr::Tag(name.to_owned())
+    }
+
+    #[test]
+    fn parse_expressions() {
+        assert_eq!(TagExpr::parse("anime").unwrap(), tag("anime"));
+        assert_eq!(
+            TagExpr::parse("a b | NOT c").unwrap(),
+            TagExpr::Or(vec![
+                TagExpr::And(vec![tag("a"), tag("b")]),
+                TagExpr::Not(Box::new(tag("c"))),
+            ])
+        );
+        assert_eq!(
+            TagExpr::parse("a AND (b OR -\"sci fi\")").unwrap(),
+            TagExpr::And(vec![
+                tag("a"),
+                TagExpr::Or(vec![tag("b"), TagExpr::Not(Box::new(tag("sci fi")))]),
+            ])
+        );
+        // 小文字の and はタグ名
+        assert_eq!(
+            TagExpr::parse("a & and").unwrap(),
+            TagExpr::And(vec![tag("a"), tag("and")])
+        );
+        for text in ["", "  ", "a OR", "(a", "a)", "\"a", "NOT", "& a"] {
+            assert!(TagExpr::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn query_and_replace_tags() {
+        let mut tierlist = TierList::empty();
+        for tags in [
+            vec!["Anime", "2022", "anime"],
+            vec!["anime"],
+            vec!["2022", "manga"],
+            vec![],
+        ] {
+            let id = tierlist.add_item(ItemData {
+                name: "item".to_owned(),
+                url: "".to_owned(),
+                thumb: None,
+                memo: "".to_owned(),
+            });
+            let tags = tags.into_iter().map(|t| t.to_owned()).collect();
+            tierlist.set_item_tags(id, tags).unwrap();
+        }
+        assert_eq!(tierlist.items[0].tags, vec!["2022", "Anime"]);
+
+        let ids = |text: &str| query(&tierlist, &TagExpr::parse(text).unwrap());
+        assert_eq!(ids("anime"), vec![1, 2]);
+        assert_eq!(ids("anime -2022"), vec![2]);
+        assert_eq!(ids("anime | manga"), vec![1, 2, 3]);
+        assert_eq!(ids("NOT (anime OR manga)"), vec![4]);
+
+        let changes = replace_tag(&tierlist, "2022", Some("manga"));
+        assert_eq!(
+            changes,
+            vec![
+                (1, vec!["Anime".to_owned(), "manga".to_owned()]),
+                (3, vec!["manga".to_owned(), "manga".to_owned()]),
+            ]
+        );
+        for (id, tags) in changes {
+            tierlist.set_item_tags(id, tags).unwrap();
+        }
+        assert_eq!(tierlist.items[2].tags, vec!["manga"]);
+        let counts = tierlist.tag_counts();
+        let counts: Vec<(&str, usize)> =
+            counts.iter().map(|c| (c.name.as_str(), c.count)).collect();
+        assert_eq!(counts, vec![("Anime", 2), ("manga", 2)]);
+
+        // 名前の変更や削除も大文字小文字を区別しない
+        let changes = replace_tag(&tierlist, "ANIME", Some("anime"));
+        assert_eq!(
+            changes,
+            vec![
+                (1, vec!["manga".to_owned(), "anime".to_owned()]),
+                (2, vec!["anime".to_owned()]),
+            ]
+        );
+        for (id, tags) in changes {
+            tierlist.set_item_tags(id, tags).unwrap();
+        }
+        assert_eq!(tierlist.tag_counts()[0].name, "anime");
+        let changes = replace_tag(&tierlist, "Anime", None);
+        assert_eq!(changes, vec![(1, vec!["manga".to_owned()]), (2, vec![])]);
+    }
+}
@@ -0,0 +1,290 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::tierlist::{palette_color, TierList, TierStyle};
+
+const TEMPLATES_FILE_NAME: &str = "templates.json";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateTier {
+    pub title: String,
+    #[serde(flatten)]
+    pub style: TierStyle,
+}
+
+// 新しい tierlist の tier の構成。アイテムは持たない
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Template {
+    pub name: String,
+    // 組み込みのものは保存されず、消せない
+    #[serde(default)]
+    pub builtin: bool,
+    pub tiers: Vec<TemplateTier>,
+}
+
+impl Template {
+    // tierlist の tier の名前と見た目だけを取り出す
+    pub fn from_tierlist(name: &str, tierlist: &TierList) -> Self {
+        Template {
+            name: name.to_owned(),
+            builtin: false,
+            tiers: tierlist
+                .tiers
+                .iter()
+                .map(|tier| TemplateTier {
+                    title: tier.title.clone(),
+                    style: TierStyle {
+                        color: tier.color.clone(),
+                        text_color: tier.text_color.clone(),
+                        icon: tier.icon.clone(),
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_tierlist(&self) -> TierList {
+        let mut tierlist = TierList::empty();
+        for template_tier in self.tiers.iter() {
+            let id = tierlist.add_tier(&template_tier.title);
+            // 色が読めないものは add_tier で付いた既定の色のままにする
+            let _ = tierlist.set_tier_style(id, template_tier.style.clone());
+        }
+        tierlist
+    }
+}
+
+fn builtin(name: &str, tiers: &[(&str, usize)]) -> Template {
+    Template {
+        name: name.to_owned(),
+        builtin: true,
+        tiers: tiers
+            .iter()
+            .map(|&(title, palette_index)| TemplateTier {
+                title: title.to_owned(),
+                style: TierStyle {
+                    color: palette_color(palette_index),
+                    text_color: None,
+                    icon: None,
+                },
+            })
+            .collect(),
+    }
+}
+
+pub fn builtin_templates() -> Vec<Template> {
+    let stars: Vec<(String, usize)> = (0..10)
+        .map(|i| (format!("{} ★", 10 - i), i * 7 / 10))
+        .collect();
+    let stars: Vec<(&str, usize)> = stars.iter().map(|(t, i)| (t.as_str(), *i)).collect();
+    vec![
+        builtin(
+            "S–F",
+            &[
+                ("S", 0),
+                ("A", 1),
+                ("B", 2),
+                ("C", 3),
+                ("D", 4),
+                ("E", 5),
+                ("F", 6),
+            ],
+        ),
+        builtin("1–10 stars", &stars),
+        builtin(
+            "Must watch / Maybe / Skip",
+            &[("Must watch", 5), ("Maybe", 3), ("Skip", 0)],
+        ),
+    ]
+}
+
+pub fn templates_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(TEMPLATES_FILE_NAME)
+}
+
+// 読めなかった場合はユーザーのテンプレートがないものとして扱う
+pub async fn load_user_templates(data_dir: &Path) -> Vec<Template> {
+    match fs::read(templates_path(data_dir)).await {
+        Ok(buf) => serde_json::from_slice(&buf).unwrap_or_default(),
+        Err(_) => vec![],
+    }
+}
+
+pub async fn save_user_templates(data_dir: &Path, templates: &[Template]) -> Result<(), String> {
+    fs::create_dir_all(data_dir)
+        .await
+        .map_err(|e| e.to_string())?;
+    let buf = serde_json::to_vec_pretty(templates).map_err(|e| e.to_string())?;
+    fs::write(templates_path(data_dir), buf)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 組み込みのものが先
+pub async fn list_templates(data_dir: &Path) -> Vec<Template> {
+    let mut templates = builtin_templates();
+    templates.extend(load_user_templates(data_dir).await);
+    templates
+}
+
+// 同じ名前のユーザーのテンプレートは置き換える
+pub async fn add_user_template(data_dir: &Path, template: Template) -> Result<(), String> {
+    if template.name.trim().is_empty() {
+        return Err("Template name is empty".to_owned());
+    }
+    if builtin_templates().iter().any(|t| t.name == template.name) {
+        return Err(format!(
+            "Built-in template {} cannot be replaced",
+            template.name
+        ));
+    }
+    let mut templates = load_user_templates(data_dir).await;
+    match templates.iter_mut().find(|t| t.name == template.name) {
+        Some(t) => *t = template,
+        None => templates.push(template),
+    }
+    save_user_templates(data_dir, &templates).await
+}
+
+pub async fn remove_user_template(data_dir: &Path, name: &str) -> Result<(), String> {
+    let mut templates = load_user_templates(data_dir).await;
+    let len = templates.len();
+    templates.retain(|t| t.name != name);
+    if templates.len() == len {
+        return Err(format!("User template {} not found", name));
+    }
+    save_user_templates(data_dir, &templates).await
+}
+
+pub mod commands {
+    use tauri::{async_runtime::Mutex, AppHandle, State};
+
+    use super::*;
+    use crate::{autosave::data_dir, history::History, tierlist::ListId};
+
+    #[tauri::command]
+    pub async fn list_templates(app: AppHandle) -> Result<Vec<Template>, String> {
+        Ok(super::list_templates(&data_dir(&app)?).await)
+    }
+
+    // 今の tierlist の tier の構成をテンプレートとして保存する
+    #[tauri::command]
+    pub async fn save_template(
+        app: AppHandle,
+        tierlist: State<'_, Mutex<TierList>>,
+        name: String,
+    ) -> Result<Vec<Template>, String> {
+        let data_dir = data_dir(&app)?;
+        let template = Template::from_tierlist(&name, &*tierlist.lock().await);
+        add_user_template(&data_dir, template).await?;
+        Ok(super::list_templates(&data_dir).await)
+    }
+
+    #[tauri::command]
+    pub async fn delete_template(app: AppHandle, name: String) -> Result<Vec<Template>, String> {
+        let data_dir = data_dir(&app)?;
+        remove_user_template(&data_dir, &name).await?;
+        Ok(super::list_templates(&data_dir).await)
+    }
+
+    #[tauri::command]
+    pub async fn new_tierlist_from_template(
+        app: AppHandle,
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+        name: String,
+    ) -> Result<TierList, String> {
+        let template = super::list_templates(&data_dir(&app)?)
+            .await
+            .into_iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| format!("Template {} not found", name))?;
+        let mut tierlist = tierlist.lock().await;
+        *tierlist = template.to_tierlist();
+        history.lock().await.clear();
+        *cur_list.lock().await = None;
+        Ok(tierlist.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::tierlist::ItemData;
+
+    #[test]
+    fn builtin_to_tierlist() {
+        let templates = builtin_templates();
+        let tierlist = templates[0].to_tierlist();
+        let titles: Vec<&str> = tierlist.tiers.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["S", "A", "B", "C", "D", "E", "F"]);
+        assert_eq!(tierlist.tier_max_id, 7);
+        assert_eq!(tierlist.tiers[6].color, palette_color(6));
+        assert!(tierlist.validate().is_empty());
+
+        let stars = templates[1].to_tierlist();
+        assert_eq!(stars.tiers.len(), 10);
+        assert_eq!(stars.tiers[0].title, "10 ★");
+        assert_eq!(stars.tiers[9].title, "1 ★");
+    }
+
+    #[tokio::test]
+    async fn save_and_remove_user_template() {
+        let dir = TempDir::new("template_test").unwrap();
+        let data_dir = dir.path().join("data");
+        assert_eq!(list_templates(&data_dir).await, builtin_templates());
+
+        let mut tierlist = TierList::empty();
+        let id = tierlist.add_tier("Top");
+        tierlist
+            .set_tier_style(
+                id,
+                TierStyle {
+                    color: "#123456".to_owned(),
+                    text_color: Some("#ffffff".to_owned()),
+                    icon: Some("👑".to_owned()),
+                },
+            )
+            .unwrap();
+        tierlist.add_tier("Rest");
+        tierlist.add_item(ItemData {
+            name: "item1".to_owned(),
+            url: "".to_owned(),
+            thumb: None,
+            memo: "".to_owned(),
+        });
+
+        add_user_template(&data_dir, Template::from_tierlist("mine", &tierlist))
+            .await
+            .unwrap();
+        let templates = list_templates(&data_dir).await;
+        let mine = templates.last().unwrap();
+        assert_eq!(mine.name, "mine");
+        assert!(!mine.builtin);
+
+        let created = mine.to_tierlist();
+        assert_eq!(created.tiers[0].color, "#123456");
+        assert_eq!(created.tiers[0].icon.as_deref(), Some("👑"));
+        assert_eq!(created.tiers[1].title, "Rest");
+        assert!(created.items.is_empty());
+        assert!(created.tiers.iter().all(|t| t.items.is_empty()));
+
+        // 組み込みのものと同じ名前では保存できない
+        assert!(
+            add_user_template(&data_dir, Template::from_tierlist("S–F", &tierlist))
+                .await
+                .is_err()
+        );
+        assert!(remove_user_template(&data_dir, "S–F").await.is_err());
+
+        remove_user_template(&data_dir, "mine").await.unwrap();
+        assert_eq!(list_templates(&data_dir).await, builtin_templates());
+    }
+}
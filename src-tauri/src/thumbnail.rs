@@ -0,0 +1,329 @@
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageOutputFormat, Rgb, RgbImage,
+};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+const THUMBNAIL_OPTIONS_NAME: &str = "thumbnail_options.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ThumbnailFormat {
+    Jpeg,
+    Png,
+}
+
+impl ThumbnailFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpeg => "jpg",
+            ThumbnailFormat::Png => "png",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailOptions {
+    // 長辺がこれより大きい画像は縮小する
+    pub max_size: u32,
+    pub format: ThumbnailFormat,
+    // JPEG の品質 (1-100)
+    pub quality: u8,
+}
+
+impl Default for ThumbnailOptions {
+    fn default() -> Self {
+        ThumbnailOptions {
+            max_size: 400,
+            format: ThumbnailFormat::Jpeg,
+            quality: 85,
+        }
+    }
+}
+
+pub fn options_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(THUMBNAIL_OPTIONS_NAME)
+}
+
+// 読めなかった場合は既定の設定として扱う
+pub async fn load_options(config_dir: &Path) -> ThumbnailOptions {
+    match fs::read(options_path(config_dir)).await {
+        Ok(buf) => serde_json::from_slice(&buf).unwrap_or_default(),
+        Err(_) => ThumbnailOptions::default(),
+    }
+}
+
+pub async fn save_options(config_dir: &Path, options: &ThumbnailOptions) -> Result<(), String> {
+    fs::create_dir_all(config_dir)
+        .await
+        .map_err(|e| e.to_string())?;
+    let buf = serde_json::to_vec_pretty(options).map_err(|e| e.to_string())?;
+    fs::write(options_path(config_dir), buf)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 透過部分を白で塗りつぶす (JPEG はアルファチャンネルを持てない)
+fn flatten(img: &DynamicImage) -> RgbImage {
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+// 画像として読めるか確かめ、縮小して options の形式でエンコードし直す
+pub fn normalize(bytes: &[u8], options: &ThumbnailOptions) -> Result<Vec<u8>, String> {
+    let img = image::load_from_memory(bytes).map_err(|e| format!("Not a valid image: {}", e))?;
+    if img.width() == 0 || img.height() == 0 {
+        return Err("Image is empty".to_owned());
+    }
+    let max_size = options.max_size.max(1);
+    let img = if img.width() > max_size || img.height() > max_size {
+        img.resize(max_size, max_size, FilterType::Lanczos3)
+    } else {
+        img
+    };
+
+    let mut buf = vec![];
+    match options.format {
+        ThumbnailFormat::Jpeg => {
+            let rgb = flatten(&img);
+            JpegEncoder::new_with_quality(&mut buf, options.quality.clamp(1, 100))
+                .encode_image(&rgb)
+                .map_err(|e| e.to_string())?;
+        }
+        ThumbnailFormat::Png => {
+            img.write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Png)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(buf)
+}
+
+// 保存した画像の通し番号。画像ディレクトリはプロセスごとの一時ディレクトリなので、これだけで名前が重ならない
+static SAVED_COUNT: AtomicU64 = AtomicU64::new(0);
+
+// bytes を正規化して dir 以下に name_{通し番号} (+ 拡張子) として保存し、パスを返す
+// 同じ名前の画像 (cover.png と cover.jpg など) を続けて取り込んでも、先のものを上書きしない
+pub async fn save_normalized(
+    bytes: Vec<u8>,
+    dir: &Path,
+    name: &str,
+    options: &ThumbnailOptions,
+) -> Result<String, String> {
+    let options = options.clone();
+    let count = SAVED_COUNT.fetch_add(1, Ordering::Relaxed);
+    let path = dir.join(format!("{}_{}.{}", name, count, options.format.extension()));
+    let buf = tokio::task::spawn_blocking(move || normalize(&bytes, &options))
+        .await
+        .map_err(|e| e.to_string())??;
+    fs::write(&path, buf).await.map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+// path の画像ファイルを正規化して dir 以下に取り込む
+pub async fn import_file(
+    path: &Path,
+    dir: &Path,
+    options: &ThumbnailOptions,
+) -> Result<String, String> {
+    let bytes = fs::read(path).await.map_err(|e| e.to_string())?;
+    let name = path
+        .file_stem()
+        .ok_or("Filename not found")?
+        .to_string_lossy()
+        .to_string();
+    save_normalized(bytes, dir, &name, options).await
+}
+
+// dir 以下の画像は取り込み済み (正規化済み) なのでそのまま使う
+// それ以外はユーザーが選んだファイルなので、正規化して取り込む
+pub async fn import_if_outside(
+    thumb: Option<String>,
+    dir: &Path,
+    options: &ThumbnailOptions,
+) -> Result<Option<String>, String> {
+    match thumb {
+        Some(thumb) if !Path::new(&thumb).starts_with(dir) => {
+            Ok(Some(import_file(Path::new(&thumb), dir, options).await?))
+        }
+        thumb => Ok(thumb),
+    }
+}
+
+pub mod commands {
+    use tauri::{async_runtime::Mutex, AppHandle, State};
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::recent;
+
+    #[tauri::command]
+    pub async fn get_thumbnail_options(
+        options: State<'_, Mutex<ThumbnailOptions>>,
+    ) -> Result<ThumbnailOptions, String> {
+        Ok(options.lock().await.clone())
+    }
+
+    #[tauri::command]
+    pub async fn set_thumbnail_options(
+        app: AppHandle,
+        options: State<'_, Mutex<ThumbnailOptions>>,
+        new_options: ThumbnailOptions,
+    ) -> Result<(), String> {
+        let mut options = options.lock().await;
+        save_options(&recent::config_dir(&app)?, &new_options).await?;
+        *options = new_options;
+        Ok(())
+    }
+
+    // ユーザーが選んだ画像ファイルを正規化して画像ディレクトリに取り込む
+    #[tauri::command]
+    pub async fn import_thumbnail(
+        img_dir: State<'_, TempDir>,
+        options: State<'_, Mutex<ThumbnailOptions>>,
+        path: String,
+    ) -> Result<String, String> {
+        let options = options.lock().await.clone();
+        import_file(Path::new(&path), img_dir.path(), &options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageFormat, Rgba, RgbaImage};
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let img = RgbaImage::from_pixel(width, height, Rgba([255, 0, 0, 128]));
+        let mut buf = vec![];
+        DynamicImage::ImageRgba8(img)
+            .write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Png)
+            .unwrap();
+        buf
+    }
+
+    #[test]
+    fn shrink_to_max_size() {
+        let options = ThumbnailOptions {
+            max_size: 200,
+            ..Default::default()
+        };
+        let buf = normalize(&png_bytes(800, 400), &options).unwrap();
+        assert_eq!(image::guess_format(&buf).unwrap(), ImageFormat::Jpeg);
+        let img = image::load_from_memory(&buf).unwrap();
+        assert_eq!((img.width(), img.height()), (200, 100));
+    }
+
+    #[test]
+    fn small_image_not_enlarged() {
+        let options = ThumbnailOptions {
+            format: ThumbnailFormat::Png,
+            ..Default::default()
+        };
+        let buf = normalize(&png_bytes(30, 50), &options).unwrap();
+        assert_eq!(image::guess_format(&buf).unwrap(), ImageFormat::Png);
+        let img = image::load_from_memory(&buf).unwrap();
+        assert_eq!((img.width(), img.height()), (30, 50));
+    }
+
+    #[test]
+    fn transparent_pixels_on_white() {
+        let buf = normalize(&png_bytes(4, 4), &ThumbnailOptions::default()).unwrap();
+        let img = image::load_from_memory(&buf).unwrap().to_rgb8();
+        let [r, g, b] = img.get_pixel(0, 0).0;
+        assert!(r > 240 && (120..136).contains(&g) && (120..136).contains(&b));
+    }
+
+    #[test]
+    fn not_an_image_err() {
+        assert!(normalize(b"<html></html>", &ThumbnailOptions::default()).is_err());
+        assert!(normalize(&[], &ThumbnailOptions::default()).is_err());
+    }
+
+    #[tokio::test]
+    async fn save_normalized_file() {
+        let dir = TempDir::new("thumb_test").unwrap();
+        let path = save_normalized(
+            png_bytes(10, 10),
+            dir.path(),
+            "cover",
+            &ThumbnailOptions::default(),
+        )
+        .await
+        .unwrap();
+        let file_name = Path::new(&path).file_name().unwrap().to_string_lossy();
+        assert!(file_name.starts_with("cover_") && file_name.ends_with(".jpg"));
+        let buf = fs::read(&path).await.unwrap();
+        assert_eq!(image::guess_format(&buf).unwrap(), ImageFormat::Jpeg);
+
+        // 同じ名前でも先に保存したものは上書きしない
+        let other = save_normalized(
+            png_bytes(20, 20),
+            dir.path(),
+            "cover",
+            &ThumbnailOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_ne!(other, path);
+        assert_eq!(fs::read(&path).await.unwrap(), buf);
+    }
+    #[tokio::test]
+    async fn import_only_outside_images() {
+        let img_dir = TempDir::new("thumb_test").unwrap();
+        let user_dir = TempDir::new("thumb_user").unwrap();
+        let user_path = user_dir.path().join("big.png");
+        fs::write(&user_path, png_bytes(800, 800)).await.unwrap();
+        let options = ThumbnailOptions::default();
+
+        // 画像ディレクトリの外のファイルは正規化して取り込む
+        let user_path = user_path.to_string_lossy().to_string();
+        let imported = import_if_outside(Some(user_path), img_dir.path(), &options)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(Path::new(&imported).starts_with(img_dir.path()));
+        let img = image::load_from_memory(&fs::read(&imported).await.unwrap()).unwrap();
+        assert_eq!((img.width(), img.height()), (400, 400));
+
+        // 取り込み済みのものはそのまま
+        let again = import_if_outside(Some(imported.clone()), img_dir.path(), &options)
+            .await
+            .unwrap();
+        assert_eq!(again, Some(imported));
+        assert_eq!(
+            import_if_outside(None, img_dir.path(), &options)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn options_saved_and_loaded() {
+        let config_dir = TempDir::new("thumb_config").unwrap();
+        assert_eq!(
+            load_options(config_dir.path()).await,
+            ThumbnailOptions::default()
+        );
+        let options = ThumbnailOptions {
+            max_size: 120,
+            format: ThumbnailFormat::Png,
+            quality: 70,
+        };
+        save_options(config_dir.path(), &options).await.unwrap();
+        assert_eq!(load_options(config_dir.path()).await, options);
+    }
+}
@@ -1,9 +1,53 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use serde::{Deserialize, Serialize};
 
+use crate::fields::{FieldDef, FieldId, FieldType};
+
+pub type ListId = i64;
 pub type TierId = i64;
 pub type ItemId = i64;
+// UNIX 時間 (秒)
+pub type Timestamp = i64;
+
+pub fn now() -> Timestamp {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as Timestamp)
+        .unwrap_or(0)
+}
+
+// S, A, B, ... の順に使う tier の色
+pub const TIER_PALETTE: [&str; 7] = [
+    "#ff7f7f", "#ffbf7f", "#ffdf7f", "#ffff7f", "#bfff7f", "#7fff7f", "#7fffff",
+];
+
+// 上から index 番目の tier の既定の色
+pub fn palette_color(index: usize) -> String {
+    TIER_PALETTE[index % TIER_PALETTE.len()].to_owned()
+}
+
+// "#rrggbb" 形式の色を読む
+pub fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TierListInfo {
+    pub id: ListId,
+    pub title: String,
+}
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TierList {
     pub title: String,
@@ -12,6 +56,11 @@ pub struct TierList {
     pub items: Vec<Item>,
     pub items_pool: Vec<ItemId>,
     pub item_max_id: ItemId,
+    // アイテムに付ける値の定義 (表示する順)
+    #[serde(default)]
+    pub fields: Vec<FieldDef>,
+    #[serde(default)]
+    pub field_max_id: FieldId,
 }
 
 impl TierList {
@@ -23,19 +72,48 @@ impl TierList {
             items: vec![],
             items_pool: vec![],
             item_max_id: 0,
+            fields: vec![],
+            field_max_id: 0,
         }
     }
+
+    // 新しく作る tierlist。S から D までの tier を持つ
+    pub fn with_default_tiers() -> Self {
+        let mut tierlist = TierList::empty();
+        for title in ["S", "A", "B", "C", "D"] {
+            tierlist.add_tier(title);
+        }
+        tierlist
+    }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Tier {
     pub id: TierId,
     pub title: String,
     pub items: Vec<ItemId>,
+    // "#rrggbb"
+    #[serde(default)]
+    pub color: String,
+    // None ならフロントエンドが color から決める
+    #[serde(default)]
+    pub text_color: Option<String>,
+    // ラベルに添える絵文字など
+    #[serde(default)]
+    pub icon: Option<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+// tier の見た目。フロントエンドから変更するときに渡される
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TierStyle {
+    pub color: String,
+    pub text_color: Option<String>,
+    pub icon: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Item {
     pub id: ItemId,
@@ -43,4 +121,1030 @@ pub struct Item {
     pub url: String,
     pub thumb: Option<String>,
     pub memo: String,
+    // 名前順で重複なし
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // fields で定義したものの値。型に合う形に揃えてある
+    #[serde(default)]
+    pub fields: BTreeMap<FieldId, String>,
+    #[serde(flatten)]
+    pub times: ItemTimes,
+}
+
+// アイテムを追加した、内容を変えた、別の tier に移した時刻
+// 0 なら不明 (記録するようになる前からあるもの)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemTimes {
+    #[serde(default)]
+    pub created_at: Timestamp,
+    #[serde(default)]
+    pub updated_at: Timestamp,
+    #[serde(default)]
+    pub moved_at: Timestamp,
+}
+
+impl ItemTimes {
+    pub fn new(at: Timestamp) -> Self {
+        ItemTimes {
+            created_at: at,
+            updated_at: at,
+            moved_at: at,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCount {
+    pub name: String,
+    pub count: usize,
+}
+
+// タグは大文字小文字を区別しない。付けるときも、探すときも、名前を変えるときも同じ
+pub fn same_tag(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+// アイテムを追加・編集するときにフロントエンドから渡される内容
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemData {
+    pub name: String,
+    pub url: String,
+    pub thumb: Option<String>,
+    pub memo: String,
+}
+
+// TierList が満たすべき条件に反している箇所
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Violation {
+    // items に同じ id のアイテムが複数ある
+    #[serde(rename_all = "camelCase")]
+    DuplicateItem { item: ItemId },
+    // 同じ id の tier が複数ある。後のほうには new_id を振り直す
+    #[serde(rename_all = "camelCase")]
+    DuplicateTier { tier: TierId, new_id: TierId },
+    // tier かプールに置かれているが items にない
+    #[serde(rename_all = "camelCase")]
+    MissingItem { item: ItemId },
+    // 複数の場所 (または同じ場所に複数回) 置かれている
+    #[serde(rename_all = "camelCase")]
+    ItemPlacedTwice { item: ItemId },
+    // どこにも置かれていない
+    #[serde(rename_all = "camelCase")]
+    UnplacedItem { item: ItemId },
+    #[serde(rename_all = "camelCase")]
+    ItemMaxIdTooLow { max_id: ItemId, actual: ItemId },
+    #[serde(rename_all = "camelCase")]
+    TierMaxIdTooLow { max_id: TierId, actual: TierId },
+    // 同じ id の定義が複数ある。後のほうを捨てる
+    #[serde(rename_all = "camelCase")]
+    DuplicateField { field: FieldId },
+    #[serde(rename_all = "camelCase")]
+    FieldMaxIdTooLow { max_id: FieldId, actual: FieldId },
+    // 定義のない値、型に合わない値は捨てる
+    #[serde(rename_all = "camelCase")]
+    UnknownField { item: ItemId, field: FieldId },
+    #[serde(rename_all = "camelCase")]
+    InvalidFieldValue { item: ItemId, field: FieldId },
+}
+
+impl From<&Item> for ItemData {
+    fn from(item: &Item) -> Self {
+        ItemData {
+            name: item.name.clone(),
+            url: item.url.clone(),
+            thumb: item.thumb.clone(),
+            memo: item.memo.clone(),
+        }
+    }
+}
+
+impl TierList {
+    pub fn validate(&self) -> Vec<Violation> {
+        self.clone().repair()
+    }
+
+    // 条件に反している箇所を直し、直した箇所を返す
+    // 重複は先に現れたもの (items の前のほう、tiers の上のほう、プールより tier) を残し、
+    // どこにも置かれていないアイテムはプールの末尾に置く
+    pub fn repair(&mut self) -> Vec<Violation> {
+        let mut violations = vec![];
+
+        let max_item = self.items.iter().map(|it| it.id).max().unwrap_or(0);
+        if self.item_max_id < max_item {
+            violations.push(Violation::ItemMaxIdTooLow {
+                max_id: self.item_max_id,
+                actual: max_item,
+            });
+            self.item_max_id = max_item;
+        }
+        let max_tier = self.tiers.iter().map(|t| t.id).max().unwrap_or(0);
+        if self.tier_max_id < max_tier {
+            violations.push(Violation::TierMaxIdTooLow {
+                max_id: self.tier_max_id,
+                actual: max_tier,
+            });
+            self.tier_max_id = max_tier;
+        }
+
+        let mut item_ids = HashSet::new();
+        self.items.retain(|it| {
+            let first = item_ids.insert(it.id);
+            if !first {
+                violations.push(Violation::DuplicateItem { item: it.id });
+            }
+            first
+        });
+
+        let mut tier_ids = HashSet::new();
+        for tier in self.tiers.iter_mut() {
+            if !tier_ids.insert(tier.id) {
+                self.tier_max_id += 1;
+                violations.push(Violation::DuplicateTier {
+                    tier: tier.id,
+                    new_id: self.tier_max_id,
+                });
+                tier.id = self.tier_max_id;
+            }
+        }
+
+        let mut placed = HashSet::new();
+        let mut keep = |id: ItemId| {
+            if !item_ids.contains(&id) {
+                violations.push(Violation::MissingItem { item: id });
+                false
+            } else if !placed.insert(id) {
+                violations.push(Violation::ItemPlacedTwice { item: id });
+                false
+            } else {
+                true
+            }
+        };
+        for tier in self.tiers.iter_mut() {
+            tier.items.retain(|&id| keep(id));
+        }
+        self.items_pool.retain(|&id| keep(id));
+
+        for item in self.items.iter() {
+            if !placed.contains(&item.id) {
+                violations.push(Violation::UnplacedItem { item: item.id });
+                self.items_pool.push(item.id);
+            }
+        }
+
+        let max_field = self.fields.iter().map(|f| f.id).max().unwrap_or(0);
+        if self.field_max_id < max_field {
+            violations.push(Violation::FieldMaxIdTooLow {
+                max_id: self.field_max_id,
+                actual: max_field,
+            });
+            self.field_max_id = max_field;
+        }
+        let mut field_types = BTreeMap::new();
+        self.fields.retain(|f| {
+            let first = !field_types.contains_key(&f.id);
+            if first {
+                field_types.insert(f.id, f.field_type);
+            } else {
+                violations.push(Violation::DuplicateField { field: f.id });
+            }
+            first
+        });
+        for item in self.items.iter_mut() {
+            let item_id = item.id;
+            item.fields
+                .retain(|field, value| match field_types.get(field) {
+                    None => {
+                        violations.push(Violation::UnknownField {
+                            item: item_id,
+                            field: *field,
+                        });
+                        false
+                    }
+                    Some(field_type) => match field_type.normalize(value) {
+                        Ok(normalized) if !normalized.is_empty() => {
+                            *value = normalized;
+                            true
+                        }
+                        _ => {
+                            violations.push(Violation::InvalidFieldValue {
+                                item: item_id,
+                                field: *field,
+                            });
+                            false
+                        }
+                    },
+                });
+        }
+        violations
+    }
+
+    pub fn item(&self, id: ItemId) -> Option<&Item> {
+        self.items.iter().find(|it| it.id == id)
+    }
+
+    pub fn item_index(&self, id: ItemId) -> Result<usize, String> {
+        self.items
+            .iter()
+            .position(|it| it.id == id)
+            .ok_or_else(|| format!("Item {} not found", id))
+    }
+
+    pub fn tier_index(&self, id: TierId) -> Result<usize, String> {
+        self.tiers
+            .iter()
+            .position(|t| t.id == id)
+            .ok_or_else(|| format!("Tier {} not found", id))
+    }
+
+    // tier が None ならプール
+    fn place_mut(&mut self, tier: Option<TierId>) -> Result<&mut Vec<ItemId>, String> {
+        match tier {
+            Some(id) => {
+                let idx = self.tier_index(id)?;
+                Ok(&mut self.tiers[idx].items)
+            }
+            None => Ok(&mut self.items_pool),
+        }
+    }
+
+    // アイテムが置かれている tier (プールなら None) とその中の位置
+    pub fn find_item(&self, id: ItemId) -> Option<(Option<TierId>, usize)> {
+        if let Some(pos) = self.items_pool.iter().position(|&it| it == id) {
+            return Some((None, pos));
+        }
+        self.tiers.iter().find_map(|t| {
+            t.items
+                .iter()
+                .position(|&it| it == id)
+                .map(|pos| (Some(t.id), pos))
+        })
+    }
+
+    // 新しいアイテムはプールの末尾に置く
+    pub fn add_item(&mut self, data: ItemData) -> ItemId {
+        self.item_max_id += 1;
+        let id = self.item_max_id;
+        self.items.push(Item {
+            id,
+            name: data.name,
+            url: data.url,
+            thumb: data.thumb,
+            memo: data.memo,
+            tags: vec![],
+            fields: BTreeMap::new(),
+            times: ItemTimes::new(now()),
+        });
+        self.items_pool.push(id);
+        id
+    }
+
+    // 変更前のタグを返す。空のタグは捨て、名前順に並べて重複 (大文字小文字違いを含む) を除く
+    pub fn set_item_tags(&mut self, id: ItemId, tags: Vec<String>) -> Result<Vec<String>, String> {
+        let idx = self.item_index(id)?;
+        let mut tags: Vec<String> = tags
+            .into_iter()
+            .map(|tag| tag.trim().to_owned())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        // 安定ソートなので、大文字小文字違いは先に書かれたものが残る
+        tags.sort_by_key(|tag| tag.to_lowercase());
+        tags.dedup_by(|a, b| same_tag(a, b));
+        let item = &mut self.items[idx];
+        if item.tags != tags {
+            item.times.updated_at = now();
+        }
+        Ok(std::mem::replace(&mut item.tags, tags))
+    }
+
+    // tierlist 全体で使われているタグと、それが付いたアイテムの数 (名前順)
+    // 大文字小文字違いは 1 つのタグとして数え、最初のアイテムでの表記で返す
+    pub fn tag_counts(&self) -> Vec<TagCount> {
+        let mut counts: BTreeMap<String, TagCount> = BTreeMap::new();
+        for tag in self.items.iter().flat_map(|it| it.tags.iter()) {
+            counts
+                .entry(tag.to_lowercase())
+                .or_insert_with(|| TagCount {
+                    name: tag.clone(),
+                    count: 0,
+                })
+                .count += 1;
+        }
+        counts.into_values().collect()
+    }
+
+    pub fn update_item(&mut self, id: ItemId, data: ItemData) -> Result<(), String> {
+        let idx = self.item_index(id)?;
+        let item = &mut self.items[idx];
+        item.name = data.name;
+        item.url = data.url;
+        item.thumb = data.thumb;
+        item.memo = data.memo;
+        item.times.updated_at = now();
+        Ok(())
+    }
+
+    pub fn delete_item(&mut self, id: ItemId) -> Result<Item, String> {
+        let idx = self.item_index(id)?;
+        if let Some((tier, pos)) = self.find_item(id) {
+            self.place_mut(tier)?.remove(pos);
+        }
+        Ok(self.items.remove(idx))
+    }
+
+    // 削除したアイテムを元の id のまま tier (None ならプール) の index の位置に戻す
+    pub fn insert_item(
+        &mut self,
+        item: Item,
+        tier: Option<TierId>,
+        index: usize,
+    ) -> Result<(), String> {
+        if self.item(item.id).is_some() {
+            return Err(format!("Item {} already exists", item.id));
+        }
+        let items = self.place_mut(tier)?;
+        let index = index.min(items.len());
+        items.insert(index, item.id);
+        self.item_max_id = self.item_max_id.max(item.id);
+        // items は id 順に並んでいるので、元の位置に戻す
+        let pos = self
+            .items
+            .iter()
+            .position(|it| it.id > item.id)
+            .unwrap_or(self.items.len());
+        self.items.insert(pos, item);
+        Ok(())
+    }
+
+    // id のアイテムを tier (None ならプール) の index の位置に移す
+    // index が末尾より後ろなら末尾に置く
+    pub fn move_item(
+        &mut self,
+        id: ItemId,
+        tier: Option<TierId>,
+        index: usize,
+    ) -> Result<(), String> {
+        let idx = self.item_index(id)?;
+        self.place_mut(tier)?;
+        let from = self.find_item(id);
+        if let Some((from, pos)) = from {
+            self.place_mut(from)?.remove(pos);
+        }
+        let items = self.place_mut(tier)?;
+        let index = index.min(items.len());
+        items.insert(index, id);
+        // 同じ tier の中での並べ替えは移したことにしない
+        if from.map(|(from, _)| from) != Some(tier) {
+            self.items[idx].times.moved_at = now();
+        }
+        Ok(())
+    }
+
+    // tier (None ならプール) のアイテムを order の順に並べ替え、変更前の順を返す
+    // order はそこに置かれているアイテムの id をちょうど 1 回ずつ含んでいなければならない
+    pub fn reorder_items(
+        &mut self,
+        tier: Option<TierId>,
+        order: Vec<ItemId>,
+    ) -> Result<Vec<ItemId>, String> {
+        let items = self.place_mut(tier)?;
+        let mut sorted = order.clone();
+        sorted.sort_unstable();
+        let mut ids = items.clone();
+        ids.sort_unstable();
+        if sorted != ids {
+            return Err("Item order does not match the current items".to_owned());
+        }
+        Ok(std::mem::replace(items, order))
+    }
+
+    pub fn add_tier(&mut self, title: &str) -> TierId {
+        self.tier_max_id += 1;
+        self.tiers.push(Tier {
+            id: self.tier_max_id,
+            title: title.to_owned(),
+            items: vec![],
+            color: palette_color(self.tiers.len()),
+            text_color: None,
+            icon: None,
+        });
+        self.tier_max_id
+    }
+
+    // 変更前の見た目を返す
+    pub fn set_tier_style(&mut self, id: TierId, style: TierStyle) -> Result<TierStyle, String> {
+        for color in Some(&style.color)
+            .into_iter()
+            .chain(style.text_color.as_ref())
+        {
+            if parse_color(color).is_none() {
+                return Err(format!("Invalid color: {}", color));
+            }
+        }
+        let idx = self.tier_index(id)?;
+        let tier = &mut self.tiers[idx];
+        let old = TierStyle {
+            color: std::mem::replace(&mut tier.color, style.color),
+            text_color: std::mem::replace(&mut tier.text_color, style.text_color),
+            icon: std::mem::replace(&mut tier.icon, style.icon),
+        };
+        Ok(old)
+    }
+
+    pub fn field_index(&self, id: FieldId) -> Result<usize, String> {
+        self.fields
+            .iter()
+            .position(|f| f.id == id)
+            .ok_or_else(|| format!("Field {} not found", id))
+    }
+
+    fn check_field_name(&self, name: &str, except: Option<FieldId>) -> Result<(), String> {
+        if name.trim().is_empty() {
+            return Err("Field name is empty".to_owned());
+        }
+        let taken = self
+            .fields
+            .iter()
+            .any(|f| Some(f.id) != except && f.name.eq_ignore_ascii_case(name.trim()));
+        if taken {
+            return Err(format!("Field {} already exists", name.trim()));
+        }
+        Ok(())
+    }
+
+    // 名前は CSV の列と対応付けるので、大文字小文字を除いて重複させない
+    pub fn add_field(&mut self, name: &str, field_type: FieldType) -> Result<FieldId, String> {
+        self.check_field_name(name, None)?;
+        self.field_max_id += 1;
+        self.fields.push(FieldDef {
+            id: self.field_max_id,
+            name: name.trim().to_owned(),
+            field_type,
+        });
+        Ok(self.field_max_id)
+    }
+
+    // 変更前の名前を返す
+    pub fn rename_field(&mut self, id: FieldId, name: &str) -> Result<String, String> {
+        let idx = self.field_index(id)?;
+        self.check_field_name(name, Some(id))?;
+        Ok(std::mem::replace(
+            &mut self.fields[idx].name,
+            name.trim().to_owned(),
+        ))
+    }
+
+    // 定義とアイテムに付いていた値を返す
+    pub fn delete_field(
+        &mut self,
+        id: FieldId,
+    ) -> Result<(FieldDef, Vec<(ItemId, String)>), String> {
+        let idx = self.field_index(id)?;
+        let field = self.fields.remove(idx);
+        let values = self
+            .items
+            .iter_mut()
+            .filter_map(|it| it.fields.remove(&id).map(|value| (it.id, value)))
+            .collect();
+        Ok((field, values))
+    }
+
+    // 削除した定義を index の位置に戻し、値も戻す
+    pub fn insert_field(
+        &mut self,
+        field: FieldDef,
+        index: usize,
+        values: Vec<(ItemId, String)>,
+    ) -> Result<(), String> {
+        if self.field_index(field.id).is_ok() {
+            return Err(format!("Field {} already exists", field.id));
+        }
+        for (item, _) in values.iter() {
+            self.item_index(*item)?;
+        }
+        let id = field.id;
+        let index = index.min(self.fields.len());
+        self.field_max_id = self.field_max_id.max(id);
+        self.fields.insert(index, field);
+        for (item, value) in values {
+            let idx = self.item_index(item)?;
+            self.items[idx].fields.insert(id, value);
+        }
+        Ok(())
+    }
+
+    // 型に合わない値はエラー。None か空なら値を消す。変更前の値を返す
+    pub fn set_item_field(
+        &mut self,
+        item: ItemId,
+        field: FieldId,
+        value: Option<String>,
+    ) -> Result<Option<String>, String> {
+        let idx = self.item_index(item)?;
+        let field_type = self.fields[self.field_index(field)?].field_type;
+        let value = match value {
+            Some(value) if !value.trim().is_empty() => Some(field_type.normalize(&value)?),
+            _ => None,
+        };
+        let item = &mut self.items[idx];
+        if item.fields.get(&field) != value.as_ref() {
+            item.times.updated_at = now();
+        }
+        Ok(match value {
+            Some(value) => item.fields.insert(field, value),
+            None => item.fields.remove(&field),
+        })
+    }
+
+    pub fn rename_tier(&mut self, id: TierId, title: &str) -> Result<(), String> {
+        let idx = self.tier_index(id)?;
+        self.tiers[idx].title = title.to_owned();
+        Ok(())
+    }
+
+    // order は今ある tier の id をちょうど 1 回ずつ含んでいなければならない
+    pub fn reorder_tiers(&mut self, order: &[TierId]) -> Result<(), String> {
+        let mut sorted = order.to_vec();
+        sorted.sort_unstable();
+        let mut ids: Vec<TierId> = self.tiers.iter().map(|t| t.id).collect();
+        ids.sort_unstable();
+        if sorted != ids {
+            return Err("Tier order does not match the current tiers".to_owned());
+        }
+        let mut tiers = std::mem::take(&mut self.tiers);
+        for id in order {
+            let idx = tiers.iter().position(|t| t.id == *id).unwrap();
+            self.tiers.push(tiers.remove(idx));
+        }
+        Ok(())
+    }
+
+    // tier に置かれていたアイテムはプールの末尾に戻す
+    pub fn delete_tier(&mut self, id: TierId) -> Result<Tier, String> {
+        let idx = self.tier_index(id)?;
+        let tier = self.tiers.remove(idx);
+        self.items_pool.extend(tier.items.iter().copied());
+        let at = now();
+        for item in self.items.iter_mut() {
+            if tier.items.contains(&item.id) {
+                item.times.moved_at = at;
+            }
+        }
+        Ok(tier)
+    }
+
+    // 削除した tier を index の位置に戻す。tier のアイテムは今ある場所から取り除く
+    pub fn insert_tier(&mut self, tier: Tier, index: usize) -> Result<(), String> {
+        if self.tier_index(tier.id).is_ok() {
+            return Err(format!("Tier {} already exists", tier.id));
+        }
+        for &id in tier.items.iter() {
+            self.item_index(id)?;
+        }
+        for &id in tier.items.iter() {
+            if let Some((from, pos)) = self.find_item(id) {
+                self.place_mut(from)?.remove(pos);
+            }
+        }
+        let index = index.min(self.tiers.len());
+        self.tier_max_id = self.tier_max_id.max(tier.id);
+        self.tiers.insert(index, tier);
+        Ok(())
+    }
+}
+
+pub mod commands {
+    use tauri::{async_runtime::Mutex, State};
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::{
+        history::{History, Op},
+        thumbnail::{self, ThumbnailOptions},
+    };
+
+    // op を履歴に記録しながら適用して、更新後の状態を返す
+    pub(crate) async fn apply(
+        tierlist: &Mutex<TierList>,
+        history: &Mutex<History>,
+        op: Op,
+    ) -> Result<TierList, String> {
+        let mut tierlist = tierlist.lock().await;
+        history.lock().await.apply(&mut tierlist, op)?;
+        Ok(tierlist.clone())
+    }
+
+    #[tauri::command]
+    pub async fn get_tierlist(tierlist: State<'_, Mutex<TierList>>) -> Result<TierList, String> {
+        Ok(tierlist.lock().await.clone())
+    }
+
+    #[tauri::command]
+    pub async fn validate_tierlist(
+        tierlist: State<'_, Mutex<TierList>>,
+    ) -> Result<Vec<Violation>, String> {
+        Ok(tierlist.lock().await.validate())
+    }
+
+    #[tauri::command]
+    pub async fn new_tierlist(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        cur_list: State<'_, Mutex<Option<ListId>>>,
+    ) -> Result<TierList, String> {
+        let mut tierlist = tierlist.lock().await;
+        *tierlist = TierList::with_default_tiers();
+        history.lock().await.clear();
+        *cur_list.lock().await = None;
+        Ok(tierlist.clone())
+    }
+
+    #[tauri::command]
+    pub async fn set_tierlist_title(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        title: String,
+    ) -> Result<TierList, String> {
+        apply(&tierlist, &history, Op::SetTitle { title }).await
+    }
+
+    #[tauri::command]
+    pub async fn add_item(
+        img_dir: State<'_, TempDir>,
+        thumb_options: State<'_, Mutex<ThumbnailOptions>>,
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        mut item: ItemData,
+    ) -> Result<TierList, String> {
+        let options = thumb_options.lock().await.clone();
+        item.thumb = thumbnail::import_if_outside(item.thumb, img_dir.path(), &options).await?;
+        apply(&tierlist, &history, Op::AddItem { data: item }).await
+    }
+
+    #[tauri::command]
+    pub async fn update_item(
+        img_dir: State<'_, TempDir>,
+        thumb_options: State<'_, Mutex<ThumbnailOptions>>,
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        id: ItemId,
+        mut item: ItemData,
+    ) -> Result<TierList, String> {
+        let options = thumb_options.lock().await.clone();
+        item.thumb = thumbnail::import_if_outside(item.thumb, img_dir.path(), &options).await?;
+        apply(&tierlist, &history, Op::UpdateItem { id, data: item }).await
+    }
+
+    #[tauri::command]
+    pub async fn delete_item(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        id: ItemId,
+    ) -> Result<TierList, String> {
+        apply(&tierlist, &history, Op::DeleteItem { id }).await
+    }
+
+    #[tauri::command]
+    pub async fn move_item(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        id: ItemId,
+        tier: Option<TierId>,
+        index: usize,
+    ) -> Result<TierList, String> {
+        apply(&tierlist, &history, Op::MoveItem { id, tier, index }).await
+    }
+
+    #[tauri::command]
+    pub async fn add_tier(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        title: String,
+    ) -> Result<TierList, String> {
+        apply(&tierlist, &history, Op::AddTier { title }).await
+    }
+
+    #[tauri::command]
+    pub async fn rename_tier(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        id: TierId,
+        title: String,
+    ) -> Result<TierList, String> {
+        apply(&tierlist, &history, Op::RenameTier { id, title }).await
+    }
+
+    #[tauri::command]
+    pub async fn set_tier_style(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        id: TierId,
+        style: TierStyle,
+    ) -> Result<TierList, String> {
+        apply(&tierlist, &history, Op::SetTierStyle { id, style }).await
+    }
+
+    #[tauri::command]
+    pub async fn reorder_tiers(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        order: Vec<TierId>,
+    ) -> Result<TierList, String> {
+        apply(&tierlist, &history, Op::ReorderTiers { order }).await
+    }
+
+    #[tauri::command]
+    pub async fn delete_tier(
+        tierlist: State<'_, Mutex<TierList>>,
+        history: State<'_, Mutex<History>>,
+        id: TierId,
+    ) -> Result<TierList, String> {
+        apply(&tierlist, &history, Op::DeleteTier { id }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(name: &str) -> ItemData {
+        ItemData {
+            name: name.to_owned(),
+            url: String::new(),
+            thumb: None,
+            memo: String::new(),
+        }
+    }
+
+    fn sample() -> TierList {
+        let mut tierlist = TierList::empty();
+        let s = tierlist.add_tier("S");
+        let a = tierlist.add_tier("A");
+        for name in ["1", "2", "3", "4"] {
+            tierlist.add_item(data(name));
+        }
+        tierlist.move_item(1, Some(s), 0).unwrap();
+        tierlist.move_item(2, Some(s), 1).unwrap();
+        tierlist.move_item(3, Some(a), 0).unwrap();
+        tierlist
+    }
+
+    #[test]
+    fn valid_list_ok() {
+        assert!(sample().validate().is_empty());
+        assert!(TierList::empty().validate().is_empty());
+    }
+
+    #[test]
+    fn repair_violations() {
+        let mut tierlist = sample();
+        tierlist.items.push(Item {
+            id: 2,
+            name: "dup".to_owned(),
+            url: String::new(),
+            thumb: None,
+            memo: String::new(),
+            tags: vec![],
+            fields: Default::default(),
+            times: Default::default(),
+        });
+        tierlist.items.push(Item {
+            id: 7,
+            name: "7".to_owned(),
+            url: String::new(),
+            thumb: None,
+            memo: String::new(),
+            tags: vec![],
+            fields: Default::default(),
+            times: Default::default(),
+        });
+        tierlist.tiers.push(Tier {
+            id: 1,
+            title: "S again".to_owned(),
+            items: vec![3, 9],
+            color: "#ff7f7f".to_owned(),
+            text_color: None,
+            icon: None,
+        });
+        tierlist.items_pool.push(1);
+
+        let violations = tierlist.validate();
+        assert_eq!(
+            violations,
+            vec![
+                Violation::ItemMaxIdTooLow {
+                    max_id: 4,
+                    actual: 7
+                },
+                Violation::DuplicateItem { item: 2 },
+                Violation::DuplicateTier { tier: 1, new_id: 3 },
+                Violation::ItemPlacedTwice { item: 3 },
+                Violation::MissingItem { item: 9 },
+                Violation::ItemPlacedTwice { item: 1 },
+                Violation::UnplacedItem { item: 7 },
+            ]
+        );
+
+        assert_eq!(tierlist.repair(), violations);
+        assert!(tierlist.validate().is_empty());
+        assert_eq!(tierlist.item(2).unwrap().name, "2");
+        assert_eq!(tierlist.tiers[2].id, 3);
+        assert!(tierlist.tiers[2].items.is_empty());
+        assert_eq!(tierlist.items_pool, vec![4, 7]);
+        assert_eq!(tierlist.item_max_id, 7);
+        assert_eq!(tierlist.tier_max_id, 3);
+    }
+
+    #[test]
+    fn item_fields() {
+        let mut tierlist = sample();
+        let year = tierlist.add_field("Year", FieldType::Number).unwrap();
+        let watched = tierlist.add_field("watched", FieldType::Date).unwrap();
+        assert!(tierlist.add_field("year", FieldType::Text).is_err());
+        assert!(tierlist.add_field(" ", FieldType::Text).is_err());
+        assert!(tierlist.rename_field(watched, "YEAR").is_err());
+        assert_eq!(tierlist.rename_field(year, "Released").unwrap(), "Year");
+
+        assert_eq!(
+            tierlist.set_item_field(1, year, Some("2022.0".to_owned())),
+            Ok(None)
+        );
+        assert_eq!(tierlist.item(1).unwrap().fields[&year], "2022");
+        assert!(tierlist
+            .set_item_field(1, watched, Some("yesterday".to_owned()))
+            .is_err());
+        assert!(tierlist.set_item_field(1, 9, Some("x".to_owned())).is_err());
+        tierlist
+            .set_item_field(2, watched, Some("2022/10/1".to_owned()))
+            .unwrap();
+        assert_eq!(
+            tierlist.set_item_field(2, watched, Some(" ".to_owned())),
+            Ok(Some("2022-10-01".to_owned()))
+        );
+        assert!(tierlist.item(2).unwrap().fields.is_empty());
+
+        let (field, values) = tierlist.delete_field(year).unwrap();
+        assert_eq!(values, vec![(1, "2022".to_owned())]);
+        assert!(tierlist.item(1).unwrap().fields.is_empty());
+        tierlist.insert_field(field, 0, values).unwrap();
+        assert_eq!(tierlist.fields[0].id, year);
+        assert_eq!(tierlist.item(1).unwrap().fields[&year], "2022");
+
+        // 定義のない値と型に合わない値は repair で捨てる
+        tierlist.items[0].fields.insert(7, "x".to_owned());
+        tierlist.items[1].fields.insert(year, "many".to_owned());
+        tierlist.fields.push(tierlist.fields[1].clone());
+        assert_eq!(
+            tierlist.repair(),
+            vec![
+                Violation::DuplicateField { field: watched },
+                Violation::UnknownField { item: 1, field: 7 },
+                Violation::InvalidFieldValue {
+                    item: 2,
+                    field: year
+                },
+            ]
+        );
+        assert_eq!(tierlist.fields.len(), 2);
+        assert_eq!(tierlist.item(1).unwrap().fields.len(), 1);
+        assert!(tierlist.item(2).unwrap().fields.is_empty());
+    }
+
+    #[test]
+    fn add_and_update_item() {
+        let mut tierlist = sample();
+        assert_eq!(tierlist.add_item(data("5")), 5);
+        assert_eq!(tierlist.items_pool, vec![4, 5]);
+        tierlist.update_item(5, data("five")).unwrap();
+        assert_eq!(tierlist.item(5).unwrap().name, "five");
+        assert!(tierlist.update_item(6, data("six")).is_err());
+    }
+
+    #[test]
+    fn move_item_between_places() {
+        let mut tierlist = sample();
+        tierlist.move_item(4, Some(1), 1).unwrap();
+        assert_eq!(tierlist.tiers[0].items, vec![1, 4, 2]);
+        assert!(tierlist.items_pool.is_empty());
+
+        // 同じ tier の中での並べ替え
+        tierlist.move_item(1, Some(1), 2).unwrap();
+        assert_eq!(tierlist.tiers[0].items, vec![4, 2, 1]);
+
+        tierlist.move_item(2, None, 100).unwrap();
+        assert_eq!(tierlist.items_pool, vec![2]);
+        assert_eq!(tierlist.find_item(2), Some((None, 0)));
+
+        assert!(tierlist.move_item(9, None, 0).is_err());
+        assert!(tierlist.move_item(1, Some(9), 0).is_err());
+        assert_eq!(tierlist.find_item(1), Some((Some(1), 1)));
+    }
+
+    #[test]
+    fn delete_item_and_tier() {
+        let mut tierlist = sample();
+        tierlist.delete_item(2).unwrap();
+        assert_eq!(tierlist.tiers[0].items, vec![1]);
+        assert!(tierlist.item(2).is_none());
+
+        tierlist.delete_tier(1).unwrap();
+        assert_eq!(tierlist.tiers.len(), 1);
+        assert_eq!(tierlist.items_pool, vec![4, 1]);
+        assert!(tierlist.delete_tier(1).is_err());
+    }
+
+    #[test]
+    fn insert_deleted_item_and_tier() {
+        let mut tierlist = sample();
+        let item = tierlist.delete_item(2).unwrap();
+        tierlist.insert_item(item.clone(), Some(1), 1).unwrap();
+        assert_eq!(tierlist.tiers[0].items, vec![1, 2]);
+        assert!(tierlist.insert_item(item, None, 0).is_err());
+
+        let tier = tierlist.delete_tier(1).unwrap();
+        assert_eq!(tierlist.items_pool, vec![4, 1, 2]);
+        tierlist.insert_tier(tier, 0).unwrap();
+        assert_eq!(tierlist.tiers[0].items, vec![1, 2]);
+        assert_eq!(tierlist.items_pool, vec![4]);
+    }
+
+    #[test]
+    fn tier_style() {
+        let mut tierlist = TierList::with_default_tiers();
+        let colors: Vec<&str> = tierlist.tiers.iter().map(|t| t.color.as_str()).collect();
+        assert_eq!(colors, TIER_PALETTE[..5].to_vec());
+
+        let style = TierStyle {
+            color: "#123ABC".to_owned(),
+            text_color: Some("#ffffff".to_owned()),
+            icon: Some("🔥".to_owned()),
+        };
+        let old = tierlist.set_tier_style(1, style.clone()).unwrap();
+        assert_eq!(old.color, TIER_PALETTE[0]);
+        assert_eq!(tierlist.tiers[0].icon.as_deref(), Some("🔥"));
+        assert_eq!(
+            parse_color(&tierlist.tiers[0].color),
+            Some([0x12, 0x3a, 0xbc])
+        );
+
+        for color in ["red", "#12345", "#12345g"] {
+            let invalid = TierStyle {
+                color: color.to_owned(),
+                ..style.clone()
+            };
+            assert!(tierlist.set_tier_style(1, invalid).is_err());
+        }
+        assert!(tierlist.set_tier_style(9, style).is_err());
+    }
+
+    #[test]
+    fn reorder_and_rename_tiers() {
+        let mut tierlist = sample();
+        let b = tierlist.add_tier("B");
+        tierlist.reorder_tiers(&[b, 1, 2]).unwrap();
+        let titles: Vec<&str> = tierlist.tiers.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["B", "S", "A"]);
+        assert!(tierlist.reorder_tiers(&[1, 2]).is_err());
+        assert!(tierlist.reorder_tiers(&[1, 1, 2]).is_err());
+
+        tierlist.rename_tier(2, "A+").unwrap();
+        assert_eq!(tierlist.tiers[2].title, "A+");
+    }
+
+    #[test]
+    fn item_times() {
+        let mut tierlist = sample();
+        let old = ItemTimes::new(1);
+        for item in tierlist.items.iter_mut() {
+            item.times = old;
+        }
+        let s = tierlist.tiers[0].id;
+        let a = tierlist.tiers[1].id;
+
+        // 同じ tier の中で並べ替えても移したことにはならない
+        tierlist.move_item(1, Some(s), 1).unwrap();
+        assert_eq!(tierlist.item(1).unwrap().times, old);
+        tierlist.move_item(1, Some(a), 0).unwrap();
+        let times = tierlist.item(1).unwrap().times;
+        assert!(times.moved_at > 1);
+        assert_eq!((times.created_at, times.updated_at), (1, 1));
+
+        tierlist.set_item_tags(2, vec![]).unwrap();
+        assert_eq!(tierlist.item(2).unwrap().times, old);
+        tierlist.update_item(2, data("two")).unwrap();
+        assert!(tierlist.item(2).unwrap().times.updated_at > 1);
+        assert_eq!(tierlist.item(2).unwrap().times.moved_at, 1);
+
+        tierlist.delete_tier(a).unwrap();
+        assert!(tierlist.item(3).unwrap().times.moved_at > 1);
+        assert_eq!(tierlist.item(4).unwrap().times, old);
+
+        let id = tierlist.add_item(data("5"));
+        let times = tierlist.item(id).unwrap().times;
+        assert!(times.created_at > 1);
+        assert_eq!(times, ItemTimes::new(times.created_at));
+    }
 }
@@ -9,12 +9,12 @@ import {
   useSensor,
   useSensors,
 } from "@dnd-kit/core";
-import { arrayMove, sortableKeyboardCoordinates } from "@dnd-kit/sortable";
+import { sortableKeyboardCoordinates } from "@dnd-kit/sortable";
 import "@fontsource/roboto/300.css";
 import "@fontsource/roboto/400.css";
 import "@fontsource/roboto/500.css";
 import "@fontsource/roboto/700.css";
-import { FolderOpenOutlined, Save } from "@mui/icons-material";
+import { FolderOpenOutlined, Redo, Save, Undo } from "@mui/icons-material";
 import {
   Box,
   Card,
@@ -24,8 +24,9 @@ import {
   Unstable_Grid2 as Grid,
 } from "@mui/material";
 import { styled } from "@mui/material/styles";
+import { confirm } from "@tauri-apps/api/dialog";
 import { invoke } from "@tauri-apps/api/tauri";
-import { useCallback, useState } from "react";
+import { useCallback, useEffect, useState } from "react";
 import { createPortal } from "react-dom";
 import "./App.css";
 import { KeyboardSensor, MouseSensor } from "./CustomSensor";
@@ -35,12 +36,16 @@ import Tierlist from "./Tierlist";
 import {
   BackendTierlist,
   fromBackendTierlist,
+  HistoryStatus,
   Item,
   ItemData,
   ItemList,
   ItemPool,
+  OpenedDbFile,
+  RecoveryInfo,
   Tier,
-  toBackendTierlist,
+  TierStyle,
+  TierlistInfo,
 } from "./TierlistData";
 
 const Pane = styled(Paper)(({ theme }) => ({
@@ -71,22 +76,20 @@ const App: React.FC = (_) => {
   const [activeId, setActiveId] = useState<UniqueIdentifier | null>(null);
 
   const [listTitle, setListTitle] = useState<string>("Untitled Tierlist");
-  const handleListTitleChange = (text: string) => {
-    setListTitle(text);
+  const handleListTitleChange = async (text: string) => {
+    applyTierlist(
+      await invoke<BackendTierlist>("set_tierlist_title", { title: text })
+    );
   };
 
+  const [dbPath, setDbPath] = useState<string | null>(null);
+  const [listId, setListId] = useState(1);
+
   const [pool, setPool] = useState<ItemPool>(new ItemPool([]));
   const [tiers, setTiers] = useState<Tier[]>([]);
 
-  const saveTierlist = async () => {
-    // TODO: Error notification
-    await invoke("write_tierlist_to_db", {
-      tierlist: toBackendTierlist(listTitle, pool, tiers),
-    });
-  };
-
-  const openTierlist = async () => {
-    const tierlist = await invoke<BackendTierlist>("read_tierlist_from_db");
+  // The backend owns the tierlist; every change comes back as the new state
+  const applyTierlist = (tierlist: BackendTierlist) => {
     const {
       title: newTitle,
       pool: newPool,
@@ -95,143 +98,177 @@ const App: React.FC = (_) => {
     setListTitle(newTitle);
     setPool(newPool);
     setTiers(newTiers);
+    invoke<HistoryStatus>("get_history_status").then(setHistoryStatus);
+  };
 
-    let maxItemId = Math.max(...newPool.items.map((it) => it.id));
-    newTiers.forEach((t) => {
-      maxItemId = Math.max(maxItemId, ...t.items.map((it) => it.id));
-    });
-    setNewItemId(maxItemId + 1);
-    setNewTierId(Math.max(...tiers.map((it) => it.numericId)) + 1);
+  const [historyStatus, setHistoryStatus] = useState<HistoryStatus>({
+    canUndo: false,
+    canRedo: false,
+  });
+  const undo = async () => {
+    applyTierlist(await invoke<BackendTierlist>("undo"));
   };
+  const redo = async () => {
+    applyTierlist(await invoke<BackendTierlist>("redo"));
+  };
+
+  useEffect(() => {
+    const handleKeyDown = (e: KeyboardEvent) => {
+      if (!(e.ctrlKey || e.metaKey)) {
+        return;
+      }
+      const key = e.key.toLowerCase();
+      if (key === "z" && !e.shiftKey) {
+        e.preventDefault();
+        undo().catch((e) => console.log(e));
+      } else if (key === "y" || (key === "z" && e.shiftKey)) {
+        e.preventDefault();
+        redo().catch((e) => console.log(e));
+      }
+    };
+    window.addEventListener("keydown", handleKeyDown);
+    return () => window.removeEventListener("keydown", handleKeyDown);
+  }, []);
 
-  const [newTierId, setNewTierId] = useState(1);
-  const handleTierAdd = () => {
-    setTiers((prev) => {
-      return [...prev, new Tier(newTierId, "Untitled Tier", [])];
+  const saveTierlist = async () => {
+    // TODO: Error notification
+    if (dbPath !== null) {
+      await invoke("write_tierlist_to_db", { listId });
+      return;
+    }
+    const path = await invoke<string | null>("pick_db_file_to_save");
+    if (path === null) {
+      return;
+    }
+    const newListId = await invoke<number>("save_tierlist_as_db_file", {
+      path,
     });
-    setNewTierId(newTierId + 1);
+    setDbPath(path);
+    setListId(newListId);
   };
 
-  const handleTierMove = (id: string, direction: "up" | "down") => {
-    const tierIdx = tiers.findIndex((t) => t.id === id);
-    const nextIdx = tierIdx + (direction === "up" ? -1 : +1);
-    console.log(`tier move: ${id}, ${direction}, ${tierIdx}, ${nextIdx}`);
-    if (0 <= nextIdx && nextIdx < tiers.length) {
-      setTiers((prev) => {
-        let ts = [...prev];
-        [ts[tierIdx], ts[nextIdx]] = [ts[nextIdx], ts[tierIdx]];
-        return ts;
-      });
+  const openTierlist = async () => {
+    const path = await invoke<string | null>("pick_db_file_to_open");
+    if (path === null) {
+      return;
+    }
+    const lists = await invoke<TierlistInfo[]>("open_db_file", { path });
+    setDbPath(path);
+    if (lists.length === 0) {
+      return;
     }
+    // TODO: Let the user choose which list to open
+    await loadTierlist(lists[0].id);
   };
 
-  const handleTierDelete = (id: string) => {
-    setPool((prev) => {
-      const tier = tiers.find((t) => t.id === id);
-      if (!tier) {
-        return prev;
-      } else {
-        return { ...prev, items: [...prev.items, ...tier.items] };
+  useEffect(() => {
+    const reopenLastFile = async () => {
+      const recovery = await invoke<RecoveryInfo | null>("check_recovery");
+      if (recovery !== null) {
+        const savedAt = new Date(recovery.savedAt * 1000).toLocaleString();
+        const restore = await confirm(
+          `"${recovery.title}" has unsaved changes from ${savedAt}. Restore them?`,
+          "Recover unsaved changes"
+        );
+        if (restore) {
+          setDbPath(null);
+          applyTierlist(await invoke<BackendTierlist>("restore_recovery"));
+          return;
+        }
+        await invoke("discard_recovery");
       }
-    });
-    setTiers((prev) => {
-      return prev.filter((t) => t.id !== id);
-    });
-  };
+      const opened = await invoke<OpenedDbFile | null>("reopen_last_file");
+      if (opened === null) {
+        return;
+      }
+      setDbPath(opened.path);
+      if (opened.listId !== null) {
+        await loadTierlist(opened.listId);
+      }
+    };
+    reopenLastFile().catch((e) => console.log(e));
+  }, []);
 
-  const handleTierTitleChange = (id: string, title: string) => {
-    setTiers((prev) => {
-      return prev.map((t) => {
-        if (t.id === id) {
-          return { ...t, title: title } as Tier;
-        } else {
-          return t;
-        }
-      });
-    });
+  const loadTierlist = async (newListId: number) => {
+    setListId(newListId);
+    applyTierlist(
+      await invoke<BackendTierlist>("read_tierlist_from_db", {
+        listId: newListId,
+      })
+    );
   };
 
-  const [newItemId, setNewItemId] = useState(1);
-  const handleAddNewItem = (itemData: ItemData) => {
-    const item = new Item(
-      newItemId,
-      itemData.name,
-      itemData.url,
-      itemData.thumb ?? "",
-      itemData.memo
+  const handleTierAdd = async () => {
+    applyTierlist(
+      await invoke<BackendTierlist>("add_tier", { title: "Untitled Tier" })
     );
-    setNewItemId(newItemId + 1);
-    setPool((prev) => {
-      return {
-        ...prev,
-        items: [...prev.items, item],
-      };
-    });
   };
 
-  const handleDeleteItem = (id: number) => {
-    const list = findContainingList(id);
-    if (!list) {
-      return;
+  const handleTierMove = (id: string, direction: "up" | "down") => {
+    const tierIdx = tiers.findIndex((t) => t.id === id);
+    const nextIdx = tierIdx + (direction === "up" ? -1 : +1);
+    console.log(`tier move: ${id}, ${direction}, ${tierIdx}, ${nextIdx}`);
+    if (0 <= nextIdx && nextIdx < tiers.length) {
+      const order = tiers.map((t) => t.numericId);
+      [order[tierIdx], order[nextIdx]] = [order[nextIdx], order[tierIdx]];
+      invoke<BackendTierlist>("reorder_tiers", { order }).then(applyTierlist);
     }
-    if (list.id === "pool") {
-      setPool((prev) => {
-        return { ...prev, items: prev.items.filter((it) => it.id !== id) };
-      });
-    } else {
-      setTiers((prev) => {
-        return prev.map((t) => {
-          if (t.id === list.id) {
-            return {
-              ...t,
-              items: t.items.filter((it) => it.id !== id),
-            } as Tier;
-          } else {
-            return t;
-          }
-        });
-      });
+  };
+
+  const handleTierDelete = async (id: string) => {
+    const tier = tiers.find((t) => t.id === id);
+    if (!tier) {
+      return;
     }
+    applyTierlist(
+      await invoke<BackendTierlist>("delete_tier", { id: tier.numericId })
+    );
   };
 
-  const handleEditItem = (item: Item) => {
-    const list = findContainingList(item.id);
-    if (!list) {
+  const handleTierTitleChange = async (id: string, title: string) => {
+    const tier = tiers.find((t) => t.id === id);
+    if (!tier) {
       return;
     }
-    if (list.id === "pool") {
-      setPool((prev) => {
-        return {
-          ...prev,
-          items: prev.items.map((it) => {
-            if (it.id === item.id) {
-              return item;
-            } else {
-              return it;
-            }
-          }),
-        };
-      });
-    } else {
-      setTiers((prev) => {
-        return prev.map((t) => {
-          if (t.id === list.id) {
-            return {
-              ...t,
-              items: t.items.map((it) => {
-                if (it.id === item.id) {
-                  return item;
-                } else {
-                  return it;
-                }
-              }),
-            } as Tier;
-          } else {
-            return t;
-          }
-        });
-      });
+    applyTierlist(
+      await invoke<BackendTierlist>("rename_tier", {
+        id: tier.numericId,
+        title,
+      })
+    );
+  };
+
+  const handleTierStyleChange = async (id: string, style: TierStyle) => {
+    const tier = tiers.find((t) => t.id === id);
+    if (!tier) {
+      return;
     }
+    applyTierlist(
+      await invoke<BackendTierlist>("set_tier_style", {
+        id: tier.numericId,
+        style,
+      })
+    );
+  };
+
+  const handleAddNewItem = async (itemData: ItemData) => {
+    applyTierlist(
+      await invoke<BackendTierlist>("add_item", { item: itemData })
+    );
+  };
+
+  const handleDeleteItem = async (id: number) => {
+    applyTierlist(await invoke<BackendTierlist>("delete_item", { id }));
+  };
+
+  const handleEditItem = async (item: Item) => {
+    const { name, url, thumb, memo } = item;
+    applyTierlist(
+      await invoke<BackendTierlist>("update_item", {
+        id: item.id,
+        item: { name, url, thumb, memo },
+      })
+    );
   };
 
   const sensors = useSensors(
@@ -316,34 +353,23 @@ const App: React.FC = (_) => {
       setActiveId(null);
 
       if (!activeList || !overList || activeList !== overList) {
+        // Drop the preview and go back to the backend state
+        invoke<BackendTierlist>("get_tierlist").then(applyTierlist);
         return;
       }
 
+      // Cross-list moves were already previewed in handleDragOver;
+      // commit the final position to the backend
       const activeIdx = activeList.items.findIndex((it) => it.id == activeId);
       const overIdx = overList.items.findIndex((it) => it.id == overId);
-      if (activeIdx !== overIdx) {
-        if (activeList.id === "pool") {
-          setPool((prev) => {
-            return {
-              id: prev.id,
-              items: arrayMove(overList.items, activeIdx, overIdx),
-            };
-          });
-        } else {
-          setTiers((prev) => {
-            return prev.map((t) => {
-              if (t.id === activeList.id) {
-                return {
-                  ...t,
-                  items: arrayMove(overList.items, activeIdx, overIdx),
-                } as Tier;
-              } else {
-                return t;
-              }
-            });
-          });
-        }
-      }
+      const index = overIdx >= 0 ? overIdx : activeIdx;
+      const tier =
+        activeList.id === "pool"
+          ? null
+          : tiers.find((t) => t.id === activeList.id)?.numericId ?? null;
+      invoke<BackendTierlist>("move_item", { id: activeId, tier, index })
+        .then(applyTierlist)
+        .catch((e) => console.log(e));
     },
     [pool, tiers]
   );
@@ -366,6 +392,7 @@ const App: React.FC = (_) => {
                 activeId={activeId}
                 onListTitleChange={handleListTitleChange}
                 onTierTitleChange={handleTierTitleChange}
+                onTierStyleChange={handleTierStyleChange}
                 onTierAdd={handleTierAdd}
                 onTierMove={handleTierMove}
                 onTierDelete={handleTierDelete}
@@ -391,6 +418,26 @@ const App: React.FC = (_) => {
                 >
                   <Save color="primary" sx={{ fontSize: 32 }} />
                 </IconButton>
+                <IconButton
+                  sx={{
+                    border: "1px solid #aaa",
+                    margin: "2px",
+                  }}
+                  disabled={!historyStatus.canUndo}
+                  onClick={() => undo()}
+                >
+                  <Undo color="primary" sx={{ fontSize: 32 }} />
+                </IconButton>
+                <IconButton
+                  sx={{
+                    border: "1px solid #aaa",
+                    margin: "2px",
+                  }}
+                  disabled={!historyStatus.canRedo}
+                  onClick={() => redo()}
+                >
+                  <Redo color="primary" sx={{ fontSize: 32 }} />
+                </IconButton>
               </div>{" "}
             </Pane>
           </Grid>
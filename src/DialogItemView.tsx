@@ -10,7 +10,7 @@ import { invoke } from "@tauri-apps/api/tauri";
 import { useState } from "react";
 import { Radio, TailSpin } from "react-loader-spinner";
 import { fileSrc } from "./FileSrcUtil";
-import { ItemData } from "./TierlistData";
+import { ItemData, ScrapedItem } from "./TierlistData";
 
 const DialogItemView: React.FC<{
   itemData: ItemData | null;
@@ -28,15 +28,17 @@ const DialogItemView: React.FC<{
 
   async function getProductInfo(amazonUrl: string) {
     setNowLoading(true);
-    const [imgPath, title] = await invoke<string[]>("scrape_amazon", {
-      amazonUrl,
+    const scraped = await invoke<ScrapedItem>("scrape_item", {
+      url: amazonUrl,
     }).catch((e) => {
       console.log(e);
       // TODO: Error Notification
-      return ["", ""];
+      return null;
     });
     setNowLoading(false);
-    props.onChange({ ...curData, name: title, thumb: imgPath });
+    if (scraped !== null) {
+      props.onChange({ ...curData, name: scraped.name, thumb: scraped.thumb });
+    }
   }
 
   return (
@@ -46,7 +48,7 @@ const DialogItemView: React.FC<{
           <TextField
             fullWidth
             variant="standard"
-            label="Amazon URL"
+            label="URL"
             value={amazonUrl}
             onChange={(e) =>
               props.onChange({ ...curData, url: e.target.value })
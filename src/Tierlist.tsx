@@ -13,7 +13,6 @@ import {
   MoveUpOutlined,
 } from "@mui/icons-material";
 import { Button, IconButton, List, ListItem, Stack } from "@mui/material";
-import { hsv } from "color-convert";
 import { useState } from "react";
 import ClickToEditField from "./ClickToEditField";
 import DialogDeleteItem from "./DialogDeleteItem";
@@ -22,7 +21,7 @@ import DialogEdit from "./DialogEdit";
 import { fileSrc } from "./FileSrcUtil";
 import { useItemDeletion, useItemEdit } from "./ItemUpdate";
 import "./Tierlist.css";
-import { Item, Tier } from "./TierlistData";
+import { Item, Tier, TierStyle } from "./TierlistData";
 
 const TierItem: React.FC<{
   item: Item;
@@ -82,8 +81,9 @@ const TierContainer: React.FC<{
   title: string;
   items: Item[];
   activeId: UniqueIdentifier | null;
-  bgColor: string;
+  style: TierStyle;
   onTierTitleChange: (id: string, title: string) => void;
+  onTierStyleChange: (id: string, style: TierStyle) => void;
   onTierMove: (id: string, direction: "up" | "down") => void;
   onTierDelete: (id: string) => void;
   onEditButtonClick: (item: Item) => void;
@@ -98,14 +98,19 @@ const TierContainer: React.FC<{
       <div
         className="tier-title-box"
         style={{
-          backgroundColor: `#${props.bgColor}`,
+          backgroundColor: props.style.color,
+          color: props.style.textColor ?? undefined,
         }}
         onMouseOver={() => setIsHovering(true)}
         onMouseOut={() => setIsHovering(false)}
       >
         <ClickToEditField
           text={props.title}
-          renderText={(text) => <span className="tier-title">{text}</span>}
+          renderText={(text) => (
+            <span className="tier-title">
+              {props.style.icon ? `${props.style.icon} ${text}` : text}
+            </span>
+          )}
           onChange={(text) => props.onTierTitleChange(props.id, text)}
         />
         {isHovering ? (
@@ -132,6 +137,19 @@ const TierContainer: React.FC<{
             <DeleteOutlined color="primary" />
           </IconButton>
         ) : null}
+        {isHovering ? (
+          <input
+            type="color"
+            className="tier-color-input"
+            value={props.style.color}
+            onChange={(e) =>
+              props.onTierStyleChange(props.id, {
+                ...props.style,
+                color: e.target.value,
+              })
+            }
+          />
+        ) : null}
       </div>
       <SortableContext
         id={props.id}
@@ -168,6 +186,7 @@ const Tierlist: React.FC<{
   activeId: UniqueIdentifier | null;
   onListTitleChange: (title: string) => void;
   onTierTitleChange: (id: string, title: string) => void;
+  onTierStyleChange: (id: string, style: TierStyle) => void;
   onTierMove: (id: string, direction: "up" | "down") => void;
   onTierAdd: () => void;
   onTierDelete: (id: string) => void;
@@ -202,8 +221,6 @@ const Tierlist: React.FC<{
     handleEditDialogClose,
   } = useItemEdit(props.onEditItem);
 
-  const numTier = props.tiers.length;
-
   return (
     <div id="tierlist-pane">
       <ClickToEditField
@@ -212,15 +229,16 @@ const Tierlist: React.FC<{
         onChange={(text) => props.onListTitleChange(text)}
       />
       <div id="tierlist">
-        {props.tiers.map((tier, idx) => (
+        {props.tiers.map((tier) => (
           <TierContainer
             id={tier.id}
             key={tier.id}
             title={tier.title}
             items={tier.items}
             activeId={props.activeId}
-            bgColor={hsv.hex([(idx / numTier) * 360, 27, 100])}
+            style={tier.style}
             onTierTitleChange={props.onTierTitleChange}
+            onTierStyleChange={props.onTierStyleChange}
             onTierMove={props.onTierMove}
             onTierDelete={(id) => openDeleteTierDialog(id)}
             onEditButtonClick={handleItemEditButtonClick}
@@ -4,22 +4,52 @@ export class Item {
   url: string;
   thumb: string | null;
   memo: string;
+  tags: string[];
+  // Values keyed by FieldDef id, normalized by the backend
+  fields: { [fieldId: number]: string };
+  // UNIX time in seconds, 0 if unknown
+  createdAt: number;
+  updatedAt: number;
+  movedAt: number;
 
   constructor(
     id: number,
     name: string,
     url: string,
     thumb: string | null,
-    memo: string
+    memo: string,
+    tags: string[],
+    fields: { [fieldId: number]: string },
+    createdAt: number,
+    updatedAt: number,
+    movedAt: number
   ) {
     this.id = id;
     this.name = name;
     this.url = url;
     this.thumb = thumb;
     this.memo = memo;
+    this.tags = tags;
+    this.fields = fields;
+    this.createdAt = createdAt;
+    this.updatedAt = updatedAt;
+    this.movedAt = movedAt;
   }
 }
 
+export interface TagCount {
+  name: string;
+  count: number;
+}
+
+export type FieldType = "text" | "number" | "date" | "url" | "boolean";
+
+export interface FieldDef {
+  id: number;
+  name: string;
+  type: FieldType;
+}
+
 export interface ItemData {
   name: string;
   url: string;
@@ -42,17 +72,27 @@ export class Tier {
   numericId: number;
   title: string;
   items: Item[];
+  style: TierStyle;
 
-  constructor(numericId: number, title: string, items: Item[]) {
+  constructor(
+    numericId: number,
+    title: string,
+    items: Item[],
+    style: TierStyle
+  ) {
     this.id = `t${numericId}`;
     this.numericId = numericId;
     this.title = title;
     this.items = items;
+    this.style = style;
   }
 }
 
-function toBackendTier(t: Tier) {
-  return { id: t.numericId, title: t.title, items: t.items.map((it) => it.id) };
+// Colors are "#rrggbb"; textColor and icon are optional
+export interface TierStyle {
+  color: string;
+  textColor: string | null;
+  icon: string | null;
 }
 
 export interface ItemList {
@@ -60,12 +100,23 @@ export interface ItemList {
   items: Item[];
 }
 
-export interface BackendTier {
+export interface BackendTier extends TierStyle {
   id: number;
   title: string;
   items: number[];
 }
 
+export interface TierlistInfo {
+  id: number;
+  title: string;
+}
+
+export interface OpenedDbFile {
+  path: string;
+  lists: TierlistInfo[];
+  listId: number | null;
+}
+
 export interface BackendTierlist {
   title: string;
   tiers: BackendTier[];
@@ -73,30 +124,8 @@ export interface BackendTierlist {
   items: Item[];
   itemsPool: number[];
   itemMaxId: number;
-}
-
-export function toBackendTierlist(
-  title: string,
-  pool: ItemPool,
-  tiers: Tier[]
-): BackendTierlist {
-  const items: Item[] = [];
-  pool.items.forEach((it) => {
-    items.push(it);
-  });
-  tiers.forEach((t) => {
-    t.items.forEach((it) => {
-      items.push(it);
-    });
-  });
-  return {
-    title,
-    tiers: tiers.map((t) => toBackendTier(t)),
-    tierMaxId: Math.max(0, ...tiers.map((t) => t.numericId)),
-    items,
-    itemsPool: pool.items.map((it) => it.id),
-    itemMaxId: Math.max(0, ...pool.items.map((it) => it.id)),
-  };
+  fields: FieldDef[];
+  fieldMaxId: number;
 }
 
 export function fromBackendTierlist(tierlist: BackendTierlist): {
@@ -115,8 +144,143 @@ export function fromBackendTierlist(tierlist: BackendTierlist): {
       return new Tier(
         bt.id,
         bt.title,
-        bt.items.map((id) => itemsById[id])
+        bt.items.map((id) => itemsById[id]),
+        { color: bt.color, textColor: bt.textColor, icon: bt.icon }
       );
     }),
   };
 }
+
+export interface TemplateTier extends TierStyle {
+  title: string;
+}
+
+// Built-in templates cannot be overwritten or deleted
+export interface Template {
+  name: string;
+  builtin: boolean;
+  tiers: TemplateTier[];
+}
+
+// Part of a search result; matched parts should be highlighted
+export interface Segment {
+  text: string;
+  matched: boolean;
+}
+
+export interface SearchHit {
+  id: number;
+  name: Segment[];
+  memo: Segment[];
+}
+
+export type SortKey =
+  | { kind: "name" }
+  | { kind: "field"; field: number }
+  | { kind: "added" }
+  | { kind: "updated" }
+  | { kind: "moved" }
+  | { kind: "random" };
+
+export type SortTarget =
+  | { kind: "tier"; id: number }
+  | { kind: "allTiers" }
+  | { kind: "pool" };
+
+// With stable set, items that compare equal keep their manual order
+export interface SortOptions {
+  key: SortKey;
+  descending: boolean;
+  stable: boolean;
+}
+
+// Where an item was; "absent" means before it was added or after it was deleted
+export type Place =
+  | { kind: "absent" }
+  | { kind: "pool" }
+  | { kind: "tier"; id: number; title: string };
+
+export interface Placement {
+  item: number;
+  name: string;
+  from: Place;
+  to: Place;
+  pos: number | null;
+  at: number;
+}
+
+export interface ItemChange {
+  item: number;
+  name: string;
+  from: Place;
+  to: Place;
+  moves: number;
+  at: number;
+}
+
+export interface SnapshotInfo {
+  id: number;
+  name: string;
+  createdAt: number;
+}
+
+// "current" is the list being edited, including unsaved changes
+export type Version = { kind: "current" } | { kind: "snapshot"; id: number };
+
+export interface ItemDiff {
+  item: number;
+  name: string;
+  from: Place;
+  to: Place;
+}
+
+export interface ListDiff {
+  added: ItemDiff[];
+  removed: ItemDiff[];
+  promoted: ItemDiff[];
+  demoted: ItemDiff[];
+}
+
+// a tier list in another database file; listId may be null if the file has only one
+export interface CompareSource {
+  path: string;
+  listId: number | null;
+}
+
+export interface ComparedItem {
+  name: string;
+  left: Place;
+  right: Place;
+  // right minus left tier position, where 0 is the top tier and 1 the bottom one;
+  // null unless both placed it in a tier
+  diff: number | null;
+}
+
+export interface Comparison {
+  leftTitle: string;
+  rightTitle: string;
+  // 0 to 1
+  agreement: number | null;
+  both: ComparedItem[];
+  onlyLeft: ComparedItem[];
+  onlyRight: ComparedItem[];
+}
+
+export interface RecoveryInfo {
+  savedAt: number;
+  path: string | null;
+  listId: number | null;
+  title: string;
+}
+
+export interface HistoryStatus {
+  canUndo: boolean;
+  canRedo: boolean;
+}
+
+export interface ScrapedItem {
+  name: string;
+  url: string;
+  thumb: string | null;
+  description: string | null;
+}